@@ -0,0 +1,213 @@
+//! Concurrent `get_recipe` throughput through the connection pool, compared
+//! to a single connection behind a mutex with the queries running on the
+//! async workers, like before the pool.
+//!
+//! Run with `cargo bench --features ssr --bench get_recipe`.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use nom::db::Pool;
+use nom::export::collection::import_collection;
+use nom::export::{ExportDocument, ExportedRecipe, FORMAT_VERSION, ImportMode};
+use nom::recipe::{Ingredient, load_recipe};
+use rusqlite::Connection;
+use tokio::runtime::Runtime;
+use tokio::sync::{Mutex, oneshot};
+use tokio::task::JoinSet;
+
+const RECIPES: i64 = 200;
+
+/// Requests in flight at the same time.
+const CONCURRENT: i64 = 32;
+
+/// How long the slow write, e.g. an import, holds on to the database.
+const WRITE_TIME: Duration = Duration::from_millis(20);
+
+fn ingredient(name: &str) -> Ingredient {
+    Ingredient {
+        quantity: Some(2.0),
+        unit: Some("el".to_string()),
+        name: name.to_string(),
+        note: None,
+    }
+}
+
+fn seed(path: &Path) {
+    let mut conn = Connection::open(path).unwrap();
+    nom::db::configure_writer(&conn).unwrap();
+    nom::migrations::migrate(&mut conn, false).unwrap();
+
+    let recipes = (0..RECIPES)
+        .map(|i| ExportedRecipe {
+            title: format!("Recept {i}"),
+            servings: Some(4),
+            prep_time: Some(15),
+            cook_time: Some(30),
+            ingredients: ["bloem", "melk", "eieren", "boter", "zout", "suiker"]
+                .into_iter()
+                .map(ingredient)
+                .collect(),
+            instructions: "Meng alles.\nBak in de pan.\nEet smakelijk.".to_string(),
+            tags: vec!["ontbijt".to_string(), "zoet".to_string()],
+        })
+        .collect();
+
+    let document = ExportDocument {
+        version: FORMAT_VERSION,
+        recipes,
+    };
+
+    import_collection(&mut conn, document, ImportMode::Replace).unwrap();
+}
+
+fn remove_db(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+}
+
+/// Runs `get` for `CONCURRENT` recipes at once.
+async fn concurrently<F, Fut>(get: F)
+where
+    F: Fn(i64) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut requests = JoinSet::new();
+
+    for id in 1..=CONCURRENT {
+        requests.spawn(get(id * (RECIPES / CONCURRENT)));
+    }
+
+    requests.join_all().await;
+}
+
+/// How `get_recipe` worked before the pool.
+async fn single_get(single: Arc<Mutex<Connection>>, id: i64) {
+    let db = single.lock().await;
+    load_recipe(&db, id).unwrap().unwrap();
+}
+
+/// What `get_recipe` does.
+async fn pool_get(pool: Pool, id: i64) {
+    pool.read(move |db| load_recipe(db, id))
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+fn bench_get_recipe(c: &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("nom-bench-{}.db", std::process::id()));
+
+    remove_db(&path);
+    seed(&path);
+
+    let runtime = Runtime::new().unwrap();
+    let pool = Pool::open(path.to_str().unwrap(), 4).unwrap();
+    let single = Arc::new(Mutex::new(Connection::open(&path).unwrap()));
+
+    let mut group = c.benchmark_group("get_recipe");
+    group.throughput(Throughput::Elements(CONCURRENT as u64));
+
+    group.bench_function("single connection", |b| {
+        b.to_async(&runtime)
+            .iter(|| concurrently(|id| single_get(single.clone(), id)));
+    });
+    group.bench_function("pool", |b| {
+        b.to_async(&runtime)
+            .iter(|| concurrently(|id| pool_get(pool.clone(), id)));
+    });
+
+    group.finish();
+
+    // Only the reads are timed, not the write they run next to
+    let mut group = c.benchmark_group("get_recipe during a slow write");
+    group.throughput(Throughput::Elements(CONCURRENT as u64));
+    group.sample_size(20);
+
+    group.bench_function("single connection", |b| {
+        b.to_async(&runtime).iter_custom(|iters| {
+            let single = single.clone();
+
+            async move {
+                let mut total = Duration::ZERO;
+
+                for _ in 0..iters {
+                    let (started, writing) = oneshot::channel();
+                    let writer = tokio::spawn({
+                        let single = single.clone();
+
+                        async move {
+                            let mut db = single.lock().await;
+                            let transaction = db.transaction().unwrap();
+                            transaction
+                                .execute("UPDATE recipes SET servings = 4 WHERE id = 1;", ())
+                                .unwrap();
+                            started.send(()).unwrap();
+
+                            std::thread::sleep(WRITE_TIME);
+                            transaction.commit().unwrap();
+                        }
+                    });
+                    writing.await.unwrap();
+
+                    let start = Instant::now();
+                    concurrently(|id| single_get(single.clone(), id)).await;
+                    total += start.elapsed();
+
+                    writer.await.unwrap();
+                }
+
+                total
+            }
+        });
+    });
+    group.bench_function("pool", |b| {
+        b.to_async(&runtime).iter_custom(|iters| {
+            let pool = pool.clone();
+
+            async move {
+                let mut total = Duration::ZERO;
+
+                for _ in 0..iters {
+                    let (started, writing) = oneshot::channel();
+                    let writer = tokio::spawn({
+                        let pool = pool.clone();
+
+                        async move {
+                            pool.write(move |db| {
+                                let transaction = db.transaction().unwrap();
+                                transaction
+                                    .execute("UPDATE recipes SET servings = 4 WHERE id = 1;", ())
+                                    .unwrap();
+                                started.send(()).unwrap();
+
+                                std::thread::sleep(WRITE_TIME);
+                                transaction.commit().unwrap();
+                            })
+                            .await
+                        }
+                    });
+                    writing.await.unwrap();
+
+                    let start = Instant::now();
+                    concurrently(|id| pool_get(pool.clone(), id)).await;
+                    total += start.elapsed();
+
+                    writer.await.unwrap();
+                }
+
+                total
+            }
+        });
+    });
+
+    group.finish();
+
+    remove_db(&path);
+}
+
+criterion_group!(benches, bench_get_recipe);
+criterion_main!(benches);
@@ -0,0 +1,590 @@
+//! The REST API under `/api/v1`, for scripts and apps. The server functions
+//! are for the web app itself and change with it, this keeps its URLs and
+//! JSON the same. Errors are JSON as well, as `{"error": "..."}`.
+//!
+//! It is described by an OpenAPI document at `/api/openapi.json`, generated
+//! from the handlers and types here.
+
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{Extension, FromRef, Path, Query, State};
+use axum::http::StatusCode;
+use axum::http::header::LOCATION;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+use crate::auth::{Role, User, allowed};
+use crate::recipe::{Ingredient, ListedRecipe, Recipe};
+use crate::store::{Store, StoreError, Update};
+
+/// Where the API is served.
+pub const PREFIX: &str = "/api/v1";
+
+/// Where the OpenAPI document of the API is served.
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+
+const DEFAULT_PER_PAGE: u32 = 20;
+const MAX_PER_PAGE: u32 = 100;
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn unknown_recipe(id: i64) -> Self {
+        ApiError::new(StatusCode::NOT_FOUND, format!("Unknown recipe {id}"))
+    }
+}
+
+/// What the API answers with when something is wrong.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiErrorBody {
+    pub error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(ApiErrorBody {
+                error: self.message,
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Database errors are logged, the client only gets to know something went
+/// wrong.
+impl From<StoreError> for ApiError {
+    fn from(err: StoreError) -> Self {
+        leptos::logging::error!("API request failed: {err}");
+
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// Fails unless the user from the auth middleware has at least `role`.
+fn require_role(user: &Option<Extension<User>>, role: Role) -> ApiResult<()> {
+    if allowed(user.as_ref().map(|Extension(user)| user), role) {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("This needs the {role} role"),
+        ))
+    }
+}
+
+/// A recipe as returned by the API, with its id.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiRecipe {
+    pub id: i64,
+    #[serde(flatten)]
+    pub recipe: Recipe,
+}
+
+/// An ingredient as sent to the API, either a line like "200 g bloem,
+/// gezeefd" or already split up.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum ApiIngredient {
+    Line(String),
+    Parsed(Ingredient),
+}
+
+/// A recipe as sent to the API to create or replace one. Images are
+/// uploaded in the web app.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ApiRecipeInput {
+    pub title: String,
+    #[serde(default)]
+    pub servings: Option<u32>,
+    /// In minutes
+    #[serde(default)]
+    pub prep_time: Option<u32>,
+    /// In minutes
+    #[serde(default)]
+    pub cook_time: Option<u32>,
+    #[serde(default)]
+    pub ingredients: Vec<ApiIngredient>,
+    #[serde(default)]
+    pub instructions: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The version of the recipe the changes are based on, needed when
+    /// replacing. If it changed since, replacing fails with 409. Ignored
+    /// when creating.
+    #[serde(default)]
+    pub version: Option<u32>,
+}
+
+impl ApiRecipeInput {
+    fn into_recipe(self) -> ApiResult<Recipe> {
+        let title = self.title.trim();
+
+        if title.is_empty() {
+            return Err(ApiError::bad_request("Recipes need a title"));
+        }
+
+        let mut ingredients = Vec::with_capacity(self.ingredients.len());
+
+        for ingredient in self.ingredients {
+            match ingredient {
+                ApiIngredient::Line(line) if line.trim().is_empty() => {}
+                ApiIngredient::Line(line) => ingredients.push(Ingredient::parse(line.trim())),
+                ApiIngredient::Parsed(ingredient) if ingredient.name.trim().is_empty() => {
+                    return Err(ApiError::bad_request("Ingredients need a name"));
+                }
+                ApiIngredient::Parsed(ingredient) => ingredients.push(ingredient),
+            }
+        }
+
+        Ok(Recipe {
+            title: title.to_string(),
+            servings: self.servings,
+            prep_time: self.prep_time,
+            cook_time: self.cook_time,
+            ingredients,
+            instructions: self.instructions,
+            tags: self.tags,
+            images: Vec::new(),
+            version: 0,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiSearchResult {
+    pub id: i64,
+    pub title: String,
+    /// Id of the main image
+    pub thumbnail: Option<i64>,
+    /// Where the recipe matched, as plain text
+    pub snippet: String,
+}
+
+/// One page of a longer list. Pages count from 1.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiPage<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiItems<T> {
+    pub items: Vec<T>,
+}
+
+/// Tags are passed comma separated, as `?tags=vega,snel`.
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListParams {
+    /// Counting from 1
+    page: Option<u32>,
+    /// 20 by default, at most 100
+    per_page: Option<u32>,
+    /// Only recipes having all these tags, comma separated
+    #[serde(default)]
+    tags: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchParams {
+    /// Words to look for in titles, ingredients and instructions
+    #[serde(default)]
+    q: String,
+    /// Only recipes having all these tags, comma separated
+    #[serde(default)]
+    tags: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RandomParams {
+    /// Only recipes having all these tags, comma separated
+    #[serde(default)]
+    tags: String,
+}
+
+/// The recipes having all `tags`, in the order they were added.
+#[utoipa::path(
+    get,
+    path = "/recipes",
+    operation_id = "list_recipes",
+    params(ListParams),
+    responses(
+        (status = 200, body = ApiPage<ListedRecipe>),
+        (status = 400, description = "Invalid page", body = ApiErrorBody),
+    ),
+)]
+async fn list(
+    State(store): State<Store>,
+    user: Option<Extension<User>>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> ApiResult<Json<ApiPage<ListedRecipe>>> {
+    require_role(&user, Role::Viewer)?;
+
+    let Query(params) = params?;
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE);
+
+    if page == 0 {
+        return Err(ApiError::bad_request("Pages count from 1"));
+    }
+
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(ApiError::bad_request(format!(
+            "per_page has to be between 1 and {MAX_PER_PAGE}"
+        )));
+    }
+
+    let tags = split_tags(&params.tags);
+    let offset = (page - 1).saturating_mul(per_page);
+
+    let recipes = store.list(tags.clone(), Some(per_page), offset).await?;
+    let total = store.count(tags).await?;
+
+    Ok(Json(ApiPage {
+        items: recipes,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+/// Searches the recipes having all `tags`, the best matches first.
+#[utoipa::path(
+    get,
+    path = "/recipes/search",
+    operation_id = "search_recipes",
+    params(SearchParams),
+    responses((status = 200, body = ApiItems<ApiSearchResult>)),
+)]
+async fn search(
+    State(store): State<Store>,
+    user: Option<Extension<User>>,
+    params: Result<Query<SearchParams>, QueryRejection>,
+) -> ApiResult<Json<ApiItems<ApiSearchResult>>> {
+    require_role(&user, Role::Viewer)?;
+
+    let Query(params) = params?;
+
+    let results = store.search(params.q, split_tags(&params.tags)).await?;
+
+    Ok(Json(ApiItems {
+        items: results
+            .into_iter()
+            .map(|result| ApiSearchResult {
+                id: result.id,
+                title: result.title,
+                thumbnail: result.thumbnail,
+                snippet: result.snippet.into_iter().map(|part| part.text).collect(),
+            })
+            .collect(),
+    }))
+}
+
+/// A random recipe having all `tags`.
+#[utoipa::path(
+    get,
+    path = "/recipes/random",
+    operation_id = "random_recipe",
+    params(RandomParams),
+    responses(
+        (status = 200, body = ApiRecipe),
+        (status = 404, description = "No recipes with these tags", body = ApiErrorBody),
+    ),
+)]
+async fn random(
+    State(store): State<Store>,
+    user: Option<Extension<User>>,
+    params: Result<Query<RandomParams>, QueryRejection>,
+) -> ApiResult<Json<ApiRecipe>> {
+    require_role(&user, Role::Viewer)?;
+
+    let Query(params) = params?;
+
+    let Some(id) = store.random(split_tags(&params.tags)).await? else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No recipes with these tags",
+        ));
+    };
+
+    let recipe = store.get(id).await?.ok_or(ApiError::unknown_recipe(id))?;
+
+    Ok(Json(ApiRecipe { id, recipe }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/recipes/{id}",
+    operation_id = "get_recipe",
+    params(("id" = i64, Path, description = "Id of the recipe")),
+    responses(
+        (status = 200, body = ApiRecipe),
+        (status = 404, description = "Unknown recipe", body = ApiErrorBody),
+    ),
+)]
+async fn show(
+    State(store): State<Store>,
+    user: Option<Extension<User>>,
+    id: Result<Path<i64>, PathRejection>,
+) -> ApiResult<Json<ApiRecipe>> {
+    require_role(&user, Role::Viewer)?;
+
+    let Path(id) = id?;
+
+    let recipe = store.get(id).await?;
+
+    recipe
+        .map(|recipe| Json(ApiRecipe { id, recipe }))
+        .ok_or(ApiError::unknown_recipe(id))
+}
+
+/// Adds a recipe, answered with the new recipe and where to find it.
+#[utoipa::path(
+    post,
+    path = "/recipes",
+    operation_id = "create_recipe",
+    request_body = ApiRecipeInput,
+    responses(
+        (status = 201, body = ApiRecipe, headers(("Location" = String, description = "Where the new recipe is"))),
+        (status = 400, description = "Invalid recipe", body = ApiErrorBody),
+        (status = 403, description = "Needs the editor role", body = ApiErrorBody),
+    ),
+)]
+async fn create(
+    State(store): State<Store>,
+    user: Option<Extension<User>>,
+    body: Result<Json<ApiRecipeInput>, JsonRejection>,
+) -> ApiResult<Response> {
+    require_role(&user, Role::Editor)?;
+
+    let Json(input) = body?;
+    let recipe = input.into_recipe()?;
+
+    let user_id = user.map(|Extension(user)| user.id);
+
+    let id = store.create(recipe, user_id).await?;
+    let created = store.get(id).await?.ok_or(ApiError::unknown_recipe(id))?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, format!("{PREFIX}/recipes/{id}"))],
+        Json(ApiRecipe {
+            id,
+            recipe: created,
+        }),
+    )
+        .into_response())
+}
+
+/// Replaces everything of a recipe but its images, only if it's still at the
+/// given version.
+#[utoipa::path(
+    put,
+    path = "/recipes/{id}",
+    operation_id = "replace_recipe",
+    params(("id" = i64, Path, description = "Id of the recipe")),
+    request_body = ApiRecipeInput,
+    responses(
+        (status = 200, body = ApiRecipe),
+        (status = 400, description = "Invalid recipe, or no version", body = ApiErrorBody),
+        (status = 403, description = "Needs the editor role", body = ApiErrorBody),
+        (status = 404, description = "Unknown recipe", body = ApiErrorBody),
+        (status = 409, description = "The recipe changed since the given version", body = ApiErrorBody),
+    ),
+)]
+async fn replace(
+    State(store): State<Store>,
+    user: Option<Extension<User>>,
+    id: Result<Path<i64>, PathRejection>,
+    body: Result<Json<ApiRecipeInput>, JsonRejection>,
+) -> ApiResult<Json<ApiRecipe>> {
+    require_role(&user, Role::Editor)?;
+
+    let Path(id) = id?;
+    let Json(input) = body?;
+    let version = input
+        .version
+        .ok_or_else(|| ApiError::bad_request("Replacing a recipe needs its version"))?;
+    let recipe = input.into_recipe()?;
+
+    let user_id = user.map(|Extension(user)| user.id);
+
+    match store.update(id, recipe, Some(version), user_id).await? {
+        Update::Updated(recipe) => Ok(Json(ApiRecipe { id, recipe })),
+        Update::Conflict(current) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!(
+                "Recipe {id} changed since version {version}, it is at version {} now",
+                current.version
+            ),
+        )),
+        Update::UnknownRecipe => Err(ApiError::unknown_recipe(id)),
+    }
+}
+
+/// Deletes a recipe with its images.
+#[utoipa::path(
+    delete,
+    path = "/recipes/{id}",
+    operation_id = "delete_recipe",
+    params(("id" = i64, Path, description = "Id of the recipe")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 403, description = "Needs the admin role", body = ApiErrorBody),
+        (status = 404, description = "Unknown recipe", body = ApiErrorBody),
+    ),
+)]
+async fn remove(
+    State(store): State<Store>,
+    user: Option<Extension<User>>,
+    id: Result<Path<i64>, PathRejection>,
+) -> ApiResult<StatusCode> {
+    require_role(&user, Role::Admin)?;
+
+    let Path(id) = id?;
+
+    if !store.delete(id).await? {
+        return Err(ApiError::unknown_recipe(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn not_found() -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, "Unknown API endpoint")
+}
+
+async fn method_not_allowed() -> ApiError {
+    ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        "Method not allowed on this endpoint",
+    )
+}
+
+/// Accepts the same logins as the rest of nom.
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("An API token, made on the settings page"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "password",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+        );
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list, search, random, show, create, replace, remove))]
+struct RecipesApi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "nom",
+        version = "1",
+        description = "Recipes in nom",
+        license(name = "GPL-3.0"),
+    ),
+    nest((path = "/api/v1", api = RecipesApi, tags = ["recipes"])),
+    components(schemas(ApiErrorBody)),
+    modifiers(&Security),
+    security(("token" = []), ("password" = [])),
+)]
+struct ApiDoc;
+
+/// The OpenAPI document of the API.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+/// The API routes, to be nested under [`PREFIX`]. The auth middleware has
+/// put the user in the request extensions.
+fn routes<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Store: FromRef<S>,
+{
+    Router::new()
+        .route("/recipes", get(list).post(create))
+        .route("/recipes/search", get(search))
+        .route("/recipes/random", get(random))
+        .route("/recipes/{id}", get(show).put(replace).delete(remove))
+        .method_not_allowed_fallback(method_not_allowed)
+        .fallback(not_found)
+}
+
+/// The API with its OpenAPI document, as served.
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Store: FromRef<S>,
+{
+    let document = openapi();
+
+    Router::new()
+        .route(OPENAPI_PATH, get(move || async move { Json(document) }))
+        .nest(PREFIX, routes())
+}
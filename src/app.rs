@@ -2,16 +2,28 @@ use leptos::prelude::*;
 use leptos::reactive::spawn_local;
 use leptos_meta::{MetaTags, Stylesheet, Title, provide_meta_context};
 use leptos_router::components::{A, Route, Router, Routes};
-use leptos_router::hooks::use_url;
+use leptos_router::hooks::{use_query_map, use_url};
 use leptos_router::{NavigateOptions, path};
 use web_sys::MouseEvent;
 
+use crate::auth::{CurrentUser, RequireRole, Role, logout, provide_current_user};
+use crate::pages::cookbook::CookbookPage;
 use crate::pages::editrecipe::EditRecipePage;
 use crate::pages::home::HomePage;
+use crate::pages::import::ImportPage;
+use crate::pages::login::LoginPage;
 use crate::pages::newrecipe::NewRecipePage;
+use crate::pages::pantry::PantryPage;
+use crate::pages::plan::PlanPage;
+use crate::pages::print::PrintRecipePage;
 use crate::pages::recipe::RecipePage;
+use crate::pages::settings::SettingsPage;
+use crate::pages::shared::SharedRecipePage;
+use crate::pages::shopping::ShoppingPage;
 use crate::pages::trmnl::TrmnlPage;
+use crate::pages::users::UsersPage;
 use crate::recipe::random_recipe;
+use crate::tags::parse_tags_param;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     view! {
@@ -35,6 +47,7 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
+    provide_current_user();
 
     view! {
         // injects a stylesheet into the document <head>
@@ -52,8 +65,18 @@ pub fn App() -> impl IntoView {
                     <Route path=path!("/") view=HomePage/>
                     <Route path=path!("/trmnl") view=TrmnlPage/>
                     <Route path=path!("/recipe/:id") view=RecipePage/>
+                    <Route path=path!("/recipe/:id/print") view=PrintRecipePage/>
                     <Route path=path!("/edit/:id") view=EditRecipePage/>
                     <Route path=path!("/new") view=NewRecipePage/>
+                    <Route path=path!("/pantry") view=PantryPage/>
+                    <Route path=path!("/plan") view=PlanPage/>
+                    <Route path=path!("/shopping") view=ShoppingPage/>
+                    <Route path=path!("/import") view=ImportPage/>
+                    <Route path=path!("/cookbook") view=CookbookPage/>
+                    <Route path=path!("/login") view=LoginPage/>
+                    <Route path=path!("/users") view=UsersPage/>
+                    <Route path=path!("/settings") view=SettingsPage/>
+                    <Route path=path!("/s/:token") view=SharedRecipePage/>
                 </Routes>
             </main>
         </Router>
@@ -63,40 +86,93 @@ pub fn App() -> impl IntoView {
 #[component]
 fn NavBar() -> impl IntoView {
     let url = use_url();
-    let url_is_trmnl = move || url.get().path() == "/trmnl";
+    // The TRMNL display, print views and shared recipes show nothing but the
+    // recipe, and there's nothing to navigate to before logging in
+    let hide_navbar = move || {
+        let url = url.get();
 
-    let random_recipe = |ev: MouseEvent| {
+        url.path() == "/trmnl"
+            || url.path() == "/login"
+            || url.path().ends_with("/print")
+            || url.path().starts_with("/s/")
+    };
+
+    let CurrentUser(current_user) = expect_context();
+
+    let logout = move |_| {
+        spawn_local(async {
+            let window = web_sys::window().unwrap();
+
+            match logout().await {
+                Ok(()) => window.location().set_href("/login").unwrap(),
+                Err(err) => window
+                    .alert_with_message(&format!("Uitloggen mislukt: {err}"))
+                    .unwrap(),
+            }
+        });
+    };
+
+    let query = use_query_map();
+
+    // Picks from the recipes currently filtered on, if any
+    let random_recipe = move |ev: MouseEvent| {
         ev.prevent_default();
 
+        let tags = parse_tags_param(query.read_untracked().get("tags"));
+
         spawn_local(async {
-            let random_recipe_id = random_recipe().await.unwrap();
+            let random_recipe_id = random_recipe(tags).await;
 
             match random_recipe_id {
-                Some(id) => {
+                Ok(Some(id)) => {
                     let navigate = leptos_router::hooks::use_navigate();
 
                     navigate(format!("/recipe/{id}").as_str(), NavigateOptions::default());
                 }
-                None => {
+                Ok(None) => {
                     web_sys::window()
                         .unwrap()
                         .alert_with_message("Er zijn nog geen recepted. Voeg er eentje toe!")
                         .unwrap();
                 }
+                Err(err) => {
+                    web_sys::window()
+                        .unwrap()
+                        .alert_with_message(err.friendly())
+                        .unwrap();
+                }
             }
         });
     };
 
     view! {
         {
-            move || if url_is_trmnl() {
+            move || if hide_navbar() {
                 ().into_any()
             } else {
                 view! {
                     <nav class="nom-navbar">
                         <A class:link-button href="/">"Home"</A>
-                        <A class:link-button href="/new">"Nieuw recept"</A>
+                        <RequireRole role=Role::Editor>
+                            <A class:link-button href="/new">"Nieuw recept"</A>
+                        </RequireRole>
                         <button class:link-button on:click=random_recipe>"Random"</button>
+                        <A class:link-button href="/pantry">"Wat kan ik maken?"</A>
+                        <A class:link-button href="/plan">"Weekmenu"</A>
+                        <A class:link-button href="/shopping">"Boodschappen"</A>
+                        <RequireRole role=Role::Admin>
+                            <A class:link-button href="/import">"Exporteren"</A>
+                        </RequireRole>
+                        <Transition>
+                            {move || current_user.get().map(|user| match user {
+                                Some(user) => view! {
+                                    <A class:link-button href="/settings">{user.username}</A>
+                                    <button class:link-button on:click=logout>"Uitloggen"</button>
+                                }
+                                .into_any(),
+                                None => view! { <A class:link-button href="/users">"Gebruikers"</A> }.into_any(),
+                            })}
+                        </Transition>
                     </nav>
                 }.into_any()
             }
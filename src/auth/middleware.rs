@@ -0,0 +1,293 @@
+use std::net::IpAddr;
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{ACCEPT, COOKIE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::headers::authorization::{Basic, Bearer};
+use axum_extra::headers::{Authorization, HeaderMapExt};
+use chrono::Utc;
+
+use super::throttle::{self, LoginThrottle};
+use super::tokens::TokenAuth;
+use super::{LoginError, SESSION_COOKIE, User, authenticate, store};
+use crate::db::Pool;
+
+/// What can be reached without logging in, to be able to log in, and the
+/// description of the REST API. Shared recipes under `/s/` check their link
+/// themselves.
+const PUBLIC_PATHS: &[&str] = &[
+    "/login",
+    "/api/login",
+    "/api/shared_recipe",
+    crate::api::OPENAPI_PATH,
+    "/favicon.ico",
+];
+
+/// Whether `path` has `.` or `..` in it, which the router resolves. Paths
+/// that are allowed by their prefix must not, or `/s/x/../../recipe/1` would
+/// be a recipe.
+pub(super) fn has_dot_segment(path: &str) -> bool {
+    path.split(['/', '\\']).any(|segment| {
+        matches!(
+            segment.to_ascii_lowercase().replace("%2e", ".").as_str(),
+            "." | ".."
+        )
+    })
+}
+
+/// Whether `path` can be reached without logging in.
+fn is_public(path: &str) -> bool {
+    !has_dot_segment(path)
+        && (PUBLIC_PATHS.contains(&path) || path.starts_with("/pkg/") || path.starts_with("/s/"))
+}
+
+fn too_many_attempts(secs: i64) -> Response {
+    Response::builder()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header("Retry-After", secs.to_string())
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn auth_required() -> Response {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header("WWW-Authenticate", "Basic realm=\"nom\"")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Refuses a request with `status`, explained in JSON for the REST API.
+fn refuse(path: &str, status: StatusCode) -> Response {
+    if path.starts_with(crate::api::PREFIX) {
+        let message = status.canonical_reason().unwrap_or_default();
+        crate::api::ApiError::new(status, message).into_response()
+    } else {
+        status.into_response()
+    }
+}
+
+/// The session token from the request's cookies, if any.
+pub fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, token)| token)
+}
+
+/// Sends browsers to the login page, coming back here afterwards.
+fn login_redirect(request: &Request) -> Response {
+    let next = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+
+    let encoded: String = next
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect();
+
+    Redirect::to(&format!("/login?next={encoded}")).into_response()
+}
+
+/// Looks up an API token, writing down when it was used if that is more than
+/// [`store::TOKEN_USE_PRECISION`] ago.
+async fn token_user(db: &Pool, token: &str) -> Result<Option<(User, TokenAuth)>, StatusCode> {
+    let token = token.to_string();
+
+    let found = db
+        .read(move |db| store::api_token_user(db, &token))
+        .await
+        .map_err(|err| {
+            leptos::logging::error!("Could not look up API token: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let Some((user, auth, last_used)) = found else {
+        return Ok(None);
+    };
+
+    let now = Utc::now();
+
+    if last_used.is_none_or(|last_used| now - last_used >= store::TOKEN_USE_PRECISION) {
+        let token_id = auth.id;
+
+        // Not worth refusing the request for
+        if let Err(err) = db
+            .write(move |db| store::mark_api_token_used(db, token_id, now))
+            .await
+        {
+            leptos::logging::error!("Could not mark API token {token_id} as used: {err}");
+        }
+    }
+
+    Ok(Some((user, auth)))
+}
+
+/// Checks Basic auth, which sends the password with every request. A password
+/// that was right lately isn't checked or counted again, or every request
+/// would take as long as logging in.
+async fn basic_user(
+    db: &Pool,
+    throttle: &LoginThrottle,
+    basic: &Basic,
+    ip: Option<IpAddr>,
+) -> Result<User, LoginError> {
+    if let Some(secs) = throttle.locked_for(&throttle::keys(ip, Some(basic.username()))) {
+        return Err(LoginError::LockedOut(secs));
+    }
+
+    let credentials = {
+        let username = basic.username().to_string();
+        db.read(move |db| store::credentials(db, &username)).await?
+    };
+
+    let now = Utc::now().timestamp();
+
+    if let Some((user, hash)) = &credentials
+        && store::recently_verified(basic.password(), hash, now)
+    {
+        return Ok(user.clone());
+    }
+
+    let user = authenticate(
+        db,
+        throttle,
+        basic.username(),
+        basic.password().to_string(),
+        ip,
+    )
+    .await?;
+
+    if let Some((_, hash)) = credentials {
+        store::remember_verified(basic.password(), &hash, now);
+    }
+
+    Ok(user)
+}
+
+/// Resolves the current user from the session cookie, an API token or Basic
+/// auth, and puts it in the request extensions for the server functions.
+/// Once there are accounts, everything but the login page requires one.
+pub async fn auth_middleware(
+    State(db): State<Pool>,
+    State(throttle): State<LoginThrottle>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let (has_users, session_user) = {
+        let token = session_token(request.headers()).map(str::to_string);
+
+        let lookup = db
+            .read(move |db| -> rusqlite::Result<_> {
+                let has_users = store::has_users(db)?;
+                let session_user = match token {
+                    Some(token) if has_users => store::session_user(db, &token)?,
+                    _ => None,
+                };
+
+                Ok((has_users, session_user))
+            })
+            .await;
+
+        lookup.map_err(|err| {
+            leptos::logging::error!("Could not look up session: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    if !has_users {
+        return Ok(next.run(request).await);
+    }
+
+    let bearer = request.headers().typed_get::<Authorization<Bearer>>();
+    let basic = request.headers().typed_get::<Authorization<Basic>>();
+    let ip = throttle::client_ip(request.headers(), request.extensions());
+
+    let user = if let Some(user) = session_user {
+        Some(user)
+    } else if let Some(Authorization(bearer)) = bearer {
+        let keys = throttle::keys(ip, None);
+
+        // Only counted when the token is wrong, unlike a login. Tokens can't
+        // be guessed, this just stops anyone from trying for long.
+        if let Some(secs) = throttle.locked_for(&keys) {
+            return Ok(too_many_attempts(secs));
+        }
+
+        // A wrong token is an error rather than a login prompt, there's no
+        // one to log in
+        let Some((mut user, auth)) = token_user(&db, bearer.token()).await? else {
+            let ip = ip.map_or("an unknown address".to_string(), |ip| ip.to_string());
+            leptos::logging::log!("Unknown API token used from {ip}");
+
+            throttle.record_failure(&db, &keys).await.map_err(|err| {
+                leptos::logging::error!("Could not record failed login: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+            return Ok(refuse(request.uri().path(), StatusCode::UNAUTHORIZED));
+        };
+
+        if !auth.allows_path(request.uri().path()) {
+            return Ok(refuse(request.uri().path(), StatusCode::FORBIDDEN));
+        }
+
+        user.role = auth.limit_role(user.role);
+        request.extensions_mut().insert(auth);
+
+        Some(user)
+    } else if let Some(Authorization(basic)) = basic {
+        // For clients that can't log in with a form
+        match basic_user(&db, &throttle, &basic, ip).await {
+            Ok(user) => Some(user),
+            Err(LoginError::Invalid) => None,
+            Err(LoginError::LockedOut(secs)) => return Ok(too_many_attempts(secs)),
+            Err(err) => {
+                leptos::logging::error!("{err}");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Some(user) = user {
+        request.extensions_mut().insert(user);
+        return Ok(next.run(request).await);
+    }
+
+    let path = request.uri().path();
+
+    if is_public(path) {
+        return Ok(next.run(request).await);
+    }
+
+    let wants_html = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+
+    if wants_html {
+        Ok(login_redirect(&request))
+    } else if path.starts_with("/api/") {
+        // No Basic challenge here, browsers would pop up a login dialog
+        // for server functions called after a session expired
+        Ok(refuse(path, StatusCode::UNAUTHORIZED))
+    } else {
+        Ok(auth_required())
+    }
+}
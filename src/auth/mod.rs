@@ -0,0 +1,431 @@
+use std::fmt;
+use std::str::FromStr;
+
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+pub mod middleware;
+#[cfg(feature = "ssr")]
+pub mod store;
+#[cfg(feature = "ssr")]
+pub mod throttle;
+pub mod tokens;
+
+/// Name of the cookie holding the session token.
+#[cfg(feature = "ssr")]
+pub const SESSION_COOKIE: &str = "nom_session";
+
+/// Passwords shorter than this are refused.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// What someone may do, each role being allowed everything the ones before
+/// it are. The meal plan and shopping list are shared, viewers see them and
+/// may tick items off the list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reads recipes
+    Viewer,
+    /// Adds and edits recipes
+    Editor,
+    /// Deletes recipes, manages accounts and imports and exports
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::Editor, Role::Admin];
+
+    /// The name as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::Viewer => "Lezer",
+            Role::Editor => "Bewerker",
+            Role::Admin => "Beheerder",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("Unknown role {s}"))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl rusqlite::types::ToSql for Role {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl rusqlite::types::FromSql for Role {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|_| rusqlite::types::FromSqlError::InvalidType)
+    }
+}
+
+/// Someone with an account.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+}
+
+/// Whether `user` may do what needs `role`. Without a user there are no
+/// accounts yet, as the middleware asks for one otherwise, and anyone may do
+/// anything.
+pub fn allowed(user: Option<&User>, role: Role) -> bool {
+    user.is_none_or(|user| user.role >= role)
+}
+
+/// The user doing the current request, as found by the auth middleware.
+/// `None` when there are no accounts yet, as nom is open then.
+#[cfg(feature = "ssr")]
+pub fn current_user() -> Option<User> {
+    use_context::<axum::http::request::Parts>()
+        .and_then(|parts| parts.extensions.get::<User>().cloned())
+}
+
+/// Fails unless the current user has at least `role`, and returns the user.
+/// Server functions take `db` before their first `await`, the request context
+/// may be gone after it.
+#[cfg(feature = "ssr")]
+pub async fn require_role(
+    db: &crate::db::Pool,
+    role: Role,
+) -> Result<Option<User>, crate::error::NomError> {
+    use crate::error::NomError;
+
+    let user = current_user();
+
+    // Checked again, in case a request got past the middleware some other way
+    if user.is_none() && db.read(store::has_users).await? {
+        return Err(NomError::NotLoggedIn);
+    }
+
+    if allowed(user.as_ref(), role) {
+        Ok(user)
+    } else {
+        Err(NomError::Unauthorized(format!(
+            "This needs the {role} role"
+        )))
+    }
+}
+
+/// The logged in user, fetched once for the whole app.
+#[derive(Clone, Copy)]
+pub struct CurrentUser(pub Resource<Option<User>>);
+
+/// Fetches the logged in user and provides it as [`CurrentUser`]. When that
+/// fails no one is taken to be logged in, hiding what needs a role.
+pub fn provide_current_user() {
+    let user = Resource::new(|| (), async |_| get_current_user().await.ok().flatten());
+
+    provide_context(CurrentUser(user));
+}
+
+/// Only shows its children to users with at least `role`.
+#[component]
+pub fn RequireRole(role: Role, children: ChildrenFn) -> impl IntoView {
+    let CurrentUser(user) = expect_context();
+
+    view! {
+        <Transition>
+            {move || {
+                user.get()
+                    .filter(|user| allowed(user.as_ref(), role))
+                    .map(|_| children())
+            }}
+        </Transition>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+pub enum LoginError {
+    Invalid,
+    /// For this many seconds
+    LockedOut(i64),
+    Internal(String),
+}
+
+#[cfg(feature = "ssr")]
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoginError::Invalid => write!(f, "Incorrect username or password"),
+            LoginError::LockedOut(secs) => {
+                write!(f, "Too many failed logins, try again in {secs} seconds")
+            }
+            LoginError::Internal(err) => write!(f, "Could not check login: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<rusqlite::Error> for LoginError {
+    fn from(err: rusqlite::Error) -> Self {
+        LoginError::Internal(err.to_string())
+    }
+}
+
+/// Checks a username and password. Failures are logged, without the
+/// password, and counted per IP address and username to lock out guessing.
+#[cfg(feature = "ssr")]
+pub async fn authenticate(
+    db: &crate::db::Pool,
+    throttle: &throttle::LoginThrottle,
+    username: &str,
+    password: String,
+    ip: Option<std::net::IpAddr>,
+) -> Result<User, LoginError> {
+    let keys = throttle::keys(ip, Some(username));
+
+    // Counted as a failure until the password turns out to be right
+    throttle.attempt(db, &keys).await?;
+
+    let credentials = {
+        let username = username.to_string();
+        db.read(move |db| store::credentials(db, &username)).await?
+    };
+
+    let (user, hash) = match credentials {
+        Some((user, hash)) => (Some(user), hash),
+        None => (None, store::DUMMY_HASH.clone()),
+    };
+
+    let valid = tokio::task::spawn_blocking(move || store::verify_password(&password, &hash))
+        .await
+        .map_err(|err| LoginError::Internal(err.to_string()))?;
+
+    match user.filter(|_| valid) {
+        Some(user) => {
+            throttle.record_success(db, ip, username).await?;
+
+            Ok(user)
+        }
+        None => {
+            let ip = ip.map_or("an unknown address".to_string(), |ip| ip.to_string());
+            leptos::logging::log!("Failed login as {username:?} from {ip}");
+
+            Err(LoginError::Invalid)
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn set_session_cookie(token: &str, max_age: i64) -> Result<(), ServerFnError> {
+    use axum::http::HeaderValue;
+    use axum::http::header::SET_COOKIE;
+
+    // Not `Secure`, nom usually runs on a home network without https
+    let cookie =
+        format!("{SESSION_COOKIE}={token}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax");
+
+    expect_context::<leptos_axum::ResponseOptions>()
+        .append_header(SET_COOKIE, HeaderValue::from_str(&cookie)?);
+
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+fn request_session_token() -> Option<String> {
+    use_context::<axum::http::request::Parts>()
+        .and_then(|parts| middleware::session_token(&parts.headers).map(str::to_string))
+}
+
+#[cfg(feature = "ssr")]
+async fn hash_password(password: String) -> Result<String, ServerFnError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(ServerFnError::new(format!(
+            "Passwords need at least {MIN_PASSWORD_LENGTH} characters"
+        )));
+    }
+
+    Ok(tokio::task::spawn_blocking(move || store::hash_password(&password)).await?)
+}
+
+#[server]
+pub async fn get_current_user() -> Result<Option<User>, ServerFnError> {
+    Ok(current_user())
+}
+
+#[server(endpoint = "login")]
+pub async fn login(username: String, password: String) -> Result<(), ServerFnError> {
+    use crate::db::use_db;
+
+    let db = use_db();
+    let throttle = expect_context::<throttle::LoginThrottle>();
+    let ip = use_context::<axum::http::request::Parts>()
+        .and_then(|parts| throttle::client_ip(&parts.headers, &parts.extensions));
+
+    let user = authenticate(&db, &throttle, username.trim(), password, ip)
+        .await
+        .map_err(ServerFnError::new)?;
+
+    let token = db
+        .write(move |db| store::create_session(db, user.id))
+        .await?;
+
+    set_session_cookie(&token, store::SESSION_DAYS * 24 * 60 * 60)
+}
+
+#[server]
+pub async fn logout() -> Result<(), ServerFnError> {
+    use crate::db::use_db;
+
+    if let Some(token) = request_session_token() {
+        use_db()
+            .write(move |db| store::delete_session(db, &token))
+            .await?;
+    }
+
+    set_session_cookie("", 0)
+}
+
+#[server]
+pub async fn list_users() -> Result<Vec<User>, ServerFnError> {
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Admin).await?;
+
+    Ok(db.read(store::list_users).await?)
+}
+
+#[server]
+pub async fn add_user(
+    username: String,
+    password: String,
+    role: Role,
+) -> Result<i64, ServerFnError> {
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Admin).await?;
+
+    let username = username.trim().to_string();
+
+    if username.is_empty() {
+        return Err(ServerFnError::new("Username can't be empty"));
+    }
+
+    let hash = hash_password(password).await?;
+
+    db.write(move |db| {
+        if store::username_taken(db, &username)? {
+            return Err(ServerFnError::new(format!(
+                "Username {username} is already taken"
+            )));
+        }
+
+        // The very first account has to be able to manage the others
+        let role = if store::has_users(db)? {
+            role
+        } else {
+            Role::Admin
+        };
+
+        Ok(store::create_user(db, &username, &hash, role)?)
+    })
+    .await
+}
+
+#[server]
+pub async fn delete_user(user_id: i64) -> Result<(), ServerFnError> {
+    use crate::db::use_db;
+
+    let db = use_db();
+    let user = require_role(&db, Role::Admin).await?;
+
+    if user.is_some_and(|user| user.id == user_id) {
+        return Err(ServerFnError::new("You can't delete your own account"));
+    }
+
+    db.write(move |db| {
+        let transaction = db.transaction()?;
+
+        if !store::delete_user(&transaction, user_id)? {
+            return Err(ServerFnError::new(format!("Unknown user {user_id}")));
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[server]
+pub async fn set_role(user_id: i64, role: Role) -> Result<(), ServerFnError> {
+    use crate::db::use_db;
+
+    let db = use_db();
+    let user = require_role(&db, Role::Admin).await?;
+
+    // So there's always an admin left
+    if user.is_some_and(|user| user.id == user_id) {
+        return Err(ServerFnError::new("You can't change your own role"));
+    }
+
+    let updated = db
+        .write(move |db| store::set_role(db, user_id, role))
+        .await?;
+
+    if !updated {
+        return Err(ServerFnError::new(format!("Unknown user {user_id}")));
+    }
+
+    Ok(())
+}
+
+/// Admins can set anyone's password, everyone else only their own. Other
+/// sessions of the user are logged out. Not possible with an API token.
+#[server]
+pub async fn set_password(user_id: i64, password: String) -> Result<(), ServerFnError> {
+    use crate::db::use_db;
+
+    let db = use_db();
+    let token = request_session_token();
+
+    if tokens::require_login()?.id != user_id {
+        require_role(&db, Role::Admin).await?;
+    }
+
+    let hash = hash_password(password).await?;
+
+    let updated = db
+        .write(move |db| store::set_password_hash(db, user_id, &hash, token.as_deref()))
+        .await?;
+
+    if !updated {
+        return Err(ServerFnError::new(format!("Unknown user {user_id}")));
+    }
+
+    Ok(())
+}
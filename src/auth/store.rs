@@ -0,0 +1,400 @@
+//! Accounts, login sessions and API tokens in the database.
+
+use argon2::Argon2;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use rusqlite::{Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use super::tokens::{ApiToken, TokenAuth, TokenScope};
+use super::{Role, User};
+
+/// How long a login lasts.
+pub const SESSION_DAYS: i64 = 30;
+
+/// Hashes a password with argon2 and a random salt. Slow on purpose, so
+/// call it from a blocking task.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Could not hash password")
+        .to_string()
+}
+
+/// Checked against when there's no such user, so that takes as long as a
+/// wrong password and doesn't give away which usernames exist.
+pub static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| hash_password("not a password"));
+
+/// Checks a password against a hash from [`hash_password`]. Just as slow,
+/// and in constant time.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// How long [`recently_verified`] remembers a password that was right.
+const VERIFIED_SECS: i64 = 10 * 60;
+
+/// Passwords that were right, until when. They are kept as an HMAC of the
+/// password and its hash with a key made at startup, so a changed password
+/// isn't found and the memory is no help in guessing them.
+struct Verified {
+    key: [u8; 32],
+    until: HashMap<Vec<u8>, i64>,
+}
+
+impl Verified {
+    fn tag(&self, password: &str, hash: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes any key length");
+
+        mac.update(hash.as_bytes());
+        mac.update(&[0]);
+        mac.update(password.as_bytes());
+
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+static VERIFIED: LazyLock<Mutex<Verified>> = LazyLock::new(|| {
+    let mut key = [0; 32];
+    OsRng.fill_bytes(&mut key);
+
+    Mutex::new(Verified {
+        key,
+        until: HashMap::new(),
+    })
+});
+
+/// Whether `password` was found to match `hash` lately. For Basic auth,
+/// which sends the password with every request, as [`verify_password`] is
+/// too slow for that.
+pub fn recently_verified(password: &str, hash: &str, now: i64) -> bool {
+    let verified = VERIFIED.lock().unwrap_or_else(PoisonError::into_inner);
+
+    verified
+        .until
+        .get(&verified.tag(password, hash))
+        .is_some_and(|&until| until > now)
+}
+
+/// Remembers that `password` matches `hash`, see [`recently_verified`].
+pub fn remember_verified(password: &str, hash: &str, now: i64) {
+    let mut verified = VERIFIED.lock().unwrap_or_else(PoisonError::into_inner);
+
+    verified.until.retain(|_, until| *until > now);
+
+    let tag = verified.tag(password, hash);
+    verified.until.insert(tag, now + VERIFIED_SECS);
+}
+
+/// Without any accounts nom is open to everyone, as it was before accounts
+/// existed.
+pub fn has_users(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.prepare_cached("SELECT 1 FROM users LIMIT 1;")?
+        .exists(())
+}
+
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        role: row.get(2)?,
+    })
+}
+
+pub fn list_users(conn: &Connection) -> rusqlite::Result<Vec<User>> {
+    conn.prepare_cached("SELECT id, username, role FROM users ORDER BY username;")?
+        .query_map((), user_from_row)?
+        .collect()
+}
+
+pub fn username_taken(conn: &Connection, username: &str) -> rusqlite::Result<bool> {
+    conn.prepare_cached("SELECT 1 FROM users WHERE username = ?1;")?
+        .exists((username,))
+}
+
+pub fn create_user(
+    conn: &Connection,
+    username: &str,
+    password_hash: &str,
+    role: Role,
+) -> rusqlite::Result<i64> {
+    conn.prepare_cached("INSERT INTO users (username, password_hash, role) VALUES (?1, ?2, ?3);")?
+        .execute((username, password_hash, role))?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+/// A user with their password hash, to check a login against.
+pub fn credentials(conn: &Connection, username: &str) -> rusqlite::Result<Option<(User, String)>> {
+    conn.prepare_cached("SELECT id, username, role, password_hash FROM users WHERE username = ?1;")?
+        .query_row((username,), |row| Ok((user_from_row(row)?, row.get(3)?)))
+        .optional()
+}
+
+pub fn set_role(conn: &Connection, user_id: i64, role: Role) -> rusqlite::Result<bool> {
+    let updated = conn
+        .prepare_cached("UPDATE users SET role = ?1 WHERE id = ?2;")?
+        .execute((role, user_id))?;
+
+    Ok(updated == 1)
+}
+
+/// Sets a new password hash and ends the user's sessions, except for
+/// `keep_session` if given.
+pub fn set_password_hash(
+    conn: &Connection,
+    user_id: i64,
+    password_hash: &str,
+    keep_session: Option<&str>,
+) -> rusqlite::Result<bool> {
+    let updated = conn
+        .prepare_cached("UPDATE users SET password_hash = ?1 WHERE id = ?2;")?
+        .execute((password_hash, user_id))?;
+
+    conn.prepare_cached("DELETE FROM sessions WHERE user = ?1 AND token IS NOT ?2;")?
+        .execute((user_id, keep_session))?;
+
+    Ok(updated == 1)
+}
+
+/// Deletes a user with their sessions and tokens. Their recipes and share
+/// links stay, without an author.
+pub fn delete_user(transaction: &rusqlite::Transaction, user_id: i64) -> rusqlite::Result<bool> {
+    transaction.execute("DELETE FROM sessions WHERE user = ?1;", (user_id,))?;
+    transaction.execute("DELETE FROM api_tokens WHERE user = ?1;", (user_id,))?;
+    transaction.execute(
+        "UPDATE recipes SET created_by = NULL WHERE created_by = ?1;",
+        (user_id,),
+    )?;
+    transaction.execute(
+        "UPDATE recipes SET updated_by = NULL WHERE updated_by = ?1;",
+        (user_id,),
+    )?;
+    transaction.execute(
+        "UPDATE share_links SET created_by = NULL WHERE created_by = ?1;",
+        (user_id,),
+    )?;
+
+    let deleted = transaction.execute("DELETE FROM users WHERE id = ?1;", (user_id,))?;
+
+    Ok(deleted == 1)
+}
+
+fn random_hex() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Starts a session for a user, returning its token for the cookie. Expired
+/// sessions are cleaned up while we're at it.
+pub fn create_session(conn: &Connection, user_id: i64) -> rusqlite::Result<String> {
+    let token = random_hex();
+
+    let now = chrono::Utc::now().timestamp();
+    let expires = now + SESSION_DAYS * 24 * 60 * 60;
+
+    conn.prepare_cached("DELETE FROM sessions WHERE expires <= ?1;")?
+        .execute((now,))?;
+    conn.prepare_cached("INSERT INTO sessions (token, user, expires) VALUES (?1, ?2, ?3);")?
+        .execute((&token, user_id, expires))?;
+
+    Ok(token)
+}
+
+/// The user of a session that hasn't expired.
+pub fn session_user(conn: &Connection, token: &str) -> rusqlite::Result<Option<User>> {
+    conn.prepare_cached(
+        "
+        SELECT users.id, users.username, users.role
+        FROM sessions JOIN users ON users.id = sessions.user
+        WHERE sessions.token = ?1 AND sessions.expires > ?2;
+        ",
+    )?
+    .query_row((token, chrono::Utc::now().timestamp()), user_from_row)
+    .optional()
+}
+
+pub fn delete_session(conn: &Connection, token: &str) -> rusqlite::Result<()> {
+    conn.prepare_cached("DELETE FROM sessions WHERE token = ?1;")?
+        .execute((token,))?;
+
+    Ok(())
+}
+
+/// Creates an admin from `NOM_USERNAME` and `NOM_PASSWORD` if there are no
+/// accounts yet. Returns its username if it did.
+pub fn bootstrap_admin(conn: &Connection) -> rusqlite::Result<Option<String>> {
+    let username = std::env::var("NOM_USERNAME").ok();
+    let password = std::env::var("NOM_PASSWORD").ok();
+
+    let Some((username, password)) = username.zip(password) else {
+        return Ok(None);
+    };
+
+    Ok(create_first_admin(conn, &username, &password)?.then_some(username))
+}
+
+/// Creates an admin if there are no accounts yet. Returns whether it did.
+pub fn create_first_admin(
+    conn: &Connection,
+    username: &str,
+    password: &str,
+) -> rusqlite::Result<bool> {
+    if has_users(conn)? {
+        return Ok(false);
+    }
+
+    create_user(conn, username, &hash_password(password), Role::Admin)?;
+
+    Ok(true)
+}
+
+/// Tokens are random enough that a plain hash keeps them safe, and can be
+/// looked up by.
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn scopes_to_sql(scopes: &[TokenScope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Fails on a scope this version doesn't know, as leaving it out could leave
+/// no scopes, which is full access.
+fn scopes_from_sql(scopes: &str) -> Result<Vec<TokenScope>, String> {
+    scopes
+        .split(',')
+        .filter(|scope| !scope.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+fn timestamp(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap_or_default()
+}
+
+/// Makes a token, returning it. It can't be recovered afterwards.
+pub fn create_api_token(
+    conn: &Connection,
+    user_id: i64,
+    name: &str,
+    scopes: &[TokenScope],
+) -> rusqlite::Result<String> {
+    // Recognisable in configuration files and secret scanners
+    let token = format!("nom_{}", random_hex());
+
+    conn.prepare_cached(
+        "
+        INSERT INTO api_tokens (user, name, token_hash, scopes, created)
+        VALUES (?1, ?2, ?3, ?4, ?5);
+        ",
+    )?
+    .execute((
+        user_id,
+        name,
+        hash_token(&token),
+        scopes_to_sql(scopes),
+        Utc::now().timestamp(),
+    ))?;
+
+    Ok(token)
+}
+
+pub fn list_api_tokens(conn: &Connection, user_id: i64) -> rusqlite::Result<Vec<ApiToken>> {
+    conn.prepare_cached(
+        "
+        SELECT id, name, scopes, created, last_used FROM api_tokens
+        WHERE user = ?1
+        ORDER BY created, id;
+        ",
+    )?
+    .query_map((user_id,), |row| {
+        Ok(ApiToken {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            // Only shown, so the token can still be found and revoked
+            scopes: scopes_from_sql(&row.get::<_, String>(2)?).unwrap_or_default(),
+            created: timestamp(row.get(3)?),
+            last_used: row.get::<_, Option<i64>>(4)?.map(timestamp),
+        })
+    })?
+    .collect()
+}
+
+pub fn delete_api_token(conn: &Connection, user_id: i64, token_id: i64) -> rusqlite::Result<bool> {
+    let deleted = conn
+        .prepare_cached("DELETE FROM api_tokens WHERE id = ?1 AND user = ?2;")?
+        .execute((token_id, user_id))?;
+
+    Ok(deleted == 1)
+}
+
+/// How far off the last use of a token may be, to not write on every request.
+pub const TOKEN_USE_PRECISION: TimeDelta = TimeDelta::minutes(5);
+
+/// A token's user, what the token may be used for and when it was last used.
+pub type TokenUse = (User, TokenAuth, Option<DateTime<Utc>>);
+
+/// Looks up who a token belongs to. Tokens with scopes this version doesn't
+/// know are refused.
+pub fn api_token_user(conn: &Connection, token: &str) -> rusqlite::Result<Option<TokenUse>> {
+    let found = conn
+        .prepare_cached(
+            "
+            SELECT users.id, users.username, users.role, api_tokens.id, api_tokens.scopes,
+                api_tokens.last_used
+            FROM api_tokens JOIN users ON users.id = api_tokens.user
+            WHERE api_tokens.token_hash = ?1;
+            ",
+        )?
+        .query_row((hash_token(token),), |row| {
+            let scopes = scopes_from_sql(&row.get::<_, String>(4)?);
+            let last_used = row.get::<_, Option<i64>>(5)?.map(timestamp);
+
+            Ok((user_from_row(row)?, row.get(3)?, scopes, last_used))
+        })
+        .optional()?;
+
+    let Some((user, id, scopes, last_used)) = found else {
+        return Ok(None);
+    };
+
+    match scopes {
+        Ok(scopes) => Ok(Some((user, TokenAuth { id, scopes }, last_used))),
+        Err(err) => {
+            leptos::logging::warn!("Refusing API token {id}: {err}");
+            Ok(None)
+        }
+    }
+}
+
+pub fn mark_api_token_used(
+    conn: &Connection,
+    token_id: i64,
+    now: DateTime<Utc>,
+) -> rusqlite::Result<()> {
+    conn.prepare_cached("UPDATE api_tokens SET last_used = ?1 WHERE id = ?2;")?
+        .execute((now.timestamp(), token_id))?;
+
+    Ok(())
+}
@@ -0,0 +1,372 @@
+//! Slows down password guessing by locking out IP addresses and usernames
+//! after too many failed logins, for longer with every failure after that.
+//!
+//! Configured with `NOM_LOGIN_MAX_FAILURES` (5 by default) and
+//! `NOM_LOGIN_LOCKOUT_SECS` (60 by default, the first lockout). Failures are
+//! kept in memory, and in the database too with `NOM_LOGIN_PERSIST=1` so a
+//! restart doesn't reset them.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::extract::ConnectInfo;
+use axum::http::{Extensions, HeaderMap};
+use rusqlite::Connection;
+
+/// Lockouts don't get longer than this.
+const MAX_LOCKOUT_SECS: i64 = 60 * 60;
+
+/// Failures are forgotten after this long without new ones.
+const FORGET_AFTER_SECS: i64 = 24 * 60 * 60;
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| matches!(value.as_str(), "1" | "true" | "yes"))
+}
+
+#[derive(Debug, Clone)]
+pub struct ThrottleConfig {
+    /// Failures before the first lockout
+    pub max_failures: u32,
+    pub lockout_secs: i64,
+    pub persist: bool,
+}
+
+impl ThrottleConfig {
+    pub fn from_env() -> Self {
+        let number = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+
+        ThrottleConfig {
+            max_failures: number("NOM_LOGIN_MAX_FAILURES", 5).clamp(1, 1000) as u32,
+            lockout_secs: number("NOM_LOGIN_LOCKOUT_SECS", 60).max(1),
+            persist: env_flag("NOM_LOGIN_PERSIST"),
+        }
+    }
+}
+
+/// Failures of a single IP address or username. Times in seconds since the
+/// epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Failures {
+    pub count: u32,
+    pub last_failure: i64,
+    pub locked_until: i64,
+}
+
+pub struct Throttle {
+    config: ThrottleConfig,
+    failures: HashMap<String, Failures>,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Throttle {
+            config,
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Seconds until all of `keys` may try again, if any is locked out.
+    pub fn locked_for(&self, keys: &[String], now: i64) -> Option<i64> {
+        keys.iter()
+            .filter_map(|key| self.failures.get(key))
+            .map(|failures| failures.locked_until - now)
+            .filter(|secs| *secs > 0)
+            .max()
+    }
+
+    /// Counts a failure, locking out the key once there are too many.
+    pub fn fail(&mut self, key: &str, now: i64) -> Failures {
+        self.failures
+            .retain(|_, failures| now - failures.last_failure <= FORGET_AFTER_SECS);
+
+        let failures = self.failures.entry(key.to_string()).or_default();
+
+        failures.count += 1;
+        failures.last_failure = now;
+
+        if failures.count >= self.config.max_failures {
+            let doublings = (failures.count - self.config.max_failures).min(16);
+            let lockout = (self.config.lockout_secs << doublings).min(MAX_LOCKOUT_SECS);
+
+            failures.locked_until = now + lockout;
+        }
+
+        *failures
+    }
+
+    /// Counts an attempt as failed for all of `keys`, unless one is locked
+    /// out, in which case it returns the seconds until it may try again.
+    /// Checking and counting at once means attempts made at the same time
+    /// can't all get past the check.
+    pub fn attempt(&mut self, keys: &[String], now: i64) -> Result<Vec<(String, Failures)>, i64> {
+        if let Some(secs) = self.locked_for(keys, now) {
+            return Err(secs);
+        }
+
+        Ok(keys
+            .iter()
+            .map(|key| (key.clone(), self.fail(key, now)))
+            .collect())
+    }
+
+    pub fn forget(&mut self, key: &str) -> bool {
+        self.failures.remove(key).is_some()
+    }
+
+    /// Takes back the failure counted by an [`attempt`](Self::attempt) that
+    /// succeeded after all, returning what is left. A count of 0 means the
+    /// key is forgotten.
+    pub fn take_back(&mut self, key: &str) -> Option<Failures> {
+        let failures = self.failures.get_mut(key)?;
+
+        failures.count = failures.count.saturating_sub(1);
+
+        // It wasn't locked out when the attempt started
+        if failures.count < self.config.max_failures {
+            failures.locked_until = 0;
+        }
+
+        let failures = *failures;
+
+        if failures.count == 0 {
+            self.failures.remove(key);
+        }
+
+        Some(failures)
+    }
+}
+
+/// The keys failures are counted under, for an attempt from `ip` as
+/// `username`.
+pub fn keys(ip: Option<IpAddr>, username: Option<&str>) -> Vec<String> {
+    ip.map(|ip| format!("ip:{ip}"))
+        .into_iter()
+        .chain(username.map(|username| format!("user:{}", username.to_lowercase())))
+        .collect()
+}
+
+/// The address of the client. With `NOM_TRUST_PROXY=1` that's what a reverse
+/// proxy put in `X-Forwarded-For`, otherwise we'd lock out the proxy.
+pub fn client_ip(headers: &HeaderMap, extensions: &Extensions) -> Option<IpAddr> {
+    let forwarded = || {
+        // The last one is added by our proxy, the ones before it by whoever
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|header| header.to_str().ok())
+            .flat_map(|header| header.split(','))
+            .next_back()
+            .and_then(|ip| ip.trim().parse().ok())
+    };
+
+    let forwarded = if env_flag("NOM_TRUST_PROXY") {
+        forwarded()
+    } else {
+        None
+    };
+
+    forwarded.or_else(|| {
+        extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    })
+}
+
+/// The [`Throttle`] of an app, shared by all its requests. Clones share it
+/// too.
+#[derive(Clone)]
+pub struct LoginThrottle(Arc<Mutex<Throttle>>);
+
+impl LoginThrottle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        LoginThrottle(Arc::new(Mutex::new(Throttle::new(config))))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Throttle> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Seconds until `keys` may try again, if locked out.
+    pub fn locked_for(&self, keys: &[String]) -> Option<i64> {
+        self.lock().locked_for(keys, chrono::Utc::now().timestamp())
+    }
+
+    /// Starts a login attempt, which counts as failed until
+    /// [`record_success`](Self::record_success) says otherwise.
+    pub async fn attempt(
+        &self,
+        db: &crate::db::Pool,
+        keys: &[String],
+    ) -> Result<(), super::LoginError> {
+        let now = chrono::Utc::now().timestamp();
+
+        let (persist, failures) = {
+            let mut throttle = self.lock();
+            let failures = throttle
+                .attempt(keys, now)
+                .map_err(super::LoginError::LockedOut)?;
+
+            (throttle.config.persist, failures)
+        };
+
+        Ok(counted(db, now, persist, failures).await?)
+    }
+
+    pub async fn record_failure(
+        &self,
+        db: &crate::db::Pool,
+        keys: &[String],
+    ) -> rusqlite::Result<()> {
+        let now = chrono::Utc::now().timestamp();
+
+        let (persist, failures) = {
+            let mut throttle = self.lock();
+            let failures = keys
+                .iter()
+                .map(|key| (key.clone(), throttle.fail(key, now)))
+                .collect::<Vec<_>>();
+
+            (throttle.config.persist, failures)
+        };
+
+        counted(db, now, persist, failures).await
+    }
+
+    /// Forgets the failures of `username` after logging in as them. Those of
+    /// the address only lose the one counted for this attempt, or someone
+    /// could go on guessing at other accounts by logging in to their own in
+    /// between.
+    pub async fn record_success(
+        &self,
+        db: &crate::db::Pool,
+        ip: Option<IpAddr>,
+        username: &str,
+    ) -> rusqlite::Result<()> {
+        let (persist, changed) = {
+            let mut throttle = self.lock();
+            let mut changed = keys(None, Some(username))
+                .into_iter()
+                .filter(|key| throttle.forget(key))
+                .map(|key| (key, Failures::default()))
+                .collect::<Vec<_>>();
+
+            for key in keys(ip, None) {
+                if let Some(failures) = throttle.take_back(&key) {
+                    changed.push((key, failures));
+                }
+            }
+
+            (throttle.config.persist, changed)
+        };
+
+        if persist && !changed.is_empty() {
+            db.write(move |db| -> rusqlite::Result<()> {
+                for (key, failures) in changed {
+                    if failures.count > 0 {
+                        save(db, &key, failures)?;
+                    } else {
+                        db.prepare_cached("DELETE FROM login_failures WHERE key = ?1;")?
+                            .execute((key,))?;
+                    }
+                }
+
+                Ok(())
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the failures from the database, if they are kept there. Old ones
+    /// are cleaned up.
+    pub fn load(&self, conn: &Connection) -> rusqlite::Result<()> {
+        let mut throttle = self.lock();
+
+        if !throttle.config.persist {
+            return Ok(());
+        }
+
+        let now = chrono::Utc::now().timestamp();
+
+        conn.prepare_cached("DELETE FROM login_failures WHERE last_failure < ?1;")?
+            .execute((now - FORGET_AFTER_SECS,))?;
+
+        let mut stmt = conn
+            .prepare_cached("SELECT key, count, last_failure, locked_until FROM login_failures;")?;
+        let rows = stmt.query_map((), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Failures {
+                    count: row.get(1)?,
+                    last_failure: row.get(2)?,
+                    locked_until: row.get(3)?,
+                },
+            ))
+        })?;
+
+        for row in rows {
+            let (key, failures) = row?;
+            throttle.failures.insert(key, failures);
+        }
+
+        Ok(())
+    }
+}
+
+/// Logs lockouts after counting `failures`, and saves them if they are
+/// kept in the database.
+async fn counted(
+    db: &crate::db::Pool,
+    now: i64,
+    persist: bool,
+    failures: Vec<(String, Failures)>,
+) -> rusqlite::Result<()> {
+    for (key, failures) in &failures {
+        if failures.locked_until > now {
+            leptos::logging::log!(
+                "Locked out {key} for {}s after {} failed logins",
+                failures.locked_until - now,
+                failures.count
+            );
+        }
+    }
+
+    if persist {
+        db.write(move |db| -> rusqlite::Result<()> {
+            for (key, failures) in failures {
+                save(db, &key, failures)?;
+            }
+
+            Ok(())
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn save(conn: &Connection, key: &str, failures: Failures) -> rusqlite::Result<()> {
+    conn.prepare_cached(
+        "
+        INSERT OR REPLACE INTO login_failures (key, count, last_failure, locked_until)
+        VALUES (?1, ?2, ?3, ?4);
+        ",
+    )?
+    .execute((
+        key,
+        failures.count,
+        failures.last_failure,
+        failures.locked_until,
+    ))?;
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! API tokens, for scripts and devices that can't log in with a form.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Limits what a token can be used for. A token without scopes can do
+/// everything its user can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    /// Reading recipes, as a viewer
+    Read,
+    /// Only the TRMNL display page
+    Trmnl,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 2] = [TokenScope::Read, TokenScope::Trmnl];
+
+    /// The name as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Trmnl => "trmnl",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TokenScope::Read => "Alleen lezen",
+            TokenScope::Trmnl => "Alleen TRMNL",
+        }
+    }
+
+    /// Whether a request for `path` is in this scope.
+    pub fn allows_path(self, path: &str) -> bool {
+        match self {
+            TokenScope::Read => true,
+            TokenScope::Trmnl => {
+                path == "/trmnl"
+                    || path == "/favicon.ico"
+                    || path.starts_with("/pkg/")
+                    || path.starts_with("/images/")
+            }
+        }
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TokenScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("Unknown token scope {s}"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub created: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// Put in the request extensions by the auth middleware when a request was
+/// made with an API token.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct TokenAuth {
+    pub id: i64,
+    pub scopes: Vec<TokenScope>,
+}
+
+#[cfg(feature = "ssr")]
+impl TokenAuth {
+    /// Whether a request for `path` may be made with this token.
+    pub fn allows_path(&self, path: &str) -> bool {
+        self.scopes.is_empty()
+            || (!super::middleware::has_dot_segment(path)
+                && self.scopes.iter().any(|scope| scope.allows_path(path)))
+    }
+
+    /// Scoped tokens only read, whatever their user may do.
+    pub fn limit_role(&self, role: super::Role) -> super::Role {
+        if self.scopes.is_empty() {
+            role
+        } else {
+            role.min(super::Role::Viewer)
+        }
+    }
+}
+
+/// The logged in user, when they logged in themselves. Managing tokens and
+/// passwords can't be done with a token, or a read-only token could make
+/// itself a full one.
+#[cfg(feature = "ssr")]
+pub(super) fn require_login() -> Result<super::User, ServerFnError> {
+    let parts = use_context::<axum::http::request::Parts>();
+
+    if parts
+        .as_ref()
+        .is_some_and(|parts| parts.extensions.get::<TokenAuth>().is_some())
+    {
+        return Err(ServerFnError::new("Not possible with an API token"));
+    }
+
+    super::current_user().ok_or_else(|| ServerFnError::new("Not logged in"))
+}
+
+#[server]
+pub async fn list_api_tokens() -> Result<Vec<ApiToken>, ServerFnError> {
+    use crate::db::use_db;
+
+    let user = require_login()?;
+
+    Ok(use_db()
+        .read(move |db| super::store::list_api_tokens(db, user.id))
+        .await?)
+}
+
+/// Makes a token for the current user. It is returned only this once, only
+/// a hash of it is kept.
+#[server]
+pub async fn create_api_token(
+    name: String,
+    #[server(default)] scopes: Vec<TokenScope>,
+) -> Result<String, ServerFnError> {
+    use crate::db::use_db;
+
+    let user = require_login()?;
+
+    let name = name.trim().to_string();
+
+    if name.is_empty() {
+        return Err(ServerFnError::new("Tokens need a name"));
+    }
+
+    Ok(use_db()
+        .write(move |db| super::store::create_api_token(db, user.id, &name, &scopes))
+        .await?)
+}
+
+#[server]
+pub async fn revoke_api_token(token_id: i64) -> Result<(), ServerFnError> {
+    use crate::db::use_db;
+
+    let user = require_login()?;
+
+    let deleted = use_db()
+        .write(move |db| super::store::delete_api_token(db, user.id, token_id))
+        .await?;
+
+    if !deleted {
+        return Err(ServerFnError::new(format!("Unknown token {token_id}")));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,605 @@
+use leptos::prelude::*;
+use leptos::server_fn::codec::{MultipartData, MultipartFormData};
+
+use crate::export::ImportSummary;
+
+/// Imports Cooklang recipes. Expects one or more `file` fields, each a
+/// `.cook` file or a zip with `.cook` files in any directory. Recipes with a
+/// title we already have are skipped, as with a merging import.
+#[server(input = MultipartFormData)]
+pub async fn import_cooklang(data: MultipartData) -> Result<ImportSummary, ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+    use crate::export::{ExportDocument, FORMAT_VERSION, ImportMode};
+    use crate::store::use_store;
+
+    let store = use_store();
+    require_role(&use_db(), Role::Admin).await?;
+
+    let mut data = data.into_inner().unwrap();
+
+    let mut recipes = Vec::new();
+
+    while let Some(mut field) = data.next_field().await? {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        let mut bytes = Vec::new();
+
+        // Read in chunks, so a huge file is refused before it's in memory
+        while let Some(chunk) = field.chunk().await? {
+            if bytes.len() + chunk.len() > format::MAX_IMPORT_SIZE {
+                return Err(ServerFnError::new(format!(
+                    "{file_name} is larger than {} MB",
+                    format::MAX_IMPORT_SIZE / 1024 / 1024
+                )));
+            }
+
+            bytes.extend_from_slice(&chunk);
+        }
+
+        if file_name.to_lowercase().ends_with(".zip") {
+            for (name, text) in format::read_zip(&bytes)? {
+                recipes.push(format::parse(&text, &name).into());
+            }
+        } else if !bytes.is_empty() {
+            recipes.push(format::parse(&String::from_utf8_lossy(&bytes), &file_name).into());
+        }
+    }
+
+    if recipes.is_empty() {
+        return Err(ServerFnError::new("No Cooklang recipes found"));
+    }
+
+    let document = ExportDocument {
+        version: FORMAT_VERSION,
+        recipes,
+    };
+
+    Ok(store.import(document, ImportMode::Merge).await?)
+}
+
+/// Reading and writing the Cooklang format, see <https://cooklang.org>.
+///
+/// Cooklang marks up ingredients inside the steps, while we keep them as a
+/// separate list. Exported ingredients therefore get a step of their own,
+/// which is recognised on import and not added to the instructions.
+#[cfg(feature = "ssr")]
+pub mod format {
+    use std::io::{Cursor, Read};
+
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+    use axum::response::{IntoResponse, Response};
+
+    use crate::db::Pool;
+    use crate::recipe::{Ingredient, Recipe, load_recipe, parse_quantity};
+
+    /// Largest `.cook` file or zip that can be imported, and the most the
+    /// `.cook` files in a zip can add up to unpacked.
+    pub const MAX_IMPORT_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Writes a recipe as Cooklang, with its details as metadata.
+    pub fn write(recipe: &Recipe) -> String {
+        let mut lines = vec![format!(">> title: {}", recipe.title)];
+
+        if let Some(servings) = recipe.servings {
+            lines.push(format!(">> servings: {servings}"));
+        }
+
+        if let Some(prep_time) = recipe.prep_time {
+            lines.push(format!(">> prep time: {prep_time} minutes"));
+        }
+
+        if let Some(cook_time) = recipe.cook_time {
+            lines.push(format!(">> cook time: {cook_time} minutes"));
+        }
+
+        if !recipe.tags.is_empty() {
+            lines.push(format!(">> tags: {}", recipe.tags.join(", ")));
+        }
+
+        if !recipe.ingredients.is_empty() {
+            lines.push(String::new());
+            lines.extend(recipe.ingredients.iter().map(write_ingredient));
+        }
+
+        for step in recipe
+            .instructions
+            .lines()
+            .map(str::trim)
+            .filter(|step| !step.is_empty())
+        {
+            lines.push(String::new());
+            lines.push(step.to_string());
+        }
+
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    fn write_ingredient(ingredient: &Ingredient) -> String {
+        let amount = match (ingredient.quantity, &ingredient.unit) {
+            (Some(quantity), Some(unit)) => format!("{}%{unit}", write_quantity(quantity)),
+            (Some(quantity), None) => write_quantity(quantity),
+            (None, _) => String::new(),
+        };
+
+        let note = ingredient
+            .note
+            .as_ref()
+            .map(|note| format!("({note})"))
+            .unwrap_or_default();
+
+        format!("@{}{{{amount}}}{note}", ingredient.name)
+    }
+
+    /// Cooklang knows no vulgar fractions or decimal commas, so quantities
+    /// are written as "1/3" or "1.5".
+    fn write_quantity(quantity: f64) -> String {
+        if (quantity - quantity.round()).abs() < 0.001 {
+            return format!("{}", quantity.round());
+        }
+
+        if quantity < 1.0 {
+            for denominator in [2.0, 3.0, 4.0, 8.0] {
+                let numerator = quantity * denominator;
+
+                if (numerator - numerator.round()).abs() < 0.001 {
+                    return format!("{}/{denominator}", numerator.round());
+                }
+            }
+        }
+
+        format!("{quantity:.2}")
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+
+    /// Parses a Cooklang recipe. Cooklang names recipes after their file, so
+    /// `file_name` gives the title if the recipe has no `title` metadata.
+    ///
+    /// Cookware and timers become plain text in the instructions.
+    pub fn parse(text: &str, file_name: &str) -> Recipe {
+        let (mut metadata, body) = read_front_matter(text).unwrap_or((Vec::new(), text));
+
+        let mut instructions = Vec::new();
+        let mut ingredients = Vec::new();
+        let mut step = Vec::new();
+
+        let body = strip_comments(body);
+
+        for line in body.lines().map(str::trim) {
+            if let Some(line) = line.strip_prefix(">>") {
+                if let Some((key, value)) = line.split_once(':') {
+                    metadata.push((key.trim().to_lowercase(), value.trim().to_string()));
+                }
+            } else if line.is_empty() {
+                read_step(
+                    &std::mem::take(&mut step),
+                    &mut instructions,
+                    &mut ingredients,
+                );
+            } else if line.starts_with('=') {
+                // A section, "= Saus ="
+                read_step(
+                    &std::mem::take(&mut step),
+                    &mut instructions,
+                    &mut ingredients,
+                );
+
+                let name = line.trim_matches('=').trim();
+                if !name.is_empty() {
+                    instructions.push(format!("{name}:"));
+                }
+            } else if let Some(note) = line.strip_prefix('>') {
+                read_step(
+                    &std::mem::take(&mut step),
+                    &mut instructions,
+                    &mut ingredients,
+                );
+                instructions.push(note.trim().to_string());
+            } else {
+                step.push(line);
+            }
+        }
+
+        read_step(&step, &mut instructions, &mut ingredients);
+
+        let mut recipe = Recipe {
+            title: String::new(),
+            servings: None,
+            prep_time: None,
+            cook_time: None,
+            ingredients,
+            instructions: instructions.join("\n"),
+            tags: Vec::new(),
+            images: Vec::new(),
+            version: 0,
+        };
+
+        for (key, value) in metadata {
+            let value = value.trim_matches(['"', '\'']);
+
+            match key.as_str() {
+                "title" => recipe.title = value.to_string(),
+                "servings" | "serves" | "yield" => recipe.servings = first_number(value),
+                "tags" => {
+                    recipe.tags = value
+                        .trim_matches(['[', ']'])
+                        .split(',')
+                        .map(|tag| tag.trim().trim_matches(['"', '\'']))
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                "prep time" | "prep_time" | "preptime" | "prep" => {
+                    recipe.prep_time = parse_duration(value)
+                }
+                "cook time" | "cook_time" | "cooktime" | "cook" => {
+                    recipe.cook_time = parse_duration(value)
+                }
+                _ => {}
+            }
+        }
+
+        if recipe.title.trim().is_empty() {
+            recipe.title = title_from_file_name(file_name);
+        }
+
+        recipe
+    }
+
+    /// Reads YAML front matter between `---` lines. Only flat keys are
+    /// supported, with lists of `- item` lines joined by commas.
+    fn read_front_matter(text: &str) -> Option<(Vec<(String, String)>, &str)> {
+        let rest = text.trim_start().strip_prefix("---")?;
+        let rest = rest
+            .strip_prefix('\n')
+            .or_else(|| rest.strip_prefix("\r\n"))?;
+        let end = rest.find("\n---")?;
+
+        let mut metadata: Vec<(String, String)> = Vec::new();
+
+        for line in rest[..end].lines() {
+            if let Some(item) = line.trim().strip_prefix("- ") {
+                if let Some((_, value)) = metadata.last_mut() {
+                    if !value.is_empty() {
+                        value.push_str(", ");
+                    }
+                    value.push_str(item.trim());
+                }
+            } else if let Some((key, value)) = line.split_once(':') {
+                metadata.push((key.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let body = rest[end + 1..]
+            .split_once('\n')
+            .map_or("", |(_, body)| body);
+
+        Some((metadata, body))
+    }
+
+    /// Removes `-- line` and `[- block -]` comments. Lines with nothing but
+    /// a comment are dropped, so they don't split a step.
+    fn strip_comments(text: &str) -> String {
+        let mut stripped = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find("[-") {
+            stripped.push_str(&rest[..start]);
+            rest = rest[start..]
+                .find("-]")
+                .map_or("", |end| &rest[start + end + 2..]);
+        }
+        stripped.push_str(rest);
+
+        stripped
+            .lines()
+            .filter_map(|line| match line.find("--") {
+                Some(start) if line[..start].trim().is_empty() => None,
+                Some(start) => Some(&line[..start]),
+                None => Some(line),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// A component after its `@`, `#` or `~`.
+    struct Component<'a> {
+        name: &'a str,
+        amount: Option<&'a str>,
+        note: Option<&'a str>,
+        rest: &'a str,
+    }
+
+    /// Reads the lines of a step into an instruction, collecting its
+    /// ingredients. A step with nothing but ingredients is an ingredient list
+    /// and doesn't become an instruction.
+    fn read_step(
+        lines: &[&str],
+        instructions: &mut Vec<String>,
+        ingredients: &mut Vec<Ingredient>,
+    ) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let has_words = |text: &str| text.chars().any(char::is_alphanumeric);
+
+        let mut text = String::new();
+        let mut only_ingredients = true;
+        let step = lines.join(" ");
+        let mut rest = step.as_str();
+
+        while let Some(start) = rest.find(['@', '#', '~']) {
+            let sigil = rest[start..].chars().next().unwrap();
+            let before = &rest[..start];
+
+            text.push_str(before);
+            only_ingredients &= !has_words(before);
+
+            let Some(component) = read_component(&rest[start + 1..], sigil) else {
+                text.push(sigil);
+                only_ingredients = false;
+                rest = &rest[start + 1..];
+                continue;
+            };
+
+            rest = component.rest;
+
+            match sigil {
+                '@' => {
+                    let reference = component.name.starts_with('&');
+                    let name = component.name.trim_start_matches(['&', '?', '+', '-']);
+                    let (name, shown) = name.split_once('|').unwrap_or((name, name));
+
+                    text.push_str(shown);
+
+                    let ingredient = ingredient(name, component.amount, component.note);
+                    let duplicate = ingredient.quantity.is_none()
+                        && ingredients.iter().any(|i| i.name == ingredient.name);
+
+                    if !reference && !duplicate {
+                        ingredients.push(ingredient);
+                    }
+                }
+                '#' => {
+                    text.push_str(component.name);
+                    only_ingredients = false;
+                }
+                _ => {
+                    // Timers read as their duration, "~{25%minutes}"
+                    match component.amount {
+                        Some(amount) => text.push_str(amount.replace('%', " ").trim()),
+                        None => text.push_str(component.name),
+                    }
+                    only_ingredients = false;
+                }
+            }
+        }
+
+        text.push_str(rest);
+        only_ingredients &= !has_words(rest);
+
+        if !only_ingredients {
+            instructions.push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+    }
+
+    /// Reads a component: a single word, or several words ending in braces
+    /// ("@olijfolie extra vierge{}"). Only ingredients take a note.
+    fn read_component(text: &str, sigil: char) -> Option<Component<'_>> {
+        let multi_word_end = text.find('{').filter(|end| {
+            !text.starts_with(char::is_whitespace)
+                && !text[..*end].contains(['@', '#', '~', '}', '(', ')'])
+        });
+
+        let (name, rest) = match multi_word_end {
+            Some(end) => text.split_at(end),
+            None => {
+                let start = text
+                    .find(|c: char| !['&', '?', '+', '-'].contains(&c))
+                    .unwrap_or(text.len());
+                let end = text[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .map_or(text.len(), |end| start + end);
+
+                text.split_at(end)
+            }
+        };
+
+        let name = name.trim();
+
+        // Timers may be nameless, "~{10%minutes}"
+        if name.is_empty() && !(sigil == '~' && rest.starts_with('{')) {
+            return None;
+        }
+
+        let (amount, rest) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+            Some((amount, rest)) => (Some(amount.trim()).filter(|a| !a.is_empty()), rest),
+            None => (None, rest),
+        };
+
+        let (note, rest) = match rest.strip_prefix('(').and_then(|r| r.split_once(')')) {
+            Some((note, rest)) if sigil == '@' => {
+                (Some(note.trim()).filter(|n| !n.is_empty()), rest)
+            }
+            _ => (None, rest),
+        };
+
+        Some(Component {
+            name,
+            amount,
+            note,
+            rest,
+        })
+    }
+
+    /// An ingredient from its name and "quantity%unit" amount. Amounts we
+    /// can't read as a number, like "{een snufje}", are kept in the note.
+    fn ingredient(name: &str, amount: Option<&str>, note: Option<&str>) -> Ingredient {
+        let (quantity, unit) = amount
+            .map(|amount| amount.split_once('%').unwrap_or((amount, "")))
+            .map(|(quantity, unit)| (quantity.trim().trim_start_matches('=').trim(), unit.trim()))
+            .unwrap_or_default();
+
+        let parsed = match parse_quantity(quantity) {
+            Ok(Some((quantity, rest))) if rest.trim().is_empty() => Some(quantity),
+            _ => None,
+        };
+
+        let (unit, amount_note) = match parsed {
+            Some(_) => (Some(unit).filter(|u| !u.is_empty()), None),
+            None if quantity.is_empty() => (None, None),
+            None => (None, Some(format!("{quantity} {unit}").trim().to_string())),
+        };
+
+        let note = match (amount_note, note) {
+            (Some(amount), Some(note)) => Some(format!("{amount}, {note}")),
+            (amount, note) => amount.or(note.map(str::to_string)),
+        };
+
+        Ingredient {
+            quantity: parsed,
+            unit: unit.map(str::to_string),
+            name: name.trim().to_string(),
+            note,
+        }
+    }
+
+    fn first_number(text: &str) -> Option<u32> {
+        let digits: String = text
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(char::is_ascii_digit)
+            .collect();
+
+        digits.parse().ok().filter(|number| *number > 0)
+    }
+
+    /// Parses durations like "10 minutes", "1 hour 30 min", "1h30m" or
+    /// "1,5 uur" into minutes. A number without unit is in minutes.
+    fn parse_duration(duration: &str) -> Option<u32> {
+        let mut minutes = 0.0;
+        let mut found = false;
+        let mut rest = duration.trim();
+
+        while !rest.is_empty() {
+            let number_end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ','))
+                .unwrap_or(rest.len());
+
+            if number_end == 0 {
+                // Skip anything that isn't a number, like "en" in "1 uur en 10 min"
+                let skip = rest.chars().next().map_or(1, char::len_utf8);
+                rest = &rest[skip..];
+                continue;
+            }
+
+            let value: f64 = rest[..number_end].replace(',', ".").parse().ok()?;
+            rest = rest[number_end..].trim_start();
+
+            let unit_end = rest
+                .find(|c: char| !c.is_alphabetic())
+                .unwrap_or(rest.len());
+            let unit = rest[..unit_end].to_lowercase();
+            rest = &rest[unit_end..];
+
+            minutes += match unit.chars().next() {
+                Some('h' | 'u') => value * 60.0,
+                Some('d') => value * 24.0 * 60.0,
+                Some('s') => value / 60.0,
+                _ => value,
+            };
+            found = true;
+        }
+
+        let minutes = minutes.round() as u32;
+
+        (found && minutes > 0).then_some(minutes)
+    }
+
+    fn title_from_file_name(file_name: &str) -> String {
+        let name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+
+        match name.rsplit_once('.') {
+            Some((stem, extension)) if extension.eq_ignore_ascii_case("cook") => stem,
+            _ => name,
+        }
+        .trim()
+        .to_string()
+    }
+
+    fn is_cook_file(name: &str) -> bool {
+        name.rsplit_once('.')
+            .is_some_and(|(_, extension)| extension.eq_ignore_ascii_case("cook"))
+    }
+
+    /// The `.cook` files in a zip, with their paths. Anything else, like
+    /// images next to the recipes, is skipped. Refuses zips whose `.cook`
+    /// files unpack to more than [`MAX_IMPORT_SIZE`].
+    pub fn read_zip(bytes: &[u8]) -> zip::result::ZipResult<Vec<(String, String)>> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+        let mut files = Vec::new();
+        let mut unpacked = 0;
+
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let name = file.name().to_string();
+
+            // Finder puts metadata for every file in __MACOSX
+            if file.is_dir() || name.starts_with("__MACOSX/") || !is_cook_file(&name) {
+                continue;
+            }
+
+            // The sizes in the zip can't be trusted, so stop reading at the limit
+            let mut contents = Vec::new();
+            let remaining = (MAX_IMPORT_SIZE - unpacked) as u64;
+            (&mut file).take(remaining + 1).read_to_end(&mut contents)?;
+            unpacked += contents.len();
+
+            if unpacked > MAX_IMPORT_SIZE {
+                return Err(zip::result::ZipError::UnsupportedArchive(
+                    "The recipes are too large unpacked",
+                ));
+            }
+
+            files.push((name, String::from_utf8_lossy(&contents).into_owned()));
+        }
+
+        Ok(files)
+    }
+
+    /// Serves `/recipe/{id}/cook`, a recipe as a `.cook` download named
+    /// after its title.
+    pub async fn serve_recipe(State(db): State<Pool>, Path(recipe_id): Path<i64>) -> Response {
+        let recipe = db.read(move |db| load_recipe(db, recipe_id)).await;
+
+        let recipe = match recipe {
+            Ok(Some(recipe)) => recipe,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => {
+                leptos::logging::error!("Could not load recipe {recipe_id}: {err}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        (
+            [
+                (CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+                (
+                    CONTENT_DISPOSITION,
+                    crate::attachment_disposition(&recipe.title, "cook"),
+                ),
+            ],
+            write(&recipe),
+        )
+            .into_response()
+    }
+}
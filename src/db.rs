@@ -0,0 +1,159 @@
+//! The connections to the database.
+//!
+//! SQLite in WAL mode allows one writer next to any number of readers, so
+//! there is a single connection to write with and a few read-only ones,
+//! `NOM_DB_READERS` (4 by default). Queries run on tokio's blocking threads,
+//! so a slow import or search doesn't hold up the workers serving pages.
+//!
+//! The pool is created in `main` and handed to the server functions through
+//! context, see [`use_db`], and to the other handlers as axum state.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use rusqlite::{Connection, OpenFlags};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::migrations::{MigrationError, migrate};
+
+/// How long to wait for another connection to finish writing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DEFAULT_READERS: usize = 4;
+
+pub fn readers_from_env() -> usize {
+    std::env::var("NOM_DB_READERS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&readers| readers > 0)
+        .unwrap_or(DEFAULT_READERS)
+}
+
+/// Sets up a connection to write with. The journal mode is stored in the
+/// database, so the readers get WAL from this too.
+pub fn configure_writer(conn: &Connection) -> rusqlite::Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update(None, "journal_mode", "WAL")
+}
+
+/// The database of the current request, for server functions.
+pub fn use_db() -> Pool {
+    leptos::prelude::expect_context()
+}
+
+/// Cheap to clone, all clones share the same connections.
+#[derive(Clone)]
+pub struct Pool {
+    writer: Arc<Mutex<Connection>>,
+    /// Empty for in-memory databases, which can't be opened twice
+    readers: Arc<[Arc<Mutex<Connection>>]>,
+    /// Where the next reader is looked for, to spread the waiting when all
+    /// of them are busy
+    next_reader: Arc<AtomicUsize>,
+}
+
+impl Pool {
+    /// Opens and migrates the database at `path`, `:memory:` for one that
+    /// only lives as long as the pool.
+    pub fn open(path: &str, readers: usize) -> Result<Self, MigrationError> {
+        let mut conn = Connection::open(path)?;
+
+        configure_writer(&conn)?;
+
+        for migration in migrate(&mut conn, false)? {
+            leptos::logging::log!(
+                "Applied migration {}: {}",
+                migration.version,
+                migration.description
+            );
+        }
+
+        Ok(Pool::new(conn, readers)?)
+    }
+
+    /// Opens `readers` read-only connections next to `writer`, which has to
+    /// be set up with [`configure_writer`] and migrated already.
+    pub fn new(writer: Connection, readers: usize) -> rusqlite::Result<Self> {
+        let readers = match writer.path().filter(|path| !path.is_empty()) {
+            Some(path) => (0..readers.max(1))
+                .map(|_| {
+                    let conn = Connection::open_with_flags(
+                        path,
+                        OpenFlags::SQLITE_OPEN_READ_ONLY
+                            | OpenFlags::SQLITE_OPEN_URI
+                            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                    )?;
+                    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+                    Ok(Arc::new(Mutex::new(conn)))
+                })
+                .collect::<rusqlite::Result<Arc<[_]>>>()?,
+            None => Arc::new([]),
+        };
+
+        Ok(Pool {
+            writer: Arc::new(Mutex::new(writer)),
+            readers,
+            next_reader: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Runs `f` with one of the read-only connections. Use [`Pool::write`]
+    /// for anything that changes the database.
+    pub async fn read<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&Connection) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.reader().await;
+
+        run(conn, move |conn| f(conn)).await
+    }
+
+    /// Runs `f` with the connection to write with, waiting for any other
+    /// write to finish first.
+    pub async fn write<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Connection) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.writer.clone().lock_owned().await;
+
+        run(conn, f).await
+    }
+
+    async fn reader(&self) -> OwnedMutexGuard<Connection> {
+        if self.readers.is_empty() {
+            return self.writer.clone().lock_owned().await;
+        }
+
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed);
+
+        for i in 0..self.readers.len() {
+            let reader = &self.readers[(start + i) % self.readers.len()];
+
+            if let Ok(conn) = reader.clone().try_lock_owned() {
+                return conn;
+            }
+        }
+
+        self.readers[start % self.readers.len()]
+            .clone()
+            .lock_owned()
+            .await
+    }
+}
+
+async fn run<T, F>(mut conn: OwnedMutexGuard<Connection>, f: F) -> T
+where
+    F: FnOnce(&mut Connection) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(move || f(&mut conn)).await {
+        Ok(value) => value,
+        // Fail the request the same way as when the query ran on this task
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(err) => panic!("Database task failed: {err}"),
+    }
+}
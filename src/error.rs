@@ -0,0 +1,142 @@
+use std::fmt;
+
+use leptos::prelude::*;
+use leptos::server_fn::Bytes;
+use leptos::server_fn::codec::JsonEncoding;
+use leptos::server_fn::error::{FromServerFnError, ServerFnErrorErr};
+use leptos::server_fn::{Decodes, Encodes};
+use serde::{Deserialize, Serialize};
+
+/// What went wrong in a server function, so pages can tell people something
+/// more useful than a crash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NomError {
+    /// There is no such thing, e.g. "recipe 12"
+    NotFound(String),
+    /// The input can't be used as it is
+    Validation(String),
+    /// The input clashes with what is stored already
+    Conflict(String),
+    /// Someone else changed it since it was loaded
+    Stale(String),
+    /// Nobody is logged in
+    NotLoggedIn,
+    /// Logged in, but not allowed to do this
+    Unauthorized(String),
+    /// The database failed. The details are logged on the server only.
+    Database,
+    /// Calling the server failed, e.g. because it can't be reached
+    ServerFn(String),
+}
+
+impl NomError {
+    pub fn not_found(what: impl fmt::Display) -> Self {
+        NomError::NotFound(what.to_string())
+    }
+
+    pub fn unknown_recipe(id: i64) -> Self {
+        NomError::not_found(format!("recipe {id}"))
+    }
+
+    /// The status of the response a server function fails with.
+    #[cfg(feature = "ssr")]
+    pub fn status(&self) -> axum::http::StatusCode {
+        use axum::http::StatusCode;
+
+        match self {
+            NomError::NotFound(_) => StatusCode::NOT_FOUND,
+            NomError::Validation(_) => StatusCode::BAD_REQUEST,
+            NomError::Conflict(_) | NomError::Stale(_) => StatusCode::CONFLICT,
+            NomError::NotLoggedIn => StatusCode::UNAUTHORIZED,
+            NomError::Unauthorized(_) => StatusCode::FORBIDDEN,
+            NomError::Database | NomError::ServerFn(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// What to tell the people using nom, who don't need the details.
+    pub fn friendly(&self) -> &'static str {
+        match self {
+            NomError::NotFound(_) => "Dit bestaat niet (meer)",
+            NomError::Validation(_) => "Dat klopt niet helemaal, kijk nog eens naar wat je invulde",
+            NomError::Conflict(_) => "Dat bestaat al",
+            NomError::Stale(_) => "Het recept is intussen gewijzigd",
+            NomError::NotLoggedIn => "Log eerst in",
+            NomError::Unauthorized(_) => "Dat mag je niet met dit account",
+            NomError::Database => "Er ging iets mis met de database, probeer het later nog eens",
+            NomError::ServerFn(_) => "De server is niet bereikbaar, probeer het later nog eens",
+        }
+    }
+}
+
+impl fmt::Display for NomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NomError::NotFound(what) => write!(f, "Unknown {what}"),
+            NomError::Validation(msg)
+            | NomError::Conflict(msg)
+            | NomError::Stale(msg)
+            | NomError::Unauthorized(msg) => f.write_str(msg),
+            NomError::NotLoggedIn => f.write_str("Not logged in"),
+            NomError::Database => f.write_str("Database error"),
+            NomError::ServerFn(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for NomError {}
+
+#[cfg(feature = "ssr")]
+impl From<crate::store::StoreError> for NomError {
+    fn from(err: crate::store::StoreError) -> Self {
+        leptos::logging::error!("Recipe store error: {err}");
+
+        NomError::Database
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<rusqlite::Error> for NomError {
+    fn from(err: rusqlite::Error) -> Self {
+        leptos::logging::error!("Database error: {err}");
+
+        NomError::Database
+    }
+}
+
+impl From<ServerFnError> for NomError {
+    fn from(err: ServerFnError) -> Self {
+        NomError::ServerFn(err.to_string())
+    }
+}
+
+impl FromServerFnError for NomError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        NomError::ServerFn(value.to_string())
+    }
+
+    /// Also gives the failed response the status of the error, which is
+    /// always 500 otherwise.
+    fn ser(&self) -> Bytes {
+        #[cfg(feature = "ssr")]
+        if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+            response.set_status(self.status());
+        }
+
+        Self::Encoder::encode(self).unwrap_or_else(|err| {
+            Self::Encoder::encode(&NomError::ServerFn(err.to_string()))
+                .expect("Errors always serialize")
+        })
+    }
+
+    fn de(data: Bytes) -> Self {
+        Self::Encoder::decode(data).unwrap_or_else(|err| NomError::ServerFn(err.to_string()))
+    }
+}
+
+/// Tells people what went wrong, without the details meant for the logs.
+#[component]
+pub fn ErrorMessage(error: NomError) -> impl IntoView {
+    view! { <p class="error-message">{error.friendly()}</p> }
+}
@@ -0,0 +1,357 @@
+use std::str::FromStr;
+
+use leptos::prelude::*;
+use leptos::server_fn::codec::{MultipartData, MultipartFormData};
+use serde::{Deserialize, Serialize};
+
+use crate::recipe::{Ingredient, Recipe};
+
+/// Version of the export format. Bump it when the format changes in a way
+/// older versions of nom can't read.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The whole recipe collection, as written by the export and read by the
+/// import. Images are not part of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportDocument {
+    pub version: u32,
+    pub recipes: Vec<ExportedRecipe>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedRecipe {
+    pub title: String,
+    pub servings: Option<u32>,
+    /// In minutes
+    pub prep_time: Option<u32>,
+    /// In minutes
+    pub cook_time: Option<u32>,
+    pub ingredients: Vec<Ingredient>,
+    pub instructions: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl From<Recipe> for ExportedRecipe {
+    fn from(recipe: Recipe) -> Self {
+        ExportedRecipe {
+            title: recipe.title,
+            servings: recipe.servings,
+            prep_time: recipe.prep_time,
+            cook_time: recipe.cook_time,
+            ingredients: recipe.ingredients,
+            instructions: recipe.instructions,
+            tags: recipe.tags,
+        }
+    }
+}
+
+impl From<ExportedRecipe> for Recipe {
+    fn from(recipe: ExportedRecipe) -> Self {
+        Recipe {
+            title: recipe.title,
+            servings: recipe.servings,
+            prep_time: recipe.prep_time,
+            cook_time: recipe.cook_time,
+            ingredients: recipe.ingredients,
+            instructions: recipe.instructions,
+            tags: recipe.tags,
+            images: Vec::new(),
+            version: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Adds the imported recipes, skipping those with a title we already have
+    Merge,
+    /// Deletes all existing recipes first
+    Replace,
+}
+
+impl ImportMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImportMode::Merge => "merge",
+            ImportMode::Replace => "replace",
+        }
+    }
+}
+
+impl FromStr for ImportMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [ImportMode::Merge, ImportMode::Replace]
+            .into_iter()
+            .find(|mode| mode.as_str() == s)
+            .ok_or_else(|| format!("Unknown import mode {s}"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Titles of the recipes that were skipped as duplicates
+    pub skipped: Vec<String>,
+}
+
+/// Imports an export document. Expects a `mode` field with the [`ImportMode`]
+/// and a `document` field with the JSON file.
+#[server(input = MultipartFormData)]
+pub async fn import_recipes(data: MultipartData) -> Result<ImportSummary, ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+    use crate::store::use_store;
+
+    let store = use_store();
+    require_role(&use_db(), Role::Admin).await?;
+
+    let mut data = data.into_inner().unwrap();
+
+    let mut mode = None;
+    let mut document = None;
+
+    while let Some(field) = data.next_field().await? {
+        match field.name() {
+            Some("mode") => {
+                mode = Some(
+                    field
+                        .text()
+                        .await?
+                        .parse::<ImportMode>()
+                        .map_err(ServerFnError::new)?,
+                )
+            }
+            Some("document") => document = Some(field.bytes().await?),
+            _ => {}
+        }
+    }
+
+    let mode = mode.ok_or_else(|| ServerFnError::new("Missing import mode"))?;
+    let document = document.ok_or_else(|| ServerFnError::new("Missing document"))?;
+
+    let document = collection::parse_document(&document)?;
+
+    Ok(store.import(document, mode).await?)
+}
+
+/// Reading and writing the collection, separate from the server functions so
+/// it can be used on any connection.
+#[cfg(feature = "ssr")]
+pub mod collection {
+    use std::collections::HashSet;
+    use std::fmt;
+
+    use axum::Extension;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+    use axum::response::{IntoResponse, Response};
+    use rusqlite::{Connection, Transaction};
+
+    use super::{ExportDocument, FORMAT_VERSION, ImportMode, ImportSummary};
+    use crate::auth::{Role, User, allowed};
+    use crate::db::Pool;
+    use crate::recipe::{delete_recipe_rows, insert_recipe, load_recipe};
+
+    #[derive(Debug)]
+    pub enum ImportError {
+        Sqlite(rusqlite::Error),
+        Json(serde_json::Error),
+        /// The document was exported by a newer version of nom.
+        UnsupportedVersion {
+            document: u32,
+            supported: u32,
+        },
+    }
+
+    impl fmt::Display for ImportError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ImportError::Sqlite(err) => write!(f, "database error: {err}"),
+                ImportError::Json(err) => write!(f, "invalid export document: {err}"),
+                ImportError::UnsupportedVersion {
+                    document,
+                    supported,
+                } => write!(
+                    f,
+                    "export format version {document} is newer than the latest supported version {supported}"
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for ImportError {}
+
+    impl From<rusqlite::Error> for ImportError {
+        fn from(err: rusqlite::Error) -> Self {
+            ImportError::Sqlite(err)
+        }
+    }
+
+    impl From<serde_json::Error> for ImportError {
+        fn from(err: serde_json::Error) -> Self {
+            ImportError::Json(err)
+        }
+    }
+
+    /// Titles are compared case-insensitively and without surrounding
+    /// whitespace to find duplicates.
+    fn title_key(title: &str) -> String {
+        title.trim().to_lowercase()
+    }
+
+    pub fn parse_document(json: &[u8]) -> Result<ExportDocument, ImportError> {
+        let document: ExportDocument = serde_json::from_slice(json)?;
+
+        if document.version > FORMAT_VERSION {
+            return Err(ImportError::UnsupportedVersion {
+                document: document.version,
+                supported: FORMAT_VERSION,
+            });
+        }
+
+        Ok(document)
+    }
+
+    /// All recipes, in the order they were added.
+    pub fn export_collection(conn: &Connection) -> rusqlite::Result<ExportDocument> {
+        let ids = conn
+            .prepare_cached("SELECT id FROM recipes ORDER BY id;")?
+            .query_map((), |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut recipes = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            if let Some(recipe) = load_recipe(conn, id)? {
+                recipes.push(recipe.into());
+            }
+        }
+
+        Ok(ExportDocument {
+            version: FORMAT_VERSION,
+            recipes,
+        })
+    }
+
+    /// Imports a document in a single transaction. Recipes with a title that
+    /// is already taken, by an existing recipe or one earlier in the
+    /// document, are skipped.
+    pub fn import_collection(
+        conn: &mut Connection,
+        document: ExportDocument,
+        mode: ImportMode,
+    ) -> rusqlite::Result<ImportSummary> {
+        let transaction = conn.transaction()?;
+        let imported = import_rows(&transaction, document, mode)?;
+        transaction.commit()?;
+
+        crate::images::storage::remove(&imported.removed_images);
+
+        Ok(imported.summary)
+    }
+
+    /// What [`import_rows`] changed.
+    pub(crate) struct Imported {
+        pub summary: ImportSummary,
+        /// Ids of the new recipes
+        pub created: Vec<i64>,
+        /// Ids of the recipes a replacing import deleted
+        pub deleted: Vec<i64>,
+        /// Ids of the images of the deleted recipes, to remove once committed
+        pub removed_images: Vec<i64>,
+    }
+
+    /// [`import_collection`], in a transaction of the caller.
+    pub(crate) fn import_rows(
+        transaction: &Transaction,
+        document: ExportDocument,
+        mode: ImportMode,
+    ) -> rusqlite::Result<Imported> {
+        let mut imported = Imported {
+            summary: ImportSummary {
+                imported: 0,
+                skipped: Vec::new(),
+            },
+            created: Vec::new(),
+            deleted: Vec::new(),
+            removed_images: Vec::new(),
+        };
+
+        if mode == ImportMode::Replace {
+            imported.deleted = transaction
+                .prepare_cached("SELECT id FROM recipes;")?
+                .query_map((), |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for &id in &imported.deleted {
+                imported
+                    .removed_images
+                    .extend(delete_recipe_rows(transaction, id)?);
+            }
+        }
+
+        let mut titles = transaction
+            .prepare_cached("SELECT title FROM recipes;")?
+            .query_map((), |row| row.get::<_, String>(0))?
+            .map(|title| title.map(|title| title_key(&title)))
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        for recipe in document.recipes {
+            if !titles.insert(title_key(&recipe.title)) {
+                imported.summary.skipped.push(recipe.title);
+                continue;
+            }
+
+            imported
+                .created
+                .push(insert_recipe(transaction, &recipe.into())?);
+            imported.summary.imported += 1;
+        }
+
+        Ok(imported)
+    }
+
+    /// Serves `/export.json`, the whole collection as a download. Only for
+    /// admins.
+    pub async fn serve_export(State(db): State<Pool>, user: Option<Extension<User>>) -> Response {
+        if !allowed(user.as_ref().map(|Extension(user)| user), Role::Admin) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+
+        let document = db.read(export_collection).await;
+
+        let json = match document.map(|document| serde_json::to_vec_pretty(&document)) {
+            Ok(Ok(json)) => json,
+            Ok(Err(err)) => {
+                leptos::logging::error!("Could not serialize export: {err}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            Err(err) => {
+                leptos::logging::error!("Could not export recipes: {err}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        let filename = format!(
+            "nom-export-{}.json",
+            chrono::Local::now().date_naive().format("%Y-%m-%d")
+        );
+
+        (
+            [
+                (CONTENT_TYPE, "application/json".to_string()),
+                (
+                    CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{filename}\""),
+                ),
+            ],
+            json,
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,369 @@
+use leptos::prelude::*;
+use leptos::server_fn::codec::{MultipartData, MultipartFormData};
+
+use crate::error::{ErrorMessage, NomError};
+
+pub fn large_url(image_id: i64) -> String {
+    format!("/images/{image_id}/large")
+}
+
+pub fn thumb_url(image_id: i64) -> String {
+    format!("/images/{image_id}/thumb")
+}
+
+/// Uploads the files selected in a file input to a recipe. Does nothing if no
+/// files were selected.
+pub async fn upload_selected(
+    recipe_id: i64,
+    input: &web_sys::HtmlInputElement,
+) -> Result<Vec<i64>, ServerFnError> {
+    let Some(files) = input.files().filter(|files| files.length() > 0) else {
+        return Ok(Vec::new());
+    };
+
+    let form_data = web_sys::FormData::new().unwrap();
+    form_data
+        .append_with_str("recipe", &recipe_id.to_string())
+        .unwrap();
+
+    for i in 0..files.length() {
+        let file = files.get(i).unwrap();
+        form_data.append_with_blob("image", &file).unwrap();
+    }
+
+    upload_images(form_data.into()).await
+}
+
+/// Adds images to a recipe. Expects a `recipe` field with the recipe id and
+/// any number of `image` fields with the image files.
+#[server(input = MultipartFormData)]
+pub async fn upload_images(data: MultipartData) -> Result<Vec<i64>, ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    let mut data = data.into_inner().unwrap();
+
+    let mut recipe_id = None;
+    let mut uploads = Vec::new();
+
+    while let Some(mut field) = data.next_field().await? {
+        match field.name() {
+            Some("recipe") => recipe_id = Some(field.text().await?.parse::<i64>()?),
+            Some("image") => {
+                let mut upload = Vec::new();
+
+                // Read in chunks, so a huge file is refused before it's in memory
+                while let Some(chunk) = field.chunk().await? {
+                    if upload.len() + chunk.len() > storage::MAX_UPLOAD_SIZE {
+                        return Err(ServerFnError::new(format!(
+                            "Images can be at most {} MB",
+                            storage::MAX_UPLOAD_SIZE / 1024 / 1024
+                        )));
+                    }
+
+                    upload.extend_from_slice(&chunk);
+                }
+
+                uploads.push(upload);
+            }
+            _ => {}
+        }
+    }
+
+    let recipe_id = recipe_id.ok_or_else(|| ServerFnError::new("Missing recipe id"))?;
+
+    let mut image_ids = Vec::with_capacity(uploads.len());
+
+    for upload in uploads {
+        let variants = tokio::task::spawn_blocking(move || storage::resize(&upload)).await??;
+
+        let image_id = db
+            .write(move |db| {
+                let transaction = db.transaction()?;
+
+                let recipe_exists = transaction
+                    .prepare_cached("SELECT 1 FROM recipes WHERE id = (?1);")?
+                    .exists((recipe_id,))?;
+
+                if !recipe_exists {
+                    return Err(ServerFnError::new(format!("Unknown recipe {recipe_id}")));
+                }
+
+                transaction.execute(
+                    "
+                    INSERT INTO images (recipe, position)
+                    VALUES (?1, (SELECT coalesce(max(position), -1) + 1 FROM images WHERE recipe = ?1));
+                    ",
+                    (recipe_id,),
+                )?;
+
+                let image_id = transaction.last_insert_rowid();
+
+                // Only commit once the files are on disk, so we never point to
+                // images that don't exist
+                storage::write(image_id, &variants)?;
+
+                transaction.commit()?;
+
+                Ok(image_id)
+            })
+            .await?;
+
+        image_ids.push(image_id);
+    }
+
+    Ok(image_ids)
+}
+
+#[server]
+pub async fn delete_image(image_id: i64) -> Result<(), ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| db.execute("DELETE FROM images WHERE id = (?1);", (image_id,)))
+        .await?;
+
+    storage::remove(&[image_id]);
+
+    Ok(())
+}
+
+/// The first image of a recipe as hero, the others as thumbnails below it.
+/// With a `share_token` the images are loaded through the share link.
+#[component]
+pub fn RecipeImages(
+    images: Vec<i64>,
+    #[prop(optional_no_strip)] share_token: Option<String>,
+) -> impl IntoView {
+    let url = move |image: i64, variant: &str| match &share_token {
+        Some(token) => crate::share::shared_image_url(token, image, variant),
+        None => format!("/images/{image}/{variant}"),
+    };
+
+    let mut images = images.into_iter();
+
+    images.next().map(|hero| {
+        let others = images
+            .map(|image| {
+                view! {
+                    <a href=url(image, "large")>
+                        <img class="recipe-gallery-image" src=url(image, "thumb") alt=""/>
+                    </a>
+                }
+            })
+            .collect_view();
+
+        view! {
+            <img class="recipe-hero" src=url(hero, "large") alt=""/>
+            <div class="recipe-gallery">{others}</div>
+        }
+    })
+}
+
+/// Thumbnails of the images of a recipe, each with a button to delete it.
+#[component]
+pub fn ImageManager(images: RwSignal<Vec<i64>>) -> impl IntoView {
+    let error = RwSignal::new(None::<NomError>);
+
+    let remove = move |image_id: i64| {
+        leptos::reactive::spawn_local(async move {
+            match delete_image(image_id).await {
+                Ok(()) => images.update(|images| images.retain(|i| *i != image_id)),
+                Err(err) => error.set(Some(err.into())),
+            }
+        });
+    };
+
+    view! {
+        {move || error.get().map(|error| view! { <ErrorMessage error/> })}
+        <div class="recipe-gallery">
+            {move || images.get().into_iter().map(|image| {
+                view! {
+                    <div class="image-manager-item">
+                        <img class="recipe-gallery-image" src=thumb_url(image) alt=""/>
+                        <button
+                            type="button"
+                            class:link-button
+                            class:button-negative
+                            on:click=move |_| remove(image)
+                        >"Verwijder"</button>
+                    </div>
+                }
+            }).collect_view()}
+        </div>
+    }
+}
+
+/// Storing, resizing and serving the image files. Every image is stored as a
+/// directory named after its id, holding the resized variants.
+#[cfg(feature = "ssr")]
+pub mod storage {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+    use axum::response::{IntoResponse, Response};
+    use image::codecs::jpeg::JpegEncoder;
+    use image::imageops::FilterType;
+    use image::{DynamicImage, ImageDecoder, ImageReader, ImageResult, Limits};
+
+    /// Largest image file that can be uploaded
+    pub const MAX_UPLOAD_SIZE: usize = 20 * 1024 * 1024;
+    /// Longest side of an uploaded image. A small file can claim to be a huge
+    /// image, so this is checked before decoding.
+    const MAX_UPLOAD_SIDE: u32 = 10_000;
+    /// Memory a decoder may use for a single image
+    const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+
+    /// Longest side of the image shown on the recipe page
+    const LARGE_SIZE: u32 = 1600;
+    /// Width and height of the square thumbnails
+    const THUMB_SIZE: u32 = 320;
+
+    const JPEG_QUALITY: u8 = 85;
+
+    pub struct Variants {
+        large: Vec<u8>,
+        thumb: Vec<u8>,
+    }
+
+    /// Images are stored in `NOM_IMAGES`, or next to the database by default.
+    pub fn images_dir() -> PathBuf {
+        match std::env::var("NOM_IMAGES") {
+            Ok(dir) => dir.into(),
+            Err(_) => std::path::Path::new(&crate::db_path())
+                .parent()
+                .unwrap_or(std::path::Path::new("."))
+                .join("images"),
+        }
+    }
+
+    fn image_dir(image_id: i64) -> PathBuf {
+        images_dir().join(image_id.to_string())
+    }
+
+    /// Decodes an uploaded image and creates the variants we serve. Honours
+    /// the EXIF orientation, which phone cameras rely on.
+    ///
+    /// Images larger than [`MAX_UPLOAD_SIDE`] are refused with
+    /// [`image::ImageError::Limits`].
+    pub fn resize(upload: &[u8]) -> ImageResult<Variants> {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_UPLOAD_SIDE);
+        limits.max_image_height = Some(MAX_UPLOAD_SIDE);
+        limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+        let mut reader = ImageReader::new(Cursor::new(upload)).with_guessed_format()?;
+        reader.limits(limits);
+
+        let mut decoder = reader.into_decoder()?;
+        let orientation = decoder.orientation()?;
+
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+
+        let large = if image.width() > LARGE_SIZE || image.height() > LARGE_SIZE {
+            image.resize(LARGE_SIZE, LARGE_SIZE, FilterType::Lanczos3)
+        } else {
+            image.clone()
+        };
+
+        let thumb = image.resize_to_fill(THUMB_SIZE, THUMB_SIZE, FilterType::Lanczos3);
+
+        Ok(Variants {
+            large: encode_jpeg(&large)?,
+            thumb: encode_jpeg(&thumb)?,
+        })
+    }
+
+    fn encode_jpeg(image: &DynamicImage) -> ImageResult<Vec<u8>> {
+        let mut encoded = Vec::new();
+
+        // JPEG can't store transparency, so drop the alpha channel first
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY))?;
+
+        Ok(encoded)
+    }
+
+    pub fn write(image_id: i64, variants: &Variants) -> std::io::Result<()> {
+        let dir = image_dir(image_id);
+
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("large.jpg"), &variants.large)?;
+        std::fs::write(dir.join("thumb.jpg"), &variants.thumb)?;
+
+        Ok(())
+    }
+
+    /// Removes the files of the given images. Failures are only logged, as
+    /// the images are already gone from the database at this point.
+    pub fn remove(image_ids: &[i64]) {
+        for image_id in image_ids {
+            let dir = image_dir(*image_id);
+
+            if let Err(err) = std::fs::remove_dir_all(&dir)
+                && err.kind() != std::io::ErrorKind::NotFound
+            {
+                leptos::logging::error!("Could not remove image {}: {err}", dir.display());
+            }
+        }
+    }
+
+    /// Deletes the image rows of a recipe, returning the ids so their files
+    /// can be removed once the transaction is committed.
+    pub(crate) fn delete_recipe_images(
+        transaction: &rusqlite::Transaction,
+        recipe_id: i64,
+    ) -> rusqlite::Result<Vec<i64>> {
+        let image_ids = transaction
+            .prepare_cached("SELECT id FROM images WHERE recipe = (?1);")?
+            .query_map((recipe_id,), |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        transaction.execute("DELETE FROM images WHERE recipe = (?1);", (recipe_id,))?;
+
+        Ok(image_ids)
+    }
+
+    pub(crate) fn recipe_images(
+        conn: &rusqlite::Connection,
+        recipe_id: i64,
+    ) -> rusqlite::Result<Vec<i64>> {
+        conn.prepare_cached("SELECT id FROM images WHERE recipe = (?1) ORDER BY position;")?
+            .query_map((recipe_id,), |row| row.get(0))?
+            .collect()
+    }
+
+    /// Serves `/images/{id}/{variant}`. Image ids are never reused, so the
+    /// files can be cached forever, by the browser only. A shared cache would
+    /// hand them out without logging in.
+    pub async fn serve_image(Path((image_id, variant)): Path<(i64, String)>) -> Response {
+        let file = match variant.as_str() {
+            "large" => "large.jpg",
+            "thumb" => "thumb.jpg",
+            _ => return StatusCode::NOT_FOUND.into_response(),
+        };
+
+        match tokio::fs::read(image_dir(image_id).join(file)).await {
+            Ok(bytes) => (
+                [
+                    (CONTENT_TYPE, "image/jpeg"),
+                    (CACHE_CONTROL, "private, max-age=31536000, immutable"),
+                ],
+                bytes,
+            )
+                .into_response(),
+            Err(_) => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+}
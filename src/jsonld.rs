@@ -0,0 +1,448 @@
+use leptos::prelude::*;
+use leptos::server_fn::codec::{MultipartData, MultipartFormData};
+use serde::{Deserialize, Serialize};
+
+use crate::recipe::RawRecipe;
+
+/// A recipe found in a web page, to be reviewed before it is saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractedRecipe {
+    pub recipe: RawRecipe,
+    /// Url of the main image on the original site, if any
+    pub image: Option<String>,
+}
+
+/// Reads the schema.org `Recipe` embedded in a saved web page. Expects a
+/// `page` field with the html file or its pasted source. Empty fields are
+/// ignored, so a form can send both.
+#[server(input = MultipartFormData)]
+pub async fn extract_recipe(data: MultipartData) -> Result<ExtractedRecipe, ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    require_role(&use_db(), Role::Editor).await?;
+
+    let mut data = data.into_inner().unwrap();
+
+    let mut page = None;
+
+    while let Some(field) = data.next_field().await? {
+        if field.name() == Some("page") {
+            let bytes = field.bytes().await?;
+
+            if !bytes.is_empty() {
+                page = Some(bytes);
+            }
+        }
+    }
+
+    let page = page.ok_or_else(|| ServerFnError::new("Missing page"))?;
+
+    parse::extract(&String::from_utf8_lossy(&page))
+        .ok_or_else(|| ServerFnError::new("No schema.org Recipe found in the page"))
+}
+
+/// Finding and reading the JSON-LD in a page. Everything is read from the
+/// page itself, nothing is fetched.
+#[cfg(feature = "ssr")]
+pub mod parse {
+    use serde_json::{Map, Value};
+
+    use super::ExtractedRecipe;
+    use crate::recipe::RawRecipe;
+
+    /// Extracts the first recipe from an html page, or from bare JSON-LD.
+    pub fn extract(page: &str) -> Option<ExtractedRecipe> {
+        let mut blocks = json_ld_blocks(page);
+
+        if blocks.is_empty() {
+            blocks.push(page);
+        }
+
+        blocks
+            .into_iter()
+            .filter_map(parse_json)
+            .find_map(|value| find_recipe(&value).map(read_recipe))
+    }
+
+    /// The contents of all `<script type="application/ld+json">` tags.
+    fn json_ld_blocks(html: &str) -> Vec<&str> {
+        // ASCII lowercasing keeps byte offsets the same, so we can search the
+        // lowered page and slice the original
+        let lowered = html.to_ascii_lowercase();
+        let mut blocks = Vec::new();
+        let mut pos = 0;
+
+        while let Some(start) = lowered[pos..].find("<script").map(|i| pos + i) {
+            let Some(tag_end) = lowered[start..].find('>').map(|i| start + i + 1) else {
+                break;
+            };
+            let Some(end) = lowered[tag_end..].find("</script").map(|i| tag_end + i) else {
+                break;
+            };
+
+            if lowered[start..tag_end].contains("application/ld+json") {
+                blocks.push(&html[tag_end..end]);
+            }
+
+            pos = end;
+        }
+
+        blocks
+    }
+
+    fn parse_json(block: &str) -> Option<Value> {
+        let block = block.trim();
+        let block = block
+            .strip_prefix("//<![CDATA[")
+            .or_else(|| block.strip_prefix("<![CDATA["))
+            .unwrap_or(block);
+        let block = block
+            .strip_suffix("//]]>")
+            .or_else(|| block.strip_suffix("]]>"))
+            .unwrap_or(block);
+
+        serde_json::from_str(block)
+            .or_else(|_| serde_json::from_str(&escape_control_chars(block)))
+            .ok()
+    }
+
+    /// Some sites put raw newlines and tabs inside strings, which JSON doesn't
+    /// allow. Escapes those, leaving the whitespace between values alone.
+    fn escape_control_chars(json: &str) -> String {
+        let mut escaped = String::with_capacity(json.len());
+        let mut in_string = false;
+        let mut backslash = false;
+
+        for c in json.chars() {
+            match c {
+                '"' if !backslash => in_string = !in_string,
+                '\n' if in_string => {
+                    escaped.push_str("\\n");
+                    continue;
+                }
+                '\r' | '\t' if in_string => {
+                    escaped.push(' ');
+                    continue;
+                }
+                _ => {}
+            }
+
+            backslash = c == '\\' && !backslash;
+            escaped.push(c);
+        }
+
+        escaped
+    }
+
+    fn has_type(object: &Map<String, Value>, wanted: &str) -> bool {
+        let matches = |value: &Value| {
+            value.as_str().is_some_and(|t| {
+                t == wanted
+                    || t.ends_with(&format!("/{wanted}"))
+                    || t.ends_with(&format!(":{wanted}"))
+            })
+        };
+
+        match object.get("@type") {
+            Some(Value::Array(types)) => types.iter().any(matches),
+            Some(value) => matches(value),
+            None => false,
+        }
+    }
+
+    /// Searches the whole document, as the recipe may be at the top, in an
+    /// `@graph` or nested in another node like `mainEntity`.
+    fn find_recipe(value: &Value) -> Option<&Map<String, Value>> {
+        match value {
+            Value::Object(object) if has_type(object, "Recipe") => Some(object),
+            Value::Object(object) => object.values().find_map(find_recipe),
+            Value::Array(values) => values.iter().find_map(find_recipe),
+            _ => None,
+        }
+    }
+
+    fn read_recipe(recipe: &Map<String, Value>) -> ExtractedRecipe {
+        let title = recipe
+            .get("name")
+            .or_else(|| recipe.get("headline"))
+            .and_then(text)
+            .unwrap_or_default();
+
+        let mut ingredients = Vec::new();
+        if let Some(value) = recipe
+            .get("recipeIngredient")
+            .or_else(|| recipe.get("ingredients"))
+        {
+            collect_lines(value, &mut ingredients);
+        }
+
+        let mut instructions = Vec::new();
+        if let Some(value) = recipe.get("recipeInstructions") {
+            collect_instructions(value, &mut instructions);
+        }
+
+        let time = |key: &str| {
+            recipe
+                .get(key)
+                .and_then(text)
+                .and_then(|t| parse_duration(&t))
+        };
+
+        let prep_time = time("prepTime");
+        let total_time = time("totalTime");
+
+        // Sites often only give the total time
+        let cook_time = time("cookTime").or_else(|| {
+            total_time
+                .map(|total| total.saturating_sub(prep_time.unwrap_or(0)))
+                .filter(|cook| *cook > 0)
+        });
+
+        ExtractedRecipe {
+            recipe: RawRecipe {
+                title,
+                servings: recipe.get("recipeYield").and_then(servings),
+                prep_time,
+                cook_time,
+                ingredients: ingredients.join("\n"),
+                instructions: instructions.join("\n"),
+                tags: Vec::new(),
+            },
+            image: recipe.get("image").and_then(image_url),
+        }
+    }
+
+    /// A single text value, cleaned up. Arrays give their first text.
+    fn text(value: &Value) -> Option<String> {
+        match value {
+            Value::String(s) => Some(clean_text(s)).filter(|s| !s.is_empty()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Array(values) => values.iter().find_map(text),
+            _ => None,
+        }
+    }
+
+    /// Text values as lines, for fields that may be a string or a list.
+    fn collect_lines(value: &Value, lines: &mut Vec<String>) {
+        match value {
+            Value::String(s) => lines.extend(
+                clean_text(s)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string),
+            ),
+            Value::Array(values) => values.iter().for_each(|v| collect_lines(v, lines)),
+            value => lines.extend(text(value)),
+        }
+    }
+
+    /// Instructions come as a single string, a list of strings, or a list of
+    /// `HowToStep`s, possibly grouped in `HowToSection`s.
+    fn collect_instructions(value: &Value, lines: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                let steps = object.get("itemListElement");
+
+                if has_type(object, "HowToSection") || steps.is_some() {
+                    if let Some(name) = object.get("name").and_then(text) {
+                        lines.push(format!("{name}:"));
+                    }
+
+                    if let Some(steps) = steps {
+                        collect_instructions(steps, lines);
+                    }
+                } else if let Some(step) = object.get("text").or_else(|| object.get("name")) {
+                    collect_lines(step, lines);
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|v| collect_instructions(v, lines)),
+            value => collect_lines(value, lines),
+        }
+    }
+
+    /// The first number in the yield, so "4 personen" and `["4", "4
+    /// servings"]` both give 4.
+    fn servings(value: &Value) -> Option<u32> {
+        match value {
+            Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+            Value::String(s) => {
+                let digits: String = s
+                    .chars()
+                    .skip_while(|c| !c.is_ascii_digit())
+                    .take_while(char::is_ascii_digit)
+                    .collect();
+
+                digits.parse().ok()
+            }
+            Value::Array(values) => values.iter().find_map(servings),
+            _ => None,
+        }
+        .filter(|servings| *servings > 0)
+    }
+
+    fn image_url(value: &Value) -> Option<String> {
+        let url = match value {
+            Value::String(url) => Some(url.trim().to_string()),
+            Value::Array(values) => return values.iter().find_map(image_url),
+            Value::Object(object) => ["url", "contentUrl", "@id"]
+                .iter()
+                .find_map(|key| object.get(*key).and_then(Value::as_str))
+                .map(|url| url.trim().to_string()),
+            _ => None,
+        }?;
+
+        // Relative urls point into the saved page's files, which we don't have
+        if url.starts_with("https://") || url.starts_with("http://") {
+            Some(url)
+        } else {
+            url.strip_prefix("//").map(|url| format!("https://{url}"))
+        }
+    }
+
+    /// Parses an ISO 8601 duration like "PT1H30M" or "P0DT0H20M" into
+    /// minutes.
+    fn parse_duration(duration: &str) -> Option<u32> {
+        let rest = duration.trim().strip_prefix(['P', 'p'])?;
+
+        let mut minutes = 0.0;
+        let mut number = String::new();
+        let mut in_time = false;
+        let mut found = false;
+
+        for c in rest.chars() {
+            match c.to_ascii_uppercase() {
+                'T' => in_time = true,
+                c if c.is_ascii_digit() || c == '.' || c == ',' => {
+                    number.push(if c == ',' { '.' } else { c })
+                }
+                unit => {
+                    let value: f64 = number.parse().ok()?;
+                    number.clear();
+                    found = true;
+
+                    minutes += match unit {
+                        'W' => value * 7.0 * 24.0 * 60.0,
+                        'D' => value * 24.0 * 60.0,
+                        'H' if in_time => value * 60.0,
+                        'M' if in_time => value,
+                        'S' if in_time => value / 60.0,
+                        // Years and months make no sense for recipes
+                        _ => 0.0,
+                    };
+                }
+            }
+        }
+
+        let minutes = minutes.round() as u32;
+
+        (found && minutes > 0).then_some(minutes)
+    }
+
+    /// Turns html in a text field into plain text, keeping line breaks.
+    fn clean_text(text: &str) -> String {
+        let mut plain = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('<') {
+            plain.push_str(&rest[..start]);
+
+            let Some(end) = rest[start..].find('>').map(|i| start + i) else {
+                rest = &rest[start..];
+                break;
+            };
+
+            let tag = rest[start + 1..end]
+                .trim_start_matches('/')
+                .to_ascii_lowercase();
+            if ["br", "p", "li", "div"].iter().any(|t| {
+                tag == *t || tag.starts_with(&format!("{t} ")) || tag.starts_with(&format!("{t}/"))
+            }) {
+                plain.push('\n');
+            }
+
+            rest = &rest[end + 1..];
+        }
+        plain.push_str(rest);
+
+        decode_entities(&plain)
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()
+    }
+
+    fn decode_entities(text: &str) -> String {
+        let mut decoded = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('&') {
+            decoded.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let entity = rest[1..]
+                .find(';')
+                .filter(|end| *end <= 10)
+                .map(|end| &rest[1..end + 1]);
+
+            let c = entity.and_then(|entity| match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "hellip" => Some('…'),
+                "ndash" => Some('–'),
+                "mdash" => Some('—'),
+                "lsquo" | "rsquo" => Some('\''),
+                "ldquo" | "rdquo" => Some('"'),
+                "deg" => Some('°'),
+                "frac12" => Some('½'),
+                "frac14" => Some('¼'),
+                "frac34" => Some('¾'),
+                "aacute" => Some('á'),
+                "agrave" => Some('à'),
+                "auml" => Some('ä'),
+                "eacute" => Some('é'),
+                "egrave" => Some('è'),
+                "ecirc" => Some('ê'),
+                "euml" => Some('ë'),
+                "iuml" => Some('ï'),
+                "oacute" => Some('ó'),
+                "ouml" => Some('ö'),
+                "uuml" => Some('ü'),
+                "ccedil" => Some('ç'),
+                "ntilde" => Some('ñ'),
+                _ => {
+                    let code = match entity.strip_prefix(['#']) {
+                        Some(hex) if hex.starts_with(['x', 'X']) => {
+                            u32::from_str_radix(&hex[1..], 16).ok()
+                        }
+                        Some(dec) => dec.parse().ok(),
+                        None => None,
+                    };
+
+                    code.and_then(char::from_u32)
+                }
+            });
+
+            match (c, entity) {
+                (Some(c), Some(entity)) => {
+                    decoded.push(c);
+                    rest = &rest[entity.len() + 2..];
+                }
+                _ => {
+                    decoded.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        decoded.push_str(rest);
+        decoded
+    }
+}
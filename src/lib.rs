@@ -1,38 +1,76 @@
+#[cfg(feature = "ssr")]
+pub mod api;
 pub mod app;
 pub mod auth;
+pub mod cooklang;
+#[cfg(feature = "ssr")]
+pub mod db;
+pub mod error;
+pub mod export;
+pub mod images;
+pub mod jsonld;
+pub mod log;
+#[cfg(feature = "ssr")]
+pub mod migrations;
 pub mod pages;
+#[cfg(feature = "ssr")]
+pub mod pdf;
+pub mod plan;
 pub mod recipe;
-pub mod log;
+#[cfg(feature = "ssr")]
+pub mod server;
+pub mod share;
+pub mod shopping;
+#[cfg(feature = "ssr")]
+pub mod store;
+pub mod tags;
 
 #[cfg(feature = "ssr")]
-pub static DB: std::sync::LazyLock<tokio::sync::Mutex<rusqlite::Connection>> =
-    std::sync::LazyLock::new(|| {
-        let db_path = std::env::var("NOM_DB").ok().unwrap_or("nom.db".to_string());
+pub fn db_path() -> String {
+    std::env::var("NOM_DB").ok().unwrap_or("nom.db".to_string())
+}
 
-        let conn = rusqlite::Connection::open_with_flags(db_path, rusqlite::OpenFlags::default())
-            .expect("Could not open database");
+/// Opens the database at `NOM_DB`, without running any migrations.
+#[cfg(feature = "ssr")]
+pub fn open_db() -> rusqlite::Result<rusqlite::Connection> {
+    rusqlite::Connection::open_with_flags(db_path(), rusqlite::OpenFlags::default())
+}
 
-        conn.execute_batch(
-            "
-        BEGIN;
-        CREATE TABLE IF NOT EXISTS recipes (
-            id INTEGER PRIMARY KEY,
-            title TEXT,
-            instructions TEXT
-        );
-        CREATE TABLE IF NOT EXISTS ingredients (
-            id INTEGER PRIMARY KEY,
-            recipe INTEGER,
-            ingredient TEXT,
-            FOREIGN KEY(recipe) REFERENCES recipes(id)
-        );
-        COMMIT;
-    ",
-        )
-        .unwrap();
+/// A `Content-Disposition` header value to download a file named after
+/// `title`, e.g. a recipe.
+#[cfg(feature = "ssr")]
+pub fn attachment_disposition(title: &str, extension: &str) -> String {
+    let name: String = title
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+    let name = if name.is_empty() { "recept" } else { &name };
+
+    // Browsers read the plain filename as Latin-1, so non-ASCII names go in
+    // `filename*` as well
+    let ascii_name: String = name
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+    let encoded_name: String = name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect();
 
-        tokio::sync::Mutex::new(conn)
-    });
+    format!(
+        "attachment; filename=\"{ascii_name}.{extension}\"; filename*=UTF-8''{encoded_name}.{extension}"
+    )
+}
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
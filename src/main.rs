@@ -1,35 +1,57 @@
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
-    use axum::Router;
     use leptos::logging::log;
     use leptos::prelude::*;
-    use leptos_axum::{LeptosRoutes, generate_route_list};
-    use nom::app::*;
-    use nom::auth::middleware::auth_middleware;
-    use nom::log::middleware::log_middleware;
-    use tower_http::compression::CompressionLayer;
+    use nom::auth::throttle::{LoginThrottle, ThrottleConfig};
+    use nom::db::{Pool, readers_from_env};
+    use nom::server::{AppState, router};
+
+    if std::env::args().any(|arg| arg == "--migrate-dry-run") {
+        migrate_dry_run();
+        return;
+    }
+
+    // Migrate before serving anything, so we refuse to start against a
+    // database we don't understand instead of failing on the first request
+    let db = match Pool::open(&nom::db_path(), readers_from_env()) {
+        Ok(db) => db,
+        Err(err) => panic!("Could not open database: {err}"),
+    };
+
+    match db.write(|db| nom::auth::store::bootstrap_admin(db)).await {
+        Ok(Some(username)) => log!("Created admin account {username}"),
+        Ok(None) => {}
+        Err(err) => panic!("Could not create admin account: {err}"),
+    }
+
+    let throttle = LoginThrottle::new(ThrottleConfig::from_env());
+
+    {
+        let throttle = throttle.clone();
+
+        db.write(move |db| throttle.load(db))
+            .await
+            .expect("Could not load failed logins");
+    }
+
+    let store = match nom::store::from_env(db.clone()).await {
+        Ok(store) => store,
+        Err(err) => panic!("Could not open recipes: {err}"),
+    };
 
     let conf = get_configuration(Some("./Cargo.toml")).unwrap();
 
     log!("Using config: {:#?}", conf.leptos_options);
 
     let addr = conf.leptos_options.site_addr;
-    let leptos_options = conf.leptos_options;
-
-    // Generate the list of routes in your Leptos App
-    let routes = generate_route_list(App);
-
-    let app = Router::new()
-        .leptos_routes(&leptos_options, routes, {
-            let leptos_options = leptos_options.clone();
-            move || shell(leptos_options.clone())
-        })
-        .fallback(leptos_axum::file_and_error_handler(shell))
-        .layer(CompressionLayer::new())
-        .layer(axum::middleware::from_fn(auth_middleware))
-        .layer(axum::middleware::from_fn(log_middleware))
-        .with_state(leptos_options);
+
+    let app = router(AppState {
+        leptos_options: conf.leptos_options,
+        db,
+        store,
+        throttle,
+    });
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
@@ -37,9 +59,40 @@ async fn main() {
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
 
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    // With the client's address, to lock out password guessing
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
+}
+
+/// Runs all pending migrations against `NOM_DB` and rolls them back, to check
+/// an upgrade before doing it for real.
+#[cfg(feature = "ssr")]
+fn migrate_dry_run() {
+    use nom::migrations::{migrate, schema_version};
+
+    let mut conn = nom::open_db().expect("Could not open database");
+    let version = schema_version(&conn).expect("Could not read schema version");
+
+    match migrate(&mut conn, true) {
+        Ok(applied) if applied.is_empty() => {
+            println!("Database is up to date at version {version}");
+        }
+        Ok(applied) => {
+            println!("Database is at version {version}, would apply:");
+
+            for migration in applied {
+                println!("  {}: {}", migration.version, migration.description);
+            }
+        }
+        Err(err) => {
+            eprintln!("Migration failed: {err}");
+            std::process::exit(1);
+        }
+    }
 }
 
 #[cfg(not(feature = "ssr"))]
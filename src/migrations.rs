@@ -0,0 +1,707 @@
+//! Versioned schema migrations.
+//!
+//! The schema version is tracked in `PRAGMA user_version`. Every migration
+//! bumps it by one, so a database at version `n` has had the first `n`
+//! migrations applied. Databases from before migrations existed are at
+//! version 0 and are adopted by the first migration.
+
+use std::fmt;
+
+use rusqlite::{Connection, Transaction};
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// All migrations, in order. Never edit or reorder a migration that has been
+/// released, add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create recipes and ingredients tables",
+        apply: create_tables,
+    },
+    Migration {
+        version: 2,
+        description: "Split ingredients into quantity, unit, name and note",
+        apply: structure_ingredients,
+    },
+    Migration {
+        version: 3,
+        description: "Add servings to recipes",
+        apply: add_servings,
+    },
+    Migration {
+        version: 4,
+        description: "Add full-text search index",
+        apply: create_search_index,
+    },
+    Migration {
+        version: 5,
+        description: "Add tags",
+        apply: create_tags,
+    },
+    Migration {
+        version: 6,
+        description: "Add recipe images",
+        apply: create_images,
+    },
+    Migration {
+        version: 7,
+        description: "Add meal plan",
+        apply: create_meal_plan,
+    },
+    Migration {
+        version: 8,
+        description: "Add shopping list",
+        apply: create_shopping_list,
+    },
+    Migration {
+        version: 9,
+        description: "Add preparation and cooking times to recipes",
+        apply: add_times,
+    },
+    Migration {
+        version: 10,
+        description: "Add user accounts and sessions",
+        apply: create_users,
+    },
+    Migration {
+        version: 11,
+        description: "Replace the admin flag of users with roles",
+        apply: add_roles,
+    },
+    Migration {
+        version: 12,
+        description: "Add API tokens",
+        apply: create_api_tokens,
+    },
+    Migration {
+        version: 13,
+        description: "Add failed login counts",
+        apply: create_login_failures,
+    },
+    Migration {
+        version: 14,
+        description: "Add share links for recipes",
+        apply: create_share_links,
+    },
+    Migration {
+        version: 15,
+        description: "Add versions to recipes",
+        apply: add_recipe_versions,
+    },
+];
+
+/// The schema version this binary expects.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Sqlite(rusqlite::Error),
+    /// The database was migrated by a newer version of nom.
+    NewerSchema {
+        database: u32,
+        supported: u32,
+    },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Sqlite(err) => write!(f, "database error: {err}"),
+            MigrationError::NewerSchema {
+                database,
+                supported,
+            } => write!(
+                f,
+                "database schema version {database} is newer than the latest supported version {supported}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(err: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(err)
+    }
+}
+
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Applies all pending migrations in a single transaction and returns the ones
+/// that were applied. With `dry_run` the migrations are still executed, but
+/// the transaction is rolled back afterwards.
+pub fn migrate(
+    conn: &mut Connection,
+    dry_run: bool,
+) -> Result<Vec<&'static Migration>, MigrationError> {
+    let current = schema_version(conn)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(MigrationError::NewerSchema {
+            database: current,
+            supported: latest,
+        });
+    }
+
+    let pending: Vec<_> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+
+    let transaction = conn.transaction()?;
+
+    for migration in &pending {
+        (migration.apply)(&transaction)?;
+        transaction.pragma_update(None, "user_version", migration.version)?;
+    }
+
+    if dry_run {
+        transaction.rollback()?;
+    } else {
+        transaction.commit()?;
+    }
+
+    Ok(pending)
+}
+
+fn has_column(transaction: &Transaction, table: &str, column: &str) -> rusqlite::Result<bool> {
+    transaction
+        .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2;")?
+        .exists((table, column))
+}
+
+fn create_tables(transaction: &Transaction) -> rusqlite::Result<()> {
+    // IF NOT EXISTS, because databases from before migrations already have these
+    transaction.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS recipes (
+            id INTEGER PRIMARY KEY,
+            title TEXT,
+            instructions TEXT
+        );
+        CREATE TABLE IF NOT EXISTS ingredients (
+            id INTEGER PRIMARY KEY,
+            recipe INTEGER,
+            ingredient TEXT,
+            FOREIGN KEY(recipe) REFERENCES recipes(id)
+        );
+    ",
+    )
+}
+
+fn structure_ingredients(transaction: &Transaction) -> rusqlite::Result<()> {
+    for (column, decl) in [
+        ("quantity", "REAL"),
+        ("unit", "TEXT"),
+        ("name", "TEXT"),
+        ("note", "TEXT"),
+    ] {
+        if !has_column(transaction, "ingredients", column)? {
+            transaction.execute(
+                &format!("ALTER TABLE ingredients ADD COLUMN {column} {decl};"),
+                (),
+            )?;
+        }
+    }
+
+    if !has_column(transaction, "ingredients", "ingredient")? {
+        return Ok(());
+    }
+
+    {
+        let mut raw_stmt =
+            transaction.prepare("SELECT id, ingredient FROM ingredients WHERE name IS NULL;")?;
+        let mut update_stmt = transaction.prepare(
+            "UPDATE ingredients SET quantity = ?1, unit = ?2, name = ?3, note = ?4 WHERE id = ?5;",
+        )?;
+
+        let raw_ingredients = raw_stmt
+            .query_map((), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (id, raw) in raw_ingredients {
+            let (quantity, unit, name, note) =
+                frozen_ingredients::parse(raw.as_deref().unwrap_or_default());
+
+            update_stmt.execute((quantity, unit, name, note, id))?;
+        }
+    }
+
+    transaction.execute("ALTER TABLE ingredients DROP COLUMN ingredient;", ())?;
+
+    Ok(())
+}
+
+fn add_servings(transaction: &Transaction) -> rusqlite::Result<()> {
+    if !has_column(transaction, "recipes", "servings")? {
+        transaction.execute("ALTER TABLE recipes ADD COLUMN servings INTEGER;", ())?;
+    }
+
+    Ok(())
+}
+
+fn create_search_index(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE VIRTUAL TABLE recipes_fts USING fts5(
+            title,
+            ingredients,
+            instructions,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        INSERT INTO recipes_fts (rowid, title, ingredients, instructions)
+            SELECT
+                id,
+                title,
+                (SELECT group_concat(name, char(10)) FROM ingredients WHERE recipe = recipes.id),
+                instructions
+            FROM recipes;
+    ",
+    )
+}
+
+fn create_tags(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE recipe_tags (
+            recipe INTEGER NOT NULL,
+            tag INTEGER NOT NULL,
+            PRIMARY KEY (recipe, tag),
+            FOREIGN KEY(recipe) REFERENCES recipes(id),
+            FOREIGN KEY(tag) REFERENCES tags(id)
+        );
+    ",
+    )
+}
+
+fn create_images(transaction: &Transaction) -> rusqlite::Result<()> {
+    // AUTOINCREMENT so ids of deleted images are never reused, which lets
+    // browsers cache images by id forever
+    transaction.execute_batch(
+        "
+        CREATE TABLE images (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            recipe INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            FOREIGN KEY(recipe) REFERENCES recipes(id)
+        );
+    ",
+    )
+}
+
+fn create_meal_plan(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE meal_plan (
+            id INTEGER PRIMARY KEY,
+            date TEXT NOT NULL,
+            slot TEXT NOT NULL CHECK (slot IN ('ontbijt', 'lunch', 'diner')),
+            recipe INTEGER NOT NULL,
+            servings INTEGER,
+            FOREIGN KEY(recipe) REFERENCES recipes(id)
+        );
+        CREATE INDEX meal_plan_date ON meal_plan (date);
+    ",
+    )
+}
+
+fn create_shopping_list(transaction: &Transaction) -> rusqlite::Result<()> {
+    // Only the recipes and the ticked off items are stored, the items
+    // themselves are derived from the ingredients of the recipes
+    transaction.execute_batch(
+        "
+        CREATE TABLE shopping_recipes (
+            id INTEGER PRIMARY KEY,
+            recipe INTEGER NOT NULL,
+            servings INTEGER,
+            FOREIGN KEY(recipe) REFERENCES recipes(id)
+        );
+        CREATE TABLE shopping_checked (
+            item TEXT PRIMARY KEY
+        );
+    ",
+    )
+}
+
+fn add_times(transaction: &Transaction) -> rusqlite::Result<()> {
+    // Both in minutes
+    transaction.execute_batch(
+        "
+        ALTER TABLE recipes ADD COLUMN prep_time INTEGER;
+        ALTER TABLE recipes ADD COLUMN cook_time INTEGER;
+    ",
+    )
+}
+
+fn create_users(transaction: &Transaction) -> rusqlite::Result<()> {
+    // Session expiry in seconds since the epoch
+    transaction.execute_batch(
+        "
+        CREATE TABLE users (
+            id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            password_hash TEXT NOT NULL,
+            is_admin INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE sessions (
+            token TEXT PRIMARY KEY,
+            user INTEGER NOT NULL,
+            expires INTEGER NOT NULL,
+            FOREIGN KEY(user) REFERENCES users(id)
+        );
+        CREATE INDEX sessions_user ON sessions(user);
+        ALTER TABLE recipes ADD COLUMN created_by INTEGER REFERENCES users(id);
+        ALTER TABLE recipes ADD COLUMN updated_by INTEGER REFERENCES users(id);
+    ",
+    )
+}
+
+fn add_roles(transaction: &Transaction) -> rusqlite::Result<()> {
+    // Everyone could edit recipes before, so that's what they keep
+    transaction.execute_batch(
+        "
+        ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'editor'
+            CHECK (role IN ('viewer', 'editor', 'admin'));
+        UPDATE users SET role = 'admin' WHERE is_admin;
+        ALTER TABLE users DROP COLUMN is_admin;
+    ",
+    )
+}
+
+fn create_api_tokens(transaction: &Transaction) -> rusqlite::Result<()> {
+    // Only a hash of the token is kept. The scopes are comma separated, with
+    // none meaning everything the user may do. Times in seconds since the
+    // epoch.
+    transaction.execute_batch(
+        "
+        CREATE TABLE api_tokens (
+            id INTEGER PRIMARY KEY,
+            user INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT NOT NULL UNIQUE,
+            scopes TEXT NOT NULL DEFAULT '',
+            created INTEGER NOT NULL,
+            last_used INTEGER,
+            FOREIGN KEY(user) REFERENCES users(id)
+        );
+        CREATE INDEX api_tokens_user ON api_tokens(user);
+    ",
+    )
+}
+
+fn create_login_failures(transaction: &Transaction) -> rusqlite::Result<()> {
+    // Only used with NOM_LOGIN_PERSIST. Keys are `ip:<address>` or
+    // `user:<username>`, times in seconds since the epoch.
+    transaction.execute_batch(
+        "
+        CREATE TABLE login_failures (
+            key TEXT PRIMARY KEY,
+            count INTEGER NOT NULL,
+            last_failure INTEGER NOT NULL,
+            locked_until INTEGER NOT NULL
+        );
+    ",
+    )
+}
+
+fn create_share_links(transaction: &Transaction) -> rusqlite::Result<()> {
+    // The tokens themselves aren't stored, they are signed with a secret
+    // that is generated on first use. Times in seconds since the epoch.
+    transaction.execute_batch(
+        "
+        CREATE TABLE share_links (
+            id INTEGER PRIMARY KEY,
+            recipe INTEGER NOT NULL,
+            created_by INTEGER,
+            created INTEGER NOT NULL,
+            expires INTEGER,
+            FOREIGN KEY(recipe) REFERENCES recipes(id),
+            FOREIGN KEY(created_by) REFERENCES users(id)
+        );
+        CREATE INDEX share_links_recipe ON share_links(recipe);
+        CREATE TABLE secrets (
+            name TEXT PRIMARY KEY,
+            value BLOB NOT NULL
+        );
+    ",
+    )
+}
+
+fn add_recipe_versions(transaction: &Transaction) -> rusqlite::Result<()> {
+    // Increased with every change, so edits made at the same time don't
+    // overwrite each other
+    transaction.execute_batch(
+        "
+        ALTER TABLE recipes ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+    ",
+    )
+}
+
+/// The ingredient parser as it was when [`structure_ingredients`] was
+/// released, so changes to [`crate::recipe::Ingredient::parse`] don't change
+/// what the migration makes of old databases. Never edit this.
+mod frozen_ingredients {
+    /// A parsed ingredient line: quantity, unit, name and note.
+    pub type Parsed = (Option<f64>, Option<String>, String, Option<String>);
+
+    /// Units recognised directly after a quantity. Matched case-insensitively and
+    /// without a trailing period, so "El." is a unit as well.
+    const UNITS: &[&str] = &[
+        "g",
+        "gr",
+        "gram",
+        "kg",
+        "kilo",
+        "kilogram",
+        "mg",
+        "ml",
+        "cl",
+        "dl",
+        "l",
+        "liter",
+        "el",
+        "eetlepel",
+        "eetlepels",
+        "tl",
+        "theelepel",
+        "theelepels",
+        "kl",
+        "koffielepel",
+        "koffielepels",
+        "snuf",
+        "snufje",
+        "snufjes",
+        "mespunt",
+        "mespuntje",
+        "mespuntjes",
+        "teen",
+        "teentje",
+        "teentjes",
+        "tenen",
+        "blik",
+        "blikje",
+        "blikjes",
+        "blikken",
+        "pak",
+        "pakje",
+        "pakjes",
+        "pakken",
+        "zak",
+        "zakje",
+        "zakjes",
+        "bos",
+        "bosje",
+        "bosjes",
+        "takje",
+        "takjes",
+        "plak",
+        "plakje",
+        "plakjes",
+        "plakken",
+        "kop",
+        "kopje",
+        "kopjes",
+        "cup",
+        "cups",
+        "stuk",
+        "stuks",
+        "stukje",
+        "stukjes",
+        "scheut",
+        "scheutje",
+        "handje",
+        "handvol",
+        "pot",
+        "potje",
+        "fles",
+        "tbsp",
+        "tsp",
+        "oz",
+        "lb",
+    ];
+
+    /// Unicode vulgar fractions and their values.
+    const FRACTIONS: &[(char, f64)] = &[
+        ('½', 1.0 / 2.0),
+        ('⅓', 1.0 / 3.0),
+        ('⅔', 2.0 / 3.0),
+        ('¼', 1.0 / 4.0),
+        ('¾', 3.0 / 4.0),
+        ('⅕', 1.0 / 5.0),
+        ('⅖', 2.0 / 5.0),
+        ('⅗', 3.0 / 5.0),
+        ('⅘', 4.0 / 5.0),
+        ('⅙', 1.0 / 6.0),
+        ('⅚', 5.0 / 6.0),
+        ('⅛', 1.0 / 8.0),
+        ('⅜', 3.0 / 8.0),
+        ('⅝', 5.0 / 8.0),
+        ('⅞', 7.0 / 8.0),
+    ];
+
+    /// Parses a single ingredient line. Anything that doesn't look like
+    /// "[quantity] [unit] name[, note]" is kept as the name.
+    pub fn parse(line: &str) -> Parsed {
+        let line = line.trim();
+
+        let fallback = || (None, None, line.to_string(), None);
+
+        let (quantity, rest) = match parse_quantity(line) {
+            Ok(Some((quantity, rest))) => (Some(quantity), rest),
+            Ok(None) => (None, line),
+            Err(()) => return fallback(),
+        };
+
+        let (unit, rest) = match quantity {
+            Some(_) => parse_unit(rest),
+            None => (None, rest),
+        };
+
+        let (name, note) = match rest.split_once(',') {
+            Some((name, note)) => (name.trim(), Some(note.trim())),
+            None => (rest.trim(), None),
+        };
+
+        if name.is_empty() {
+            return fallback();
+        }
+
+        (
+            quantity,
+            unit.map(str::to_string),
+            name.to_string(),
+            note.filter(|n| !n.is_empty()).map(str::to_string),
+        )
+    }
+
+    /// Parses a leading quantity such as "200", "0,5", "1/2", "½", "1½" or
+    /// "1 1/2", returning it with the remaining text.
+    ///
+    /// Returns `Ok(None)` if the text doesn't start with a quantity, and `Err` if
+    /// it does but we can't make sense of it (ranges like "2-3", "1/0").
+    fn parse_quantity(text: &str) -> Result<Option<(f64, &str)>, ()> {
+        let (whole, rest) = match parse_number(text)? {
+            Some(parsed) => parsed,
+            None => return parse_fraction(text),
+        };
+
+        if rest.starts_with(['-', '–']) {
+            return Err(());
+        }
+
+        // A fraction directly after the number ("1½") or separated by a space
+        // ("1 1/2") makes it a mixed number
+        if let Some((frac, rest)) = parse_fraction(rest)? {
+            return Ok(Some((whole + frac, rest)));
+        }
+
+        if let Some((frac, rest)) = parse_fraction(rest.trim_start())? {
+            return Ok(Some((whole + frac, rest)));
+        }
+
+        Ok(Some((whole, rest)))
+    }
+
+    /// Parses a leading decimal number, accepting both "." and "," as separator.
+    /// A number directly followed by "/" is left to [`parse_fraction`].
+    fn parse_number(text: &str) -> Result<Option<(f64, &str)>, ()> {
+        let digits_end = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+
+        if digits_end == 0 || text[digits_end..].starts_with(['/', '⁄']) {
+            return Ok(None);
+        }
+
+        let mut end = digits_end;
+
+        if let Some(after_sep) = text[digits_end..].strip_prefix(['.', ',']) {
+            let decimals = after_sep
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after_sep.len());
+
+            if decimals > 0 {
+                end = digits_end + 1 + decimals;
+            }
+        }
+
+        let number = text[..end].replace(',', ".").parse().map_err(|_| ())?;
+
+        Ok(Some((number, &text[end..])))
+    }
+
+    /// Parses a leading fraction, either a vulgar fraction ("½") or a
+    /// numerator/denominator pair ("1/2").
+    fn parse_fraction(text: &str) -> Result<Option<(f64, &str)>, ()> {
+        if let Some(first) = text.chars().next()
+            && let Some((_, value)) = FRACTIONS.iter().find(|(c, _)| *c == first)
+        {
+            return Ok(Some((*value, &text[first.len_utf8()..])));
+        }
+
+        let numerator_end = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+
+        let Some(after_slash) = text[numerator_end..].strip_prefix(['/', '⁄']) else {
+            return Ok(None);
+        };
+
+        if numerator_end == 0 {
+            return Ok(None);
+        }
+
+        let denominator_end = after_slash
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after_slash.len());
+
+        let numerator: f64 = text[..numerator_end].parse().map_err(|_| ())?;
+        let denominator: f64 = after_slash[..denominator_end].parse().map_err(|_| ())?;
+
+        if denominator == 0.0 {
+            return Err(());
+        }
+
+        Ok(Some((
+            numerator / denominator,
+            &after_slash[denominator_end..],
+        )))
+    }
+
+    /// Splits a known unit off the start of `text`. The unit may directly follow
+    /// the quantity, as in "200g".
+    fn parse_unit(text: &str) -> (Option<&str>, &str) {
+        let text = text.trim_start();
+        let word_end = text.find(char::is_whitespace).unwrap_or(text.len());
+        let word = text[..word_end].trim_end_matches(',');
+
+        let is_unit = |word: &str| {
+            let word = word.strip_suffix('.').unwrap_or(word).to_lowercase();
+            UNITS.contains(&word.as_str())
+        };
+
+        if is_unit(word) {
+            (Some(word), &text[word.len()..])
+        } else {
+            (None, text)
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use leptos::prelude::*;
+
+use crate::error::ErrorMessage;
+use crate::recipe::list_recipes;
+
+/// Putting together a PDF cookbook from a selection of recipes, to print for
+/// people who don't use the site.
+#[component]
+pub fn CookbookPage() -> impl IntoView {
+    let recipes = Resource::new(
+        || (),
+        async |_| {
+            let mut recipes = list_recipes(Vec::new()).await?;
+
+            recipes.sort_by_cached_key(|rp| rp.title.clone());
+
+            Ok(recipes)
+        },
+    );
+
+    // In the order they were ticked, which is the order in the cookbook
+    let selected = RwSignal::new(Vec::<i64>::new());
+
+    let download = move || {
+        let selected = selected.get();
+
+        if selected.is_empty() {
+            return view! { <p>"Nog geen recepten gekozen."</p> }.into_any();
+        }
+
+        let ids = selected
+            .iter()
+            .map(i64::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        view! {
+            <a class="link-button button-positive" href=format!("/recipes.pdf?ids={ids}") download rel="external">
+                {format!("Download PDF ({} recepten)", selected.len())}
+            </a>
+        }
+        .into_any()
+    };
+
+    view! {
+        <h1>"Kookboek"</h1>
+        <p>
+            "Kies de recepten voor het kookboek. Ze komen erin in de volgorde waarin je ze "
+            "aanvinkt, na een inhoudsopgave."
+        </p>
+        <Suspense fallback=move || view! { <p>"Recepten aan het laden..."</p> }>
+            {move || recipes.get().map(|recipes| {
+                let recipes = match recipes {
+                    Ok(recipes) => recipes,
+                    Err(error) => return view! { <ErrorMessage error/> }.into_any(),
+                };
+
+                view! {
+                    <ul class="cookbook-recipes">
+                        {recipes.into_iter().map(|recipe| {
+                            let id = recipe.id;
+
+                            let toggle = move |ev| {
+                                if event_target_checked(&ev) {
+                                    selected.update(|selected| selected.push(id));
+                                } else {
+                                    selected.update(|selected| selected.retain(|s| *s != id));
+                                }
+                            };
+
+                            view! {
+                                <li>
+                                    <label>
+                                        <input
+                                            type="checkbox"
+                                            prop:checked=move || selected.read().contains(&id)
+                                            on:change=toggle
+                                        />
+                                        {recipe.title}
+                                    </label>
+                                </li>
+                            }
+                        }).collect_view()}
+                    </ul>
+                }
+                .into_any()
+            })}
+        </Suspense>
+        {download}
+    }
+}
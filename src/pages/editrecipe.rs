@@ -8,13 +8,107 @@ use leptos_router::hooks::use_params;
 use leptos_router::params::Params;
 use web_sys::MouseEvent;
 
-use crate::recipe::{RawRecipe, delete_recipe, get_recipe, update_recipe};
+use crate::auth::{RequireRole, Role};
+use crate::error::{ErrorMessage, NomError};
+use crate::images::{ImageManager, upload_selected};
+use crate::recipe::{Ingredient, RawRecipe, Recipe, delete_recipe, get_recipe, update_recipe};
+use crate::tags::TagInput;
 
 #[derive(Debug, Params, PartialEq)]
 struct EditRecipeArgs {
     id: Option<String>,
 }
 
+/// A part of the form, to compare two versions of a recipe by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Servings,
+    PrepTime,
+    CookTime,
+    Ingredients,
+    Instructions,
+    Tags,
+}
+
+impl Field {
+    const ALL: [Field; 7] = [
+        Field::Title,
+        Field::Servings,
+        Field::PrepTime,
+        Field::CookTime,
+        Field::Ingredients,
+        Field::Instructions,
+        Field::Tags,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Title => "Titel",
+            Field::Servings => "Personen",
+            Field::PrepTime => "Voorbereiding (minuten)",
+            Field::CookTime => "Bereiding (minuten)",
+            Field::Ingredients => "Ingredienten",
+            Field::Instructions => "Instructies",
+            Field::Tags => "Tags",
+        }
+    }
+
+    /// The field as it is filled in.
+    fn value(self, recipe: &RawRecipe) -> String {
+        let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+
+        match self {
+            Field::Title => recipe.title.clone(),
+            Field::Servings => number(recipe.servings),
+            Field::PrepTime => number(recipe.prep_time),
+            Field::CookTime => number(recipe.cook_time),
+            Field::Ingredients => recipe.ingredients.clone(),
+            Field::Instructions => recipe.instructions.clone(),
+            Field::Tags => recipe.tags.join(", "),
+        }
+    }
+
+    fn copy(self, from: &RawRecipe, to: &mut RawRecipe) {
+        match self {
+            Field::Title => to.title = from.title.clone(),
+            Field::Servings => to.servings = from.servings,
+            Field::PrepTime => to.prep_time = from.prep_time,
+            Field::CookTime => to.cook_time = from.cook_time,
+            Field::Ingredients => to.ingredients = from.ingredients.clone(),
+            Field::Instructions => to.instructions = from.instructions.clone(),
+            Field::Tags => to.tags = from.tags.clone(),
+        }
+    }
+}
+
+/// Someone else saved the recipe while it was being edited.
+#[derive(Debug, Clone)]
+struct Conflict {
+    /// What was submitted
+    mine: RawRecipe,
+    /// What is stored now
+    current: RawRecipe,
+}
+
+impl Conflict {
+    fn new(mine: RawRecipe, current: Recipe) -> Self {
+        Conflict {
+            // Written the way the stored recipe is, so only real differences
+            // show up
+            mine: Recipe::from(mine).into(),
+            current: current.into(),
+        }
+    }
+
+    fn differences(&self) -> Vec<Field> {
+        Field::ALL
+            .into_iter()
+            .filter(|field| field.value(&self.mine).trim() != field.value(&self.current).trim())
+            .collect()
+    }
+}
+
 #[component]
 pub fn EditRecipePage() -> impl IntoView {
     let id = move || {
@@ -23,18 +117,66 @@ pub fn EditRecipePage() -> impl IntoView {
             .as_ref()
             .ok()
             .and_then(|params| params.id.clone())
-            .unwrap()
+            .unwrap_or_default()
     };
 
     let recipe_resource = Resource::new(id, async |id| {
-        let parsed: i64 = id.parse().unwrap();
-        get_recipe(parsed).await.unwrap().map(|rcp| (parsed, rcp))
+        let parsed: i64 = id
+            .parse()
+            .map_err(|_| NomError::not_found(format!("recipe {id}")))?;
+        get_recipe(parsed).await.map(|rcp| (parsed, rcp))
     });
 
+    // Saving or deleting that went wrong
+    let (error, set_error) = signal(None::<NomError>);
+
     let id_elem: NodeRef<html::Input> = NodeRef::new();
     let title_elem: NodeRef<html::Input> = NodeRef::new();
+    let servings_elem: NodeRef<html::Input> = NodeRef::new();
+    let prep_time_elem: NodeRef<html::Input> = NodeRef::new();
+    let cook_time_elem: NodeRef<html::Input> = NodeRef::new();
     let ingredient_elem: NodeRef<html::Textarea> = NodeRef::new();
     let instruction_elem: NodeRef<html::Textarea> = NodeRef::new();
+    let tags = RwSignal::new(Vec::new());
+    let images = RwSignal::new(Vec::new());
+    let images_elem: NodeRef<html::Input> = NodeRef::new();
+    // The version being edited, to notice edits made at the same time
+    let version = RwSignal::new(0);
+    let conflict = RwSignal::new(None::<Conflict>);
+
+    Effect::new(move |_| {
+        if let Some(Ok((_, recipe))) = recipe_resource.get() {
+            tags.set(recipe.tags);
+            images.set(recipe.images);
+            version.set(recipe.version);
+        }
+    });
+
+    // Fills in the field as it is in the current version
+    let use_current = move |field: Field| {
+        let Some(current) = conflict.with_untracked(|c| c.as_ref().map(|c| c.current.clone()))
+        else {
+            return;
+        };
+
+        let value = field.value(&current);
+
+        match field {
+            Field::Title => title_elem.get().unwrap().set_value(&value),
+            Field::Servings => servings_elem.get().unwrap().set_value(&value),
+            Field::PrepTime => prep_time_elem.get().unwrap().set_value(&value),
+            Field::CookTime => cook_time_elem.get().unwrap().set_value(&value),
+            Field::Ingredients => ingredient_elem.get().unwrap().set_value(&value),
+            Field::Instructions => instruction_elem.get().unwrap().set_value(&value),
+            Field::Tags => tags.set(current.tags.clone()),
+        }
+
+        conflict.update(|conflict| {
+            if let Some(conflict) = conflict {
+                field.copy(&current, &mut conflict.mine);
+            }
+        });
+    };
 
     let on_submit = move |ev: SubmitEvent| {
         // stop the page from reloading!
@@ -42,20 +184,56 @@ pub fn EditRecipePage() -> impl IntoView {
 
         let id = id_elem.get().unwrap().value();
         let title = title_elem.get().unwrap().value();
+        let servings = servings_elem.get().unwrap().value().parse().ok();
+        let prep_time = prep_time_elem.get().unwrap().value().parse().ok();
+        let cook_time = cook_time_elem.get().unwrap().value().parse().ok();
         let ingredients = ingredient_elem.get().unwrap().value();
         let instructions = instruction_elem.get().unwrap().value();
+        let tags = tags.get();
+        let images_input = images_elem.get().unwrap();
 
         spawn_local(async move {
-            update_recipe(
-                id.parse().expect("Submitted invalid id"),
-                RawRecipe {
-                    title,
-                    ingredients,
-                    instructions,
+            let recipe_id = id.parse().expect("Submitted invalid id");
+
+            let raw_recipe = RawRecipe {
+                title,
+                servings,
+                prep_time,
+                cook_time,
+                ingredients,
+                instructions,
+                tags,
+            };
+
+            let updated =
+                update_recipe(recipe_id, raw_recipe.clone(), version.get_untracked()).await;
+
+            match updated {
+                Ok(_) => {}
+                // Shows what changed, and saving again overwrites the
+                // version they've seen now
+                Err(NomError::Stale(_)) => match get_recipe(recipe_id).await {
+                    Ok(current) => {
+                        version.set(current.version);
+                        conflict.set(Some(Conflict::new(raw_recipe, current)));
+                        set_error.set(None);
+                        return;
+                    }
+                    Err(err) => {
+                        set_error.set(Some(err));
+                        return;
+                    }
                 },
-            )
-            .await
-            .unwrap();
+                Err(err) => {
+                    set_error.set(Some(err));
+                    return;
+                }
+            }
+
+            if let Err(err) = upload_selected(recipe_id, &images_input).await {
+                set_error.set(Some(err.into()));
+                return;
+            }
 
             let navigate = leptos_router::hooks::use_navigate();
 
@@ -63,7 +241,7 @@ pub fn EditRecipePage() -> impl IntoView {
         });
     };
 
-    let delete_handler = |e: MouseEvent, id| {
+    let delete_handler = move |e: MouseEvent, id| {
         e.prevent_default();
 
         spawn_local(async move {
@@ -75,7 +253,10 @@ pub fn EditRecipePage() -> impl IntoView {
                 return;
             }
 
-            delete_recipe(id).await.unwrap();
+            if let Err(err) = delete_recipe(id).await {
+                set_error.set(Some(err));
+                return;
+            }
 
             let navigate = leptos_router::hooks::use_navigate();
 
@@ -87,16 +268,22 @@ pub fn EditRecipePage() -> impl IntoView {
         <h1>"Recept Aanpassen"</h1>
         <Suspense fallback=move || view!{ <p>"Recept aan het laden..."</p>}> {
             move || {
-                let (id, recipe) = match recipe_resource.get().flatten() {
-                    Some(rcp) => rcp,
-                    None => {
+                let (id, recipe) = match recipe_resource.get() {
+                    Some(Ok(rcp)) => rcp,
+                    Some(Err(NomError::NotFound(_))) | None => {
                         return view! {
                             <p>"Onbekend recept"</p>
                         }.into_any()
                     },
+                    Some(Err(error)) => return view! { <ErrorMessage error/> }.into_any(),
                 };
 
-                let ingredients = recipe.ingredients.join("\n");
+                let ingredients = recipe
+                    .ingredients
+                    .iter()
+                    .map(Ingredient::to_line)
+                    .collect::<Vec<_>>()
+                    .join("\n");
 
                 view! {
                     <form on:submit=on_submit>
@@ -104,19 +291,90 @@ pub fn EditRecipePage() -> impl IntoView {
                         <h3>Titel</h3>
                         <input type="text" placeholder="Titel" value={recipe.title} node_ref=title_elem/>
                         <br/>
+                        <h3>Personen</h3>
+                        <input type="number" min="1" placeholder="Aantal personen" value={recipe.servings} node_ref=servings_elem/>
+                        <br/>
+                        <h3>Tijd</h3>
+                        <input type="number" min="0" placeholder="Voorbereiding (minuten)" value={recipe.prep_time} node_ref=prep_time_elem/>
+                        <input type="number" min="0" placeholder="Bereiding (minuten)" value={recipe.cook_time} node_ref=cook_time_elem/>
+                        <br/>
                         <h3>Ingredienten</h3>
                         <textarea placeholder="Ingredienten" node_ref=ingredient_elem rows={recipe.ingredients.len() + 2}>{ingredients}</textarea>
                         <br/>
                         <h3>Instructies</h3>
                         <textarea placeholder="Instructies" node_ref=instruction_elem>{recipe.instructions}</textarea>
                         <br/>
+                        <h3>Tags</h3>
+                        <TagInput tags=tags/>
+                        <br/>
+                        <h3>"Foto's"</h3>
+                        <ImageManager images=images/>
+                        <input type="file" accept="image/*" multiple node_ref=images_elem/>
+                        <br/>
+                        {move || conflict.get().map(|conflict| conflict_view(conflict, use_current))}
+                        {move || error.get().map(|error| view! { <ErrorMessage error/> })}
                         <A class:link-button class:button-negative href={format!("/recipe/{id}")}>"Annuleer"</A>
-                        <input class="link-button button-positive" type="submit" value="Pas aan"/>
+                        <RequireRole role=Role::Editor>
+                            <input class="link-button button-positive" type="submit" value="Pas aan"/>
+                        </RequireRole>
                         <br/>
-                        <button class:link-button class:button-negative on:click=move |e| delete_handler(e, id)>"Verwijder"</button>
+                        <RequireRole role=Role::Admin>
+                            <button class:link-button class:button-negative on:click=move |e| delete_handler(e, id)>"Verwijder"</button>
+                        </RequireRole>
                     </form>
                 }.into_any()
             }
         } </Suspense>
     }
 }
+
+/// The differences between the submitted and the current version, side by
+/// side, to take over what should stay of the current one.
+fn conflict_view(
+    conflict: Conflict,
+    use_current: impl Fn(Field) + Copy + 'static,
+) -> impl IntoView {
+    let differences = conflict.differences();
+
+    let rows = differences
+        .iter()
+        .map(|&field| {
+            view! {
+                <tr>
+                    <th>{field.label()}</th>
+                    <td>{field.value(&conflict.mine)}</td>
+                    <td>{field.value(&conflict.current)}</td>
+                    <td>
+                        <button type="button" class="link-button" on:click=move |_| use_current(field)>
+                            "Neem over"
+                        </button>
+                    </td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class="recipe-conflict">
+            <p class="error-message">
+                "Iemand anders heeft dit recept aangepast terwijl jij het bewerkte. "
+                {if differences.is_empty() {
+                    "Hun versie is hetzelfde als de jouwe, sla opnieuw op om je aanpassingen te bewaren."
+                } else {
+                    "Neem over wat je van hun versie wilt houden en sla dan opnieuw op."
+                }}
+            </p>
+            <Show when=move || !differences.is_empty()>
+                <table>
+                    <tr>
+                        <th></th>
+                        <th>"Jouw versie"</th>
+                        <th>"Huidige versie"</th>
+                        <th></th>
+                    </tr>
+                    {rows.clone()}
+                </table>
+            </Show>
+        </div>
+    }
+}
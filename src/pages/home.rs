@@ -1,35 +1,132 @@
 use leptos::prelude::*;
+use leptos_router::components::A;
+use leptos_router::hooks::use_query_map;
 
-use crate::recipe::{ListedRecipe, list_recipes};
+use crate::error::ErrorMessage;
+use crate::images::thumb_url;
+use crate::recipe::{SearchResult, list_recipes, search_recipes};
+use crate::tags::{list_tags, parse_tags_param, tags_href};
 
 /// Renders the home page of your application.
 #[component]
 pub fn HomePage() -> impl IntoView {
-    async fn fetch() -> Vec<ListedRecipe> {
-        list_recipes().await.unwrap()
-    }
+    let (query, set_query) = signal(String::new());
+
+    // The tags to filter on live in the URL, so filtered lists can be shared
+    // and the navbar's random button can pick from them
+    let query_map = use_query_map();
+    let selected_tags = Memo::new(move |_| parse_tags_param(query_map.read().get("tags")));
+
+    // Without the tags the recipes can still be listed
+    let all_tags = Resource::new(|| (), async |_| list_tags().await.unwrap_or_default());
+
+    // Without a query we list everything alphabetically, otherwise we show
+    // the search results in order of relevance
+    let recipes = Resource::new(
+        move || (query.get(), selected_tags.get()),
+        async |(query, tags)| {
+            if query.trim().is_empty() {
+                let mut recipes = list_recipes(tags).await?;
+
+                recipes.sort_by_cached_key(|rp| rp.title.clone());
+
+                Ok(recipes
+                    .into_iter()
+                    .map(|rp| SearchResult {
+                        id: rp.id,
+                        title: rp.title,
+                        thumbnail: rp.thumbnail,
+                        snippet: Vec::new(),
+                    })
+                    .collect())
+            } else {
+                search_recipes(query, tags).await
+            }
+        },
+    );
 
     view! {
         <h1>"Het NomNomNom Receptenboek"</h1>
-        <Await
-            future=fetch()
-            let:recipes
-        >
+        <input
+            type="search"
+            class="recipe-search"
+            placeholder="Zoek een recept..."
+            on:input=move |ev| set_query.set(event_target_value(&ev))
+            prop:value=query
+        />
+        <Suspense>
+            {move || all_tags.get().map(|all_tags| view! {
+                <ul class="tag-list tag-filter">
+                    {all_tags.into_iter().map(|tag| {
+                        let selected = selected_tags.read().contains(&tag);
+
+                        let href = {
+                            let tag = tag.clone();
+
+                            move || {
+                                let mut toggled = selected_tags.get();
+
+                                if selected {
+                                    toggled.retain(|t| *t != tag);
+                                } else {
+                                    toggled.push(tag.clone());
+                                }
+
+                                tags_href(&toggled)
+                            }
+                        };
+
+                        view! {
+                            <li>
+                                <A class:tag-chip class:tag-selected=selected href=href>
+                                    {tag}
+                                </A>
+                            </li>
+                        }
+                    }).collect_view()}
+                </ul>
+            })}
+        </Suspense>
+        <Transition fallback=move || view! { <p>"Recepten aan het laden..."</p> }>
             <ul>
-                {
-                    let mut recipes = recipes.clone();
+                {move || recipes.get().map(|recipes| {
+                    let recipes = match recipes {
+                        Ok(recipes) => recipes,
+                        Err(error) => return view! { <ErrorMessage error/> }.into_any(),
+                    };
 
-                    recipes.sort_by_cached_key(|rp| rp.title.clone());
+                    if recipes.is_empty() && !query.read().trim().is_empty() {
+                        return view! { <p>"Geen recepten gevonden"</p> }.into_any();
+                    }
 
-                    recipes.iter().map(|rp|{
+                    recipes.into_iter().map(|rp| {
                         let url = format!("/recipe/{}", rp.id);
 
+                        let snippet = (!rp.snippet.is_empty()).then(|| {
+                            let parts = rp.snippet.into_iter().map(|part| {
+                                if part.highlight {
+                                    view! { <mark>{part.text}</mark> }.into_any()
+                                } else {
+                                    part.text.into_any()
+                                }
+                            }).collect_view();
+
+                            view! { <p class="recipe-snippet">{parts}</p> }
+                        });
+
+                        let thumbnail = rp.thumbnail.map(|image| view! {
+                            <img class="recipe-thumb" src=thumb_url(image) alt=""/>
+                        });
+
                         view! {
-                            <li class="recipe-link"><a href={url}>{rp.title.clone()}</a></li>
+                            <li class="recipe-link">
+                                <a href={url}>{thumbnail}{rp.title}</a>
+                                {snippet}
+                            </li>
                         }
-                    }).collect_view()
-                }
+                    }).collect_view().into_any()
+                })}
             </ul>
-        </Await>
+        </Transition>
     }
 }
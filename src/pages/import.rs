@@ -0,0 +1,143 @@
+use leptos::ev::SubmitEvent;
+use leptos::html;
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+use leptos_router::components::A;
+
+use crate::cooklang::import_cooklang;
+use crate::export::{ImportMode, ImportSummary, import_recipes};
+
+/// Downloading all recipes, and importing them again or from Cooklang files.
+#[component]
+pub fn ImportPage() -> impl IntoView {
+    let document_elem: NodeRef<html::Input> = NodeRef::new();
+    let (mode, set_mode) = signal(ImportMode::Merge);
+    let (result, set_result) = signal(None::<Result<ImportSummary, String>>);
+
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+
+        let Some(file) = document_elem
+            .get()
+            .unwrap()
+            .files()
+            .and_then(|files| files.get(0))
+        else {
+            return;
+        };
+
+        let mode = mode.get_untracked();
+
+        if mode == ImportMode::Replace
+            && !web_sys::window()
+                .unwrap()
+                .confirm_with_message("Alle bestaande recepten worden verwijderd. Doorgaan?")
+                .unwrap()
+        {
+            return;
+        }
+
+        let form_data = web_sys::FormData::new().unwrap();
+        form_data.append_with_str("mode", mode.as_str()).unwrap();
+        form_data.append_with_blob("document", &file).unwrap();
+
+        spawn_local(async move {
+            let summary = import_recipes(form_data.into())
+                .await
+                .map_err(|err| err.to_string());
+
+            set_result.set(Some(summary));
+        });
+    };
+
+    let cooklang_elem: NodeRef<html::Input> = NodeRef::new();
+    let (cooklang_result, set_cooklang_result) = signal(None::<Result<ImportSummary, String>>);
+
+    let on_cooklang_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+
+        let Some(files) = cooklang_elem.get().unwrap().files() else {
+            return;
+        };
+
+        if files.length() == 0 {
+            return;
+        }
+
+        let form_data = web_sys::FormData::new().unwrap();
+        for file in (0..files.length()).filter_map(|i| files.get(i)) {
+            form_data
+                .append_with_blob_and_filename("file", &file, &file.name())
+                .unwrap();
+        }
+
+        spawn_local(async move {
+            let summary = import_cooklang(form_data.into())
+                .await
+                .map_err(|err| err.to_string());
+
+            set_cooklang_result.set(Some(summary));
+        });
+    };
+
+    view! {
+        <h1>"Exporteren en importeren"</h1>
+        <h3>"Exporteren"</h3>
+        <p>"Download alle recepten als JSON-bestand. Foto's worden niet meegenomen."</p>
+        <a class="link-button" href="/export.json" download rel="external">"Download recepten"</a>
+        <A class:link-button href="/cookbook">"Kookboek als PDF"</A>
+        <form on:submit=on_submit>
+            <h3>"Importeren"</h3>
+            <input type="file" accept="application/json,.json" node_ref=document_elem/>
+            <br/>
+            <label>
+                <input
+                    type="radio"
+                    name="mode"
+                    prop:checked=move || mode.get() == ImportMode::Merge
+                    on:change=move |_| set_mode.set(ImportMode::Merge)
+                />
+                "Samenvoegen: recepten met een titel die al bestaat worden overgeslagen"
+            </label>
+            <br/>
+            <label>
+                <input
+                    type="radio"
+                    name="mode"
+                    prop:checked=move || mode.get() == ImportMode::Replace
+                    on:change=move |_| set_mode.set(ImportMode::Replace)
+                />
+                "Vervangen: alle bestaande recepten worden eerst verwijderd"
+            </label>
+            <br/>
+            <input class="link-button button-positive" type="submit" value="Importeer"/>
+        </form>
+        {move || result.get().map(result_view)}
+        <form on:submit=on_cooklang_submit>
+            <h3>"Cooklang"</h3>
+            <p>
+                "Importeer losse .cook-bestanden of een zip met een map vol recepten. "
+                "Recepten met een titel die al bestaat worden overgeslagen. "
+                "Een recept exporteren als Cooklang kan vanaf het recept zelf."
+            </p>
+            <input type="file" accept=".cook,.zip,application/zip" multiple node_ref=cooklang_elem/>
+            <br/>
+            <input class="link-button button-positive" type="submit" value="Importeer"/>
+        </form>
+        {move || cooklang_result.get().map(result_view)}
+    }
+}
+
+/// What an import did, or why it failed.
+fn result_view(result: Result<ImportSummary, String>) -> AnyView {
+    match result {
+        Ok(summary) => view! {
+            <p>{format!("{} recepten geïmporteerd", summary.imported)}</p>
+            {(!summary.skipped.is_empty()).then(|| view! {
+                <p>"Overgeslagen, want die bestaan al: " {summary.skipped.join(", ")}</p>
+            })}
+        }
+        .into_any(),
+        Err(err) => view! { <p>"Importeren mislukt: " {err}</p> }.into_any(),
+    }
+}
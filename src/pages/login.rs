@@ -0,0 +1,55 @@
+use leptos::ev::SubmitEvent;
+use leptos::html;
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+use leptos_router::hooks::use_query_map;
+
+use crate::auth::login;
+
+/// Logging in, coming back to `?next=` afterwards.
+#[component]
+pub fn LoginPage() -> impl IntoView {
+    let username_elem: NodeRef<html::Input> = NodeRef::new();
+    let password_elem: NodeRef<html::Input> = NodeRef::new();
+    let (failed, set_failed) = signal(false);
+
+    let query = use_query_map();
+
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+
+        let username = username_elem.get().unwrap().value();
+        let password = password_elem.get().unwrap().value();
+
+        // Only back to a page of our own
+        let next = query
+            .read_untracked()
+            .get("next")
+            .filter(|next| next.starts_with('/') && !next.starts_with("//"))
+            .unwrap_or_else(|| "/".to_string());
+
+        spawn_local(async move {
+            match login(username, password).await {
+                // A full reload, so everything is fetched again as this user
+                Ok(()) => web_sys::window()
+                    .unwrap()
+                    .location()
+                    .set_href(&next)
+                    .unwrap(),
+                Err(_) => set_failed.set(true),
+            }
+        });
+    };
+
+    view! {
+        <h1>"Inloggen"</h1>
+        <form on:submit=on_submit>
+            <input type="text" placeholder="Gebruikersnaam" autocomplete="username" required node_ref=username_elem/>
+            <br/>
+            <input type="password" placeholder="Wachtwoord" autocomplete="current-password" required node_ref=password_elem/>
+            <br/>
+            <input class="link-button button-positive" type="submit" value="Inloggen"/>
+        </form>
+        {move || failed.get().then(|| view! { <p>"Onjuiste gebruikersnaam of wachtwoord"</p> })}
+    }
+}
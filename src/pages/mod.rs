@@ -1,5 +1,15 @@
+pub mod cookbook;
 pub mod editrecipe;
 pub mod home;
+pub mod import;
+pub mod login;
 pub mod newrecipe;
+pub mod pantry;
+pub mod plan;
+pub mod print;
 pub mod recipe;
+pub mod settings;
+pub mod shared;
+pub mod shopping;
 pub mod trmnl;
+pub mod users;
@@ -4,30 +4,120 @@ use leptos::prelude::*;
 use leptos::reactive::spawn_local;
 use leptos_router::NavigateOptions;
 
+use crate::error::{ErrorMessage, NomError};
+use crate::images::upload_selected;
+use crate::jsonld::extract_recipe;
 use crate::recipe::{RawRecipe, new_recipe};
+use crate::tags::TagInput;
 
 #[component]
 pub fn NewRecipePage() -> impl IntoView {
     let title_elem: NodeRef<html::Input> = NodeRef::new();
+    let servings_elem: NodeRef<html::Input> = NodeRef::new();
+    let prep_time_elem: NodeRef<html::Input> = NodeRef::new();
+    let cook_time_elem: NodeRef<html::Input> = NodeRef::new();
     let ingredient_elem: NodeRef<html::Textarea> = NodeRef::new();
     let instruction_elem: NodeRef<html::Textarea> = NodeRef::new();
+    let tags = RwSignal::new(Vec::new());
+    let images_elem: NodeRef<html::Input> = NodeRef::new();
+
+    let page_file_elem: NodeRef<html::Input> = NodeRef::new();
+    let page_source_elem: NodeRef<html::Textarea> = NodeRef::new();
+    let (import_error, set_import_error) = signal(false);
+    let (image_url, set_image_url) = signal(None::<String>);
+    let (error, set_error) = signal(None::<NomError>);
+
+    // Fills in the form from a saved recipe page, so it can be checked
+    // before saving
+    let import_page = move |_| {
+        let form_data = web_sys::FormData::new().unwrap();
+
+        if let Some(file) = page_file_elem
+            .get()
+            .unwrap()
+            .files()
+            .and_then(|files| files.get(0))
+        {
+            form_data.append_with_blob("page", &file).unwrap();
+        }
+
+        form_data
+            .append_with_str("page", &page_source_elem.get().unwrap().value())
+            .unwrap();
+
+        spawn_local(async move {
+            let Ok(extracted) = extract_recipe(form_data.into()).await else {
+                set_import_error.set(true);
+                return;
+            };
+
+            let recipe = extracted.recipe;
+            let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+
+            title_elem.get().unwrap().set_value(&recipe.title);
+            servings_elem
+                .get()
+                .unwrap()
+                .set_value(&number(recipe.servings));
+            prep_time_elem
+                .get()
+                .unwrap()
+                .set_value(&number(recipe.prep_time));
+            cook_time_elem
+                .get()
+                .unwrap()
+                .set_value(&number(recipe.cook_time));
+            ingredient_elem
+                .get()
+                .unwrap()
+                .set_value(&recipe.ingredients);
+            instruction_elem
+                .get()
+                .unwrap()
+                .set_value(&recipe.instructions);
+
+            set_image_url.set(extracted.image);
+            set_import_error.set(false);
+        });
+    };
 
     let on_submit = move |ev: SubmitEvent| {
         // stop the page from reloading!
         ev.prevent_default();
 
         let title = title_elem.get().unwrap().value();
+        let servings = servings_elem.get().unwrap().value().parse().ok();
+        let prep_time = prep_time_elem.get().unwrap().value().parse().ok();
+        let cook_time = cook_time_elem.get().unwrap().value().parse().ok();
         let ingredients = ingredient_elem.get().unwrap().value();
         let instructions = instruction_elem.get().unwrap().value();
+        let tags = tags.get();
+        let images_input = images_elem.get().unwrap();
 
-        spawn_local(async {
-            new_recipe(RawRecipe {
+        spawn_local(async move {
+            let created = new_recipe(RawRecipe {
                 title,
+                servings,
+                prep_time,
+                cook_time,
                 ingredients,
                 instructions,
+                tags,
             })
-            .await
-            .unwrap();
+            .await;
+
+            let id = match created {
+                Ok(id) => id,
+                Err(err) => {
+                    set_error.set(Some(err));
+                    return;
+                }
+            };
+
+            if let Err(err) = upload_selected(id, &images_input).await {
+                set_error.set(Some(err.into()));
+                return;
+            }
 
             let navigate = leptos_router::hooks::use_navigate();
 
@@ -37,16 +127,47 @@ pub fn NewRecipePage() -> impl IntoView {
 
     view! {
         <h1>"Nieuw recept"</h1>
+        <details class="recipe-import">
+            <summary>"Importeer van een website"</summary>
+            <p>"Sla de pagina met het recept op en kies het bestand, of plak de broncode van de pagina."</p>
+            <input type="file" accept=".html,.htm,text/html" node_ref=page_file_elem/>
+            <br/>
+            <textarea placeholder="Broncode van de pagina" node_ref=page_source_elem/>
+            <br/>
+            <button type="button" class:link-button on:click=import_page>"Lees recept"</button>
+            {move || import_error.get().then(|| view! { <p>"Geen recept gevonden in deze pagina"</p> })}
+        </details>
         <form on:submit=on_submit>
             <h3>Titel</h3>
             <input type="text" placeholder="Titel" node_ref=title_elem/>
             <br/>
+            <h3>Personen</h3>
+            <input type="number" min="1" placeholder="Aantal personen" node_ref=servings_elem/>
+            <br/>
+            <h3>Tijd</h3>
+            <input type="number" min="0" placeholder="Voorbereiding (minuten)" node_ref=prep_time_elem/>
+            <input type="number" min="0" placeholder="Bereiding (minuten)" node_ref=cook_time_elem/>
+            <br/>
             <h3>Ingredienten</h3>
             <textarea placeholder="Ingredienten" node_ref=ingredient_elem/>
             <br/>
             <h3>Instructies</h3>
             <textarea placeholder="Instructies" node_ref=instruction_elem/>
             <br/>
+            <h3>Tags</h3>
+            <TagInput tags=tags/>
+            <br/>
+            <h3>"Foto's"</h3>
+            {move || image_url.get().map(|url| view! {
+                <img class="recipe-gallery-image" src=url.clone() alt=""/>
+                <p>
+                    "Deze foto staat op de website, sla hem op en voeg hem hieronder toe om hem te bewaren: "
+                    <a href=url target="_blank" rel="external noopener">"open foto"</a>
+                </p>
+            })}
+            <input type="file" accept="image/*" multiple node_ref=images_elem/>
+            <br/>
+            {move || error.get().map(|error| view! { <ErrorMessage error/> })}
             <input class="link-button button-positive" type="submit" value="Maak"/>
         </form>
     }
@@ -0,0 +1,78 @@
+use leptos::ev::SubmitEvent;
+use leptos::html;
+use leptos::prelude::*;
+
+use crate::error::ErrorMessage;
+use crate::recipe::find_recipes_by_ingredients;
+
+/// "Wat kan ik maken?": finds recipes for the ingredients you have on hand.
+#[component]
+pub fn PantryPage() -> impl IntoView {
+    let available_elem: NodeRef<html::Textarea> = NodeRef::new();
+    let (available, set_available) = signal(Vec::<String>::new());
+
+    let matches = Resource::new(
+        move || available.get(),
+        async |available| find_recipes_by_ingredients(available).await,
+    );
+
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+
+        let value = available_elem.get().unwrap().value();
+
+        set_available.set(
+            value
+                .split([',', '\n'])
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+        );
+    };
+
+    view! {
+        <h1>"Wat kan ik maken?"</h1>
+        <form on:submit=on_submit>
+            <h3>"Wat heb je in huis?"</h3>
+            <textarea placeholder="Bijvoorbeeld: kip, paprika, rijst" node_ref=available_elem/>
+            <br/>
+            <input class="link-button button-positive" type="submit" value="Zoek"/>
+        </form>
+        <Transition fallback=move || view! { <p>"Recepten aan het zoeken..."</p> }>
+            {move || matches.get().map(|matches| {
+                let matches = match matches {
+                    Ok(matches) => matches,
+                    Err(error) => return view! { <ErrorMessage error/> }.into_any(),
+                };
+
+                if matches.is_empty() {
+                    return (!available.read().is_empty())
+                        .then(|| view! { <p>"Geen recepten gevonden"</p> })
+                        .into_any();
+                }
+
+                view! {
+                    <ul>
+                        {matches.into_iter().map(|m| {
+                            let url = format!("/recipe/{}", m.id);
+                            let total = m.have.len() + m.missing.len();
+
+                            view! {
+                                <li class="recipe-link">
+                                    <a href={url}>{m.title}</a>
+                                    <div class="ingredient-match">
+                                        <p>{format!("{} van de {total} ingredienten in huis", m.have.len())}</p>
+                                        {(!m.missing.is_empty()).then(|| view! {
+                                            <p>"Nog nodig: " {m.missing.join(", ")}</p>
+                                        })}
+                                    </div>
+                                </li>
+                            }
+                        }).collect_view()}
+                    </ul>
+                }.into_any()
+            })}
+        </Transition>
+    }
+}
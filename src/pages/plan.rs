@@ -0,0 +1,292 @@
+use chrono::{Days, NaiveDate};
+use leptos::ev::SubmitEvent;
+use leptos::html;
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+use leptos_router::NavigateOptions;
+use leptos_router::components::A;
+use leptos_router::hooks::{use_navigate, use_query_map};
+
+use crate::auth::{RequireRole, Role};
+use crate::error::{ErrorMessage, NomError};
+use crate::plan::{
+    MealSlot, PlannedMeal, WeekPlan, add_meal, autofill_week, format_day, get_week_plan, move_meal,
+    remove_meal, week_days,
+};
+use crate::recipe::{ListedRecipe, list_recipes};
+use crate::shopping::add_plan_to_shopping_list;
+
+fn week_href(start: NaiveDate) -> String {
+    format!("/plan?week={start}")
+}
+
+/// The meal plan of a week, defaulting to the current one.
+#[component]
+pub fn PlanPage() -> impl IntoView {
+    let query = use_query_map();
+    let week = Memo::new(move |_| {
+        query
+            .read()
+            .get("week")
+            .and_then(|week| week.parse::<NaiveDate>().ok())
+    });
+
+    let plan = Resource::new(move || week.get(), async |week| get_week_plan(week).await);
+
+    let recipes = Resource::new(
+        || (),
+        async |_| {
+            let mut recipes = list_recipes(Vec::new()).await?;
+
+            recipes.sort_by_cached_key(|rp| rp.title.clone());
+
+            Ok(recipes)
+        },
+    );
+
+    // The cell showing the form to add a meal, if any
+    let adding = RwSignal::new(None::<(NaiveDate, MealSlot)>);
+    let error = RwSignal::new(None::<NomError>);
+
+    view! {
+        <h1>"Weekmenu"</h1>
+        {move || error.get().map(|error| view! { <ErrorMessage error/> })}
+        <Transition fallback=move || view! { <p>"Weekmenu aan het laden..."</p> }>
+            {move || plan.get().map(|week_plan| {
+                let week_plan = match week_plan {
+                    Ok(week_plan) => week_plan,
+                    Err(error) => return view! { <ErrorMessage error/> }.into_any(),
+                };
+
+                let start = week_plan.start;
+                let days: Vec<NaiveDate> = week_days(start).collect();
+
+                let autofill = move |slot: MealSlot| {
+                    spawn_local(async move {
+                        match autofill_week(start, slot, Vec::new()).await {
+                            Ok(_) => plan.refetch(),
+                            Err(err) => error.set(Some(err)),
+                        }
+                    });
+                };
+
+                let to_shopping_list = move |_| {
+                    spawn_local(async move {
+                        if let Err(err) = add_plan_to_shopping_list(start).await {
+                            error.set(Some(err));
+                            return;
+                        }
+
+                        let navigate = use_navigate();
+                        navigate("/shopping", NavigateOptions::default());
+                    });
+                };
+
+                let header = MealSlot::ALL.map(|slot| view! {
+                    <th>
+                        {slot.label()}
+                        <RequireRole role=Role::Editor>
+                            <button
+                                type="button"
+                                class="plan-autofill"
+                                title="Vul lege plekken aan met willekeurige recepten"
+                                on:click=move |_| autofill(slot)
+                            >"Vul aan"</button>
+                        </RequireRole>
+                    </th>
+                });
+
+                let rows = days.iter().map(|day| {
+                    let cells = MealSlot::ALL.map(|slot| view! {
+                        <td>
+                            <PlanCell
+                                date=*day
+                                meal_slot=slot
+                                meals=week_plan.meals_at(*day, slot)
+                                days=days.clone()
+                                plan
+                                recipes
+                                adding
+                                error
+                            />
+                        </td>
+                    });
+
+                    view! {
+                        <tr>
+                            <th>{format_day(*day)}</th>
+                            {cells}
+                        </tr>
+                    }
+                }).collect_view();
+
+                view! {
+                    <nav class="plan-nav">
+                        <A class:link-button href=week_href(start - Days::new(7))>"← Vorige week"</A>
+                        <A class:link-button href="/plan">"Deze week"</A>
+                        <A class:link-button href=week_href(start + Days::new(7))>"Volgende week →"</A>
+                        <RequireRole role=Role::Editor>
+                            <button class:link-button on:click=to_shopping_list>"Op boodschappenlijst"</button>
+                        </RequireRole>
+                    </nav>
+                    <h3>{format!("Week van {}", format_day(start))}</h3>
+                    <table class="meal-plan">
+                        <thead>
+                            <tr>
+                                <th></th>
+                                {header}
+                            </tr>
+                        </thead>
+                        <tbody>{rows}</tbody>
+                    </table>
+                }
+                .into_any()
+            })}
+        </Transition>
+    }
+}
+
+/// The meals planned in one slot, with controls for editors to move or remove
+/// them and to add another one.
+#[component]
+fn PlanCell(
+    date: NaiveDate,
+    meal_slot: MealSlot,
+    meals: Vec<PlannedMeal>,
+    days: Vec<NaiveDate>,
+    plan: Resource<Result<WeekPlan, NomError>>,
+    recipes: Resource<Result<Vec<ListedRecipe>, NomError>>,
+    adding: RwSignal<Option<(NaiveDate, MealSlot)>>,
+    error: RwSignal<Option<NomError>>,
+) -> impl IntoView {
+    let meals = meals
+        .into_iter()
+        .map(|meal| {
+            let meal_id = meal.id;
+
+            let remove = move |_| {
+                spawn_local(async move {
+                    match remove_meal(meal_id).await {
+                        Ok(()) => plan.refetch(),
+                        Err(err) => error.set(Some(err)),
+                    }
+                });
+            };
+
+            // Targets are encoded as "<date>/<slot>"
+            let on_move = move |ev| {
+                let target = event_target_value(&ev);
+
+                let Some((date, slot)) = target
+                    .split_once('/')
+                    .and_then(|(date, slot)| Some((date.parse().ok()?, slot.parse().ok()?)))
+                else {
+                    return;
+                };
+
+                spawn_local(async move {
+                    match move_meal(meal_id, date, slot).await {
+                        Ok(()) => plan.refetch(),
+                        Err(err) => error.set(Some(err)),
+                    }
+                });
+            };
+
+            let days = days.clone();
+            let current = (meal.date, meal.slot);
+            let targets = move || {
+                days.iter()
+                    .flat_map(|day| MealSlot::ALL.map(|slot| (*day, slot)))
+                    .filter(|target| *target != current)
+                    .map(|(day, slot)| {
+                        view! {
+                            <option value=format!("{day}/{slot}")>
+                                {format!("{}, {slot}", format_day(day))}
+                            </option>
+                        }
+                    })
+                    .collect_view()
+            };
+
+            view! {
+                <li class="plan-meal">
+                    <A href=format!("/recipe/{}", meal.recipe_id)>{meal.title}</A>
+                    {meal.servings.map(|servings| view! {
+                        <span class="plan-servings">{format!(" ({servings} personen)")}</span>
+                    })}
+                    <RequireRole role=Role::Editor>
+                        <select class="plan-move" on:change=on_move>
+                            <option value="" selected>"Verplaats naar..."</option>
+                            {targets()}
+                        </select>
+                        <button type="button" class="plan-remove" on:click=remove>"×"</button>
+                    </RequireRole>
+                </li>
+            }
+        })
+        .collect_view();
+
+    let recipe_elem: NodeRef<html::Select> = NodeRef::new();
+    let servings_elem: NodeRef<html::Input> = NodeRef::new();
+
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+
+        let Ok(recipe_id) = recipe_elem.get().unwrap().value().parse::<i64>() else {
+            return;
+        };
+
+        // Left empty, the servings of the recipe are used
+        let servings = servings_elem.get().unwrap().value().parse::<u32>().ok();
+
+        spawn_local(async move {
+            match add_meal(date, meal_slot, recipe_id, servings).await {
+                Ok(_) => {
+                    adding.set(None);
+                    plan.refetch();
+                }
+                Err(err) => error.set(Some(err)),
+            }
+        });
+    };
+
+    let add_form = move || {
+        if adding.get() != Some((date, meal_slot)) {
+            return view! {
+                <button
+                    type="button"
+                    class="plan-add"
+                    on:click=move |_| adding.set(Some((date, meal_slot)))
+                >"+"</button>
+            }
+            .into_any();
+        }
+
+        view! {
+            <form class="plan-add-form" on:submit=on_submit>
+                <select node_ref=recipe_elem>
+                    <Suspense>
+                        {move || recipes.get().and_then(Result::ok).map(|recipes| {
+                            recipes.into_iter().map(|rp| view! {
+                                <option value=rp.id>{rp.title}</option>
+                            }).collect_view()
+                        })}
+                    </Suspense>
+                </select>
+                <input type="number" min="1" placeholder="Personen" node_ref=servings_elem/>
+                <input class="link-button button-positive" type="submit" value="Toevoegen"/>
+                <button type="button" class:link-button on:click=move |_| adding.set(None)>
+                    "Annuleer"
+                </button>
+            </form>
+            {move || recipes.get().and_then(Result::err).map(|error| view! { <ErrorMessage error/> })}
+        }
+        .into_any()
+    };
+
+    view! {
+        <ul class="plan-meals">{meals}</ul>
+        <RequireRole role=Role::Editor>{add_form}</RequireRole>
+    }
+    .into_any()
+}
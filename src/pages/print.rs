@@ -0,0 +1,115 @@
+use leptos::prelude::*;
+use leptos_router::components::A;
+use leptos_router::hooks::{use_params, use_query_map};
+use leptos_router::params::Params;
+
+use crate::error::{ErrorMessage, NomError};
+use crate::images::large_url;
+use crate::recipe::{Recipe, get_recipe};
+
+#[derive(Debug, Params, PartialEq)]
+struct RecipeArgs {
+    id: Option<String>,
+}
+
+/// A recipe laid out to fit on a single printed page, without the navbar and
+/// buttons. Takes `?servings=6` to print it scaled.
+#[component]
+pub fn PrintRecipePage() -> impl IntoView {
+    let id = move || {
+        use_params::<RecipeArgs>()
+            .read()
+            .as_ref()
+            .ok()
+            .and_then(|params| params.id.clone())
+            .unwrap_or_default()
+    };
+
+    let query = use_query_map();
+    let servings = move || {
+        query
+            .read()
+            .get("servings")
+            .and_then(|servings| servings.parse::<u32>().ok())
+            .filter(|servings| *servings > 0)
+    };
+
+    let recipe_resource = Resource::new(id, async |id| {
+        let parsed: i64 = id
+            .parse()
+            .map_err(|_| NomError::not_found(format!("recipe {id}")))?;
+        get_recipe(parsed).await.map(|recipe| (parsed, recipe))
+    });
+
+    let render_recipe = move || {
+        recipe_resource.get().map(|recipe| match recipe {
+            Ok((id, recipe)) => view! { <PrintRecipe id recipe servings=servings()/> }.into_any(),
+            Err(NomError::NotFound(_)) => view! { <h2>"Onbekend recept"</h2>}.into_any(),
+            Err(error) => view! { <ErrorMessage error/> }.into_any(),
+        })
+    };
+
+    view! {
+        <Suspense fallback=move || view!{ <p>"Recept aan het laden..."</p>}>
+            {render_recipe}
+        </Suspense>
+    }
+}
+
+#[component]
+fn PrintRecipe(id: i64, recipe: Recipe, servings: Option<u32>) -> impl IntoView {
+    // Only recipes that know their servings can be scaled
+    let servings = recipe
+        .servings
+        .map(|original| (original, servings.unwrap_or(original)));
+    let factor = servings.map_or(1.0, |(original, wanted)| wanted as f64 / original as f64);
+
+    let details = servings
+        .map(|(_, wanted)| format!("Voor {wanted} personen"))
+        .into_iter()
+        .chain(recipe.times())
+        .collect::<Vec<_>>();
+
+    let ingredients = recipe
+        .ingredients
+        .iter()
+        .map(|ingr| view! { <li>{ingr.scaled(factor).to_string()}</li> })
+        .collect_view();
+
+    let instructions = recipe
+        .instructions
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| view! { <p>{line.to_string()}</p> })
+        .collect_view();
+
+    let print = move |_| {
+        web_sys::window().unwrap().print().unwrap();
+    };
+
+    view! {
+        <div class="print-actions">
+            <A class:link-button href=format!("/recipe/{id}")>"Terug"</A>
+            <button class:link-button on:click=print>"Afdrukken"</button>
+            <a class="link-button" href=format!("/recipes.pdf?ids={id}") download rel="external">"PDF"</a>
+        </div>
+        <article class="print-recipe">
+            {recipe.images.first().map(|image| view! {
+                <img class="print-hero" src=large_url(*image) alt=""/>
+            })}
+            <h1>{recipe.title}</h1>
+            {(!details.is_empty()).then(|| view! { <p class="print-details">{details.join(" · ")}</p> })}
+            <div class="print-columns">
+                <section>
+                    <h3>"Ingrediënten"</h3>
+                    <ul>{ingredients}</ul>
+                </section>
+                <section>
+                    <h3>"Bereiding"</h3>
+                    {instructions}
+                </section>
+            </div>
+        </article>
+    }
+}
@@ -2,6 +2,7 @@ use leptos::prelude::*;
 use leptos_router::hooks::use_params;
 use leptos_router::params::Params;
 
+use crate::error::{ErrorMessage, NomError};
 use crate::recipe::{RecipeComponent, get_recipe};
 
 #[derive(Debug, Params, PartialEq)]
@@ -17,20 +18,24 @@ pub fn RecipePage() -> impl IntoView {
             .as_ref()
             .ok()
             .and_then(|params| params.id.clone())
-            .unwrap()
+            .unwrap_or_default()
     };
 
     let recipe_resource = Resource::new(id, async |id| {
-        let parsed: i64 = id.parse().unwrap();
-        (parsed, get_recipe(parsed).await.unwrap())
+        let parsed: i64 = id
+            .parse()
+            .map_err(|_| NomError::not_found(format!("recipe {id}")))?;
+        get_recipe(parsed).await.map(|recipe| (parsed, recipe))
     });
 
     let render_recipe = move || {
-        recipe_resource.get().map(|(id, recipe)| match recipe {
-            Some(recipe) => {
-                view! {<RecipeComponent id={id} recipe={recipe} with_mod=true/> }.into_any()
+        recipe_resource.get().map(|recipe| match recipe {
+            Ok((id, recipe)) => {
+                view! {<RecipeComponent id={id} recipe={recipe} with_mod=true scalable=true/> }
+                    .into_any()
             }
-            None => view! { <h2>"Onbekend recept"</h2>}.into_any(),
+            Err(NomError::NotFound(_)) => view! { <h2>"Onbekend recept"</h2>}.into_any(),
+            Err(error) => view! { <ErrorMessage error/> }.into_any(),
         })
     };
 
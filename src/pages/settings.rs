@@ -0,0 +1,185 @@
+use leptos::ev::SubmitEvent;
+use leptos::html;
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+use leptos_router::components::A;
+
+use super::users::change_password;
+use crate::auth::tokens::{TokenScope, create_api_token, list_api_tokens, revoke_api_token};
+use crate::auth::{CurrentUser, Role, User};
+use crate::error::{ErrorMessage, NomError};
+
+/// The own account: its password and API tokens.
+#[component]
+pub fn SettingsPage() -> impl IntoView {
+    let CurrentUser(current_user) = expect_context();
+
+    view! {
+        <h1>"Instellingen"</h1>
+        <Suspense fallback=move || view! { <p>"Instellingen aan het laden..."</p> }>
+            {move || current_user.get().map(|user| match user {
+                Some(user) => {
+                    let User { id, username, role } = user;
+
+                    view! {
+                        <p>{format!("Ingelogd als {username} ({})", role.label().to_lowercase())}</p>
+                        <button class:link-button on:click=move |_| change_password(id, username.clone())>
+                            "Wachtwoord wijzigen"
+                        </button>
+                        {(role == Role::Admin).then(|| view! {
+                            <A class:link-button href="/users">"Gebruikers beheren"</A>
+                        })}
+                        <ApiTokens/>
+                    }
+                    .into_any()
+                }
+                None => view! {
+                    <p>"Er zijn nog geen gebruikers. Maak er eerst een aan om API-tokens te gebruiken."</p>
+                    <A class:link-button href="/users">"Gebruikers"</A>
+                }
+                .into_any(),
+            })}
+        </Suspense>
+    }
+}
+
+/// Tokens for scripts and devices like the TRMNL display, sent as
+/// `Authorization: Bearer <token>`.
+#[component]
+fn ApiTokens() -> impl IntoView {
+    let tokens = Resource::new(|| (), async |_| list_api_tokens().await);
+    let error = RwSignal::new(None::<NomError>);
+
+    let name_elem: NodeRef<html::Input> = NodeRef::new();
+    let scopes = RwSignal::new(Vec::<TokenScope>::new());
+    let (created, set_created) = signal(None::<Result<String, String>>);
+
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+
+        let name = name_elem.get().unwrap().value();
+
+        spawn_local(async move {
+            let token = create_api_token(name, scopes.get_untracked())
+                .await
+                .map_err(|err| err.to_string());
+
+            if token.is_ok() {
+                name_elem.get().unwrap().set_value("");
+                scopes.set(Vec::new());
+                tokens.refetch();
+            }
+
+            set_created.set(Some(token));
+        });
+    };
+
+    let scope_inputs = TokenScope::ALL
+        .map(|scope| {
+            let toggle = move |ev| {
+                if event_target_checked(&ev) {
+                    scopes.update(|scopes| scopes.push(scope));
+                } else {
+                    scopes.update(|scopes| scopes.retain(|s| *s != scope));
+                }
+            };
+
+            view! {
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=move || scopes.read().contains(&scope)
+                        on:change=toggle
+                    />
+                    {scope.label()}
+                </label>
+                <br/>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <h3>"API-tokens"</h3>
+        <p>
+            "Voor scripts en apparaten zoals het TRMNL-scherm, mee te sturen als "
+            <code>"Authorization: Bearer <token>"</code>
+            ". Zonder beperkingen kan een token alles wat jij kan."
+        </p>
+        {move || error.get().map(|error| view! { <ErrorMessage error/> })}
+        <Transition fallback=move || view! { <p>"Tokens aan het laden..."</p> }>
+            {move || tokens.get().map(|list| {
+                let list = match list {
+                    Ok(list) => list,
+                    Err(err) => return view! { <ErrorMessage error=err.into()/> }.into_any(),
+                };
+
+                if list.is_empty() {
+                    return view! { <p>"Nog geen tokens."</p> }.into_any();
+                }
+
+                view! {
+                    <ul class="token-list">
+                        {list.into_iter().map(|token| {
+                            let token_id = token.id;
+
+                            let revoke = {
+                                let name = token.name.clone();
+
+                                move |_| {
+                                    if !web_sys::window()
+                                        .unwrap()
+                                        .confirm_with_message(&format!("Token {name} intrekken? Wat het gebruikt heeft dan geen toegang meer."))
+                                        .unwrap()
+                                    {
+                                        return;
+                                    }
+
+                                    spawn_local(async move {
+                                        match revoke_api_token(token_id).await {
+                                            Ok(()) => tokens.refetch(),
+                                            Err(err) => error.set(Some(err.into())),
+                                        }
+                                    });
+                                }
+                            };
+
+                            let scopes = if token.scopes.is_empty() {
+                                "Alles".to_string()
+                            } else {
+                                token.scopes.iter().map(|scope| scope.label()).collect::<Vec<_>>().join(", ")
+                            };
+
+                            let last_used = token.last_used.map_or("nooit gebruikt".to_string(), |used| {
+                                format!("laatst gebruikt {} UTC", used.format("%d-%m-%Y %H:%M"))
+                            });
+
+                            view! {
+                                <li>
+                                    <strong>{token.name}</strong>
+                                    {format!(" ({scopes}) · gemaakt {} · {last_used}", token.created.format("%d-%m-%Y"))}
+                                    <button class:link-button class:button-negative on:click=revoke>"Intrekken"</button>
+                                </li>
+                            }
+                        }).collect_view()}
+                    </ul>
+                }
+                .into_any()
+            })}
+        </Transition>
+        <form on:submit=on_submit>
+            <h3>"Nieuw token"</h3>
+            <input type="text" placeholder="Naam, bijvoorbeeld TRMNL keuken" required node_ref=name_elem/>
+            <br/>
+            {scope_inputs}
+            <input class="link-button button-positive" type="submit" value="Maak token"/>
+        </form>
+        {move || created.get().map(|created| match created {
+            Ok(token) => view! {
+                <p>"Kopieer het token nu, het wordt hierna niet meer getoond:"</p>
+                <p><code class="api-token">{token}</code></p>
+            }
+            .into_any(),
+            Err(err) => view! { <p>"Token maken mislukt: " {err}</p> }.into_any(),
+        })}
+    }
+}
@@ -0,0 +1,47 @@
+use leptos::prelude::*;
+use leptos_router::hooks::use_params;
+use leptos_router::params::Params;
+
+use crate::error::ErrorMessage;
+use crate::recipe::RecipeComponent;
+use crate::share::get_shared_recipe;
+
+#[derive(Debug, Params, PartialEq)]
+struct ShareArgs {
+    token: Option<String>,
+}
+
+/// A recipe shown through a share link, to people without an account.
+#[component]
+pub fn SharedRecipePage() -> impl IntoView {
+    let token = move || {
+        use_params::<ShareArgs>()
+            .read()
+            .as_ref()
+            .ok()
+            .and_then(|params| params.token.clone())
+            .unwrap_or_default()
+    };
+
+    let recipe_resource = Resource::new(token, async |token| {
+        let recipe = get_shared_recipe(token.clone()).await;
+        (token, recipe)
+    });
+
+    let render_recipe = move || {
+        recipe_resource.get().map(|(token, recipe)| match recipe {
+            Ok(Some((id, recipe))) => view! {
+                <RecipeComponent id recipe with_mod=false scalable=true share_token=token/>
+            }
+            .into_any(),
+            Ok(None) => view! { <h2>"Deze link werkt niet (meer)"</h2> }.into_any(),
+            Err(err) => view! { <ErrorMessage error=err.into()/> }.into_any(),
+        })
+    };
+
+    view! {
+        <Suspense fallback=move || view!{ <p>"Recept aan het laden..."</p>}>
+            {render_recipe}
+        </Suspense>
+    }
+}
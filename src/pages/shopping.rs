@@ -0,0 +1,120 @@
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+use leptos_router::components::A;
+
+use crate::auth::{RequireRole, Role};
+use crate::error::{ErrorMessage, NomError};
+use crate::shopping::{
+    ShoppingItem, clear_shopping_list, get_shopping_list, remove_from_shopping_list,
+    set_item_checked,
+};
+
+/// The shopping list, grouped by store section so it can be followed while
+/// walking through the store.
+#[component]
+pub fn ShoppingPage() -> impl IntoView {
+    let list = Resource::new(|| (), async |_| get_shopping_list().await);
+    let error = RwSignal::new(None::<NomError>);
+
+    let clear = move |_| {
+        spawn_local(async move {
+            match clear_shopping_list().await {
+                Ok(()) => list.refetch(),
+                Err(err) => error.set(Some(err)),
+            }
+        });
+    };
+
+    view! {
+        <h1>"Boodschappenlijst"</h1>
+        {move || error.get().map(|error| view! { <ErrorMessage error/> })}
+        <Transition fallback=move || view! { <p>"Boodschappenlijst aan het laden..."</p> }>
+            {move || list.get().map(|shopping_list| {
+                let shopping_list = match shopping_list {
+                    Ok(shopping_list) => shopping_list,
+                    Err(error) => return view! { <ErrorMessage error/> }.into_any(),
+                };
+
+                if shopping_list.recipes.is_empty() {
+                    return view! {
+                        <p>"De boodschappenlijst is leeg. Voeg recepten toe vanaf een recept of het weekmenu."</p>
+                    }
+                    .into_any();
+                }
+
+                let sections = shopping_list.sections.into_iter().map(|section| view! {
+                    <h3>{section.name}</h3>
+                    <ul class="shopping-items">
+                        {section.items.into_iter().map(|item| view! { <ShoppingItemRow item error/> }).collect_view()}
+                    </ul>
+                }).collect_view();
+
+                let recipes = shopping_list.recipes.into_iter().map(|recipe| {
+                    let entry_id = recipe.id;
+
+                    let remove = move |_| {
+                        spawn_local(async move {
+                            match remove_from_shopping_list(entry_id).await {
+                                Ok(()) => list.refetch(),
+                                Err(err) => error.set(Some(err)),
+                            }
+                        });
+                    };
+
+                    view! {
+                        <li>
+                            <A href=format!("/recipe/{}", recipe.recipe_id)>{recipe.title}</A>
+                            {recipe.servings.map(|servings| format!(" ({servings} personen)"))}
+                            <RequireRole role=Role::Editor>
+                                <button type="button" class="plan-remove" on:click=remove>"×"</button>
+                            </RequireRole>
+                        </li>
+                    }
+                }).collect_view();
+
+                view! {
+                    {sections}
+                    <h3>"Voor de recepten"</h3>
+                    <ul class="shopping-recipes">{recipes}</ul>
+                    <RequireRole role=Role::Editor>
+                        <button class:link-button class:button-negative on:click=clear>"Lijst leegmaken"</button>
+                    </RequireRole>
+                }
+                .into_any()
+            })}
+        </Transition>
+    }
+}
+
+/// An item with a checkbox to tick it off. The checked state is kept locally
+/// too, so ticking off is instant on a bad connection. It is put back when
+/// the server refuses.
+#[component]
+fn ShoppingItemRow(item: ShoppingItem, error: RwSignal<Option<NomError>>) -> impl IntoView {
+    let checked = RwSignal::new(item.checked);
+    let key = item.key;
+
+    let toggle = move |ev| {
+        let now_checked = event_target_checked(&ev);
+        let key = key.clone();
+
+        checked.set(now_checked);
+
+        spawn_local(async move {
+            if let Err(err) = set_item_checked(key, now_checked).await {
+                checked.set(!now_checked);
+                error.set(Some(err));
+            }
+        });
+    };
+
+    view! {
+        <li class="shopping-item" class:shopping-item-checked=checked>
+            <label>
+                <input type="checkbox" prop:checked=checked on:change=toggle/>
+                <span>{item.text}</span>
+                <span class="shopping-item-recipes">{item.recipes.join(", ")}</span>
+            </label>
+        </li>
+    }
+}
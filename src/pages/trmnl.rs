@@ -1,19 +1,32 @@
 use leptos::prelude::*;
+use leptos_router::hooks::use_query_map;
 
+use crate::error::{ErrorMessage, NomError};
 use crate::recipe::{Recipe, RecipeComponent, get_recipe, random_recipe};
+use crate::tags::parse_tags_param;
 
+/// A random recipe for the TRMNL display, optionally limited with
+/// `?tags=diner,snel`.
 #[component]
 pub fn TrmnlPage() -> impl IntoView {
-    async fn fetch() -> (i64, Recipe) {
-        let id = random_recipe().await.unwrap().unwrap();
-        let recipe = get_recipe(id).await.unwrap().unwrap();
+    async fn fetch(tags: Vec<String>) -> Result<(i64, Recipe), NomError> {
+        let id = random_recipe(tags)
+            .await?
+            .ok_or(NomError::not_found("recipe with these tags"))?;
+        let recipe = get_recipe(id).await?;
 
-        (id, recipe)
+        Ok((id, recipe))
     }
 
+    let tags = parse_tags_param(use_query_map().read_untracked().get("tags"));
+
     view! {
-        <Await future=fetch() let:id_recipe>
-            <RecipeComponent id={id_recipe.0} recipe={id_recipe.1.clone()} with_mod=false/>
+        <Await future=fetch(tags) let:id_recipe>
+            {match id_recipe.clone() {
+                Ok((id, recipe)) => view! { <RecipeComponent id recipe with_mod=false/> }.into_any(),
+                Err(NomError::NotFound(_)) => view! { <h2>"Geen recepten gevonden"</h2> }.into_any(),
+                Err(error) => view! { <ErrorMessage error/> }.into_any(),
+            }}
         </Await>
     }
 }
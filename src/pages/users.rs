@@ -0,0 +1,191 @@
+use leptos::ev::SubmitEvent;
+use leptos::html;
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+
+use crate::auth::{
+    CurrentUser, Role, User, add_user, delete_user, list_users, set_password, set_role,
+};
+
+/// Asks for a new password and sets it, telling whether it worked.
+pub(super) fn change_password(user_id: i64, username: String) {
+    let window = web_sys::window().unwrap();
+
+    let Some(password) = window
+        .prompt_with_message(&format!("Nieuw wachtwoord voor {username}"))
+        .unwrap()
+        .filter(|password| !password.is_empty())
+    else {
+        return;
+    };
+
+    spawn_local(async move {
+        let message = match set_password(user_id, password).await {
+            Ok(()) => "Wachtwoord gewijzigd".to_string(),
+            Err(err) => format!("Wachtwoord wijzigen mislukt: {err}"),
+        };
+
+        web_sys::window()
+            .unwrap()
+            .alert_with_message(&message)
+            .unwrap();
+    });
+}
+
+fn role_options(selected: Role) -> impl IntoView {
+    Role::ALL
+        .map(|role| {
+            view! {
+                <option value=role.as_str() selected=role == selected>{role.label()}</option>
+            }
+        })
+        .collect_view()
+}
+
+/// Managing everyone's accounts, for admins.
+#[component]
+pub fn UsersPage() -> impl IntoView {
+    let CurrentUser(current_user) = expect_context();
+
+    view! {
+        <h1>"Gebruikers"</h1>
+        <Suspense fallback=move || view! { <p>"Gebruikers aan het laden..."</p> }>
+            {move || current_user.get().map(|user| match user {
+                Some(user) if user.role == Role::Admin => view! {
+                    <ManageUsers current_user=user.id/>
+                }
+                .into_any(),
+                Some(_) => view! { <p>"Alleen beheerders kunnen gebruikers beheren."</p> }.into_any(),
+                // Nobody is logged in while there are no accounts
+                None => view! {
+                    <p>"Er zijn nog geen gebruikers, iedereen kan alles. Maak een beheerder aan om in te loggen."</p>
+                    <ManageUsers current_user=0/>
+                }
+                .into_any(),
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn ManageUsers(current_user: i64) -> impl IntoView {
+    let users = Resource::new(|| (), async |_| list_users().await);
+
+    let username_elem: NodeRef<html::Input> = NodeRef::new();
+    let password_elem: NodeRef<html::Input> = NodeRef::new();
+    let role_elem: NodeRef<html::Select> = NodeRef::new();
+    let (add_error, set_add_error) = signal(None::<String>);
+    let (change_error, set_change_error) = signal(None::<String>);
+
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+
+        let username = username_elem.get().unwrap().value();
+        let password = password_elem.get().unwrap().value();
+        let Ok(role) = role_elem.get().unwrap().value().parse::<Role>() else {
+            return;
+        };
+
+        spawn_local(async move {
+            match add_user(username, password, role).await {
+                Ok(_) => {
+                    set_add_error.set(None);
+                    username_elem.get().unwrap().set_value("");
+                    password_elem.get().unwrap().set_value("");
+                    users.refetch();
+                }
+                Err(err) => set_add_error.set(Some(err.to_string())),
+            }
+        });
+    };
+
+    view! {
+        <Transition fallback=move || view! { <p>"Gebruikers aan het laden..."</p> }>
+            {move || users.get().map(|list| {
+                let list = match list {
+                    Ok(list) => list,
+                    Err(err) => {
+                        return view! { <p>"Gebruikers laden mislukt: " {err.to_string()}</p> }
+                            .into_any();
+                    }
+                };
+
+                view! {
+                    <ul class="user-list">
+                        {list.into_iter().map(|user| {
+                            let User { id, username, role } = user;
+
+                            let change_role = move |ev| {
+                                let Ok(role) = event_target_value(&ev).parse::<Role>() else {
+                                    return;
+                                };
+
+                                spawn_local(async move {
+                                    match set_role(id, role).await {
+                                        Ok(()) => set_change_error.set(None),
+                                        Err(err) => set_change_error.set(Some(err.to_string())),
+                                    }
+                                    users.refetch();
+                                });
+                            };
+
+                            let delete = {
+                                let username = username.clone();
+
+                                move |_| {
+                                    if !web_sys::window()
+                                        .unwrap()
+                                        .confirm_with_message(&format!("Weet je zeker dat je {username} wilt verwijderen?"))
+                                        .unwrap()
+                                    {
+                                        return;
+                                    }
+
+                                    spawn_local(async move {
+                                        match delete_user(id).await {
+                                            Ok(()) => set_change_error.set(None),
+                                            Err(err) => set_change_error.set(Some(err.to_string())),
+                                        }
+                                        users.refetch();
+                                    });
+                                }
+                            };
+
+                            let reset = {
+                                let username = username.clone();
+
+                                move |_| change_password(id, username.clone())
+                            };
+
+                            view! {
+                                <li>
+                                    {username}
+                                    <select on:change=change_role disabled=id == current_user>
+                                        {role_options(role)}
+                                    </select>
+                                    <button class:link-button on:click=reset>"Wachtwoord"</button>
+                                    {(id != current_user).then(|| view! {
+                                        <button class:link-button class:button-negative on:click=delete>"Verwijder"</button>
+                                    })}
+                                </li>
+                            }
+                        }).collect_view()}
+                    </ul>
+                }
+                .into_any()
+            })}
+        </Transition>
+        {move || change_error.get().map(|err| view! { <p>"Wijzigen mislukt: " {err}</p> })}
+        <form on:submit=on_submit>
+            <h3>"Nieuwe gebruiker"</h3>
+            <input type="text" placeholder="Gebruikersnaam" autocomplete="off" required node_ref=username_elem/>
+            <br/>
+            <input type="password" placeholder="Wachtwoord" autocomplete="new-password" required node_ref=password_elem/>
+            <br/>
+            <select node_ref=role_elem>{role_options(Role::Editor)}</select>
+            <br/>
+            <input class="link-button button-positive" type="submit" value="Voeg toe"/>
+        </form>
+        {move || add_error.get().map(|err| view! { <p>"Toevoegen mislukt: " {err}</p> })}
+    }
+}
@@ -0,0 +1,560 @@
+//! Recipes as PDF, to print for people who don't use the site. Uses the
+//! standard Helvetica fonts every PDF reader has, so nothing is embedded.
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use pdf_writer::types::{ActionType, AnnotationType};
+use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str, TextStr};
+use serde::Deserialize;
+
+use crate::db::Pool;
+use crate::recipe::{Recipe, load_recipe};
+
+/// A4, in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const TEXT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+
+/// Room for the list marker before ingredients
+const BULLET_INDENT: f32 = 14.0;
+
+/// Widths of the printable ASCII characters in Helvetica, from space to "~",
+/// in thousandths of the font size. From the font's metrics.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// The letters under the accented Latin-1 characters from 0xC0, which are
+/// about as wide.
+const LATIN1_BASES: &[u8; 64] = b"AAAAAAACEEEEIIIIDNOOOOO+OUUUUYPsaaaaaaaceeeeiiiidnooooo+ouuuuypy";
+
+/// Fractions the standard fonts lack, written out instead.
+const FRACTIONS: &[(char, &str)] = &[
+    ('⅓', "1/3"),
+    ('⅔', "2/3"),
+    ('⅕', "1/5"),
+    ('⅖', "2/5"),
+    ('⅗', "3/5"),
+    ('⅘', "4/5"),
+    ('⅙', "1/6"),
+    ('⅚', "5/6"),
+    ('⅛', "1/8"),
+    ('⅜', "3/8"),
+    ('⅝', "5/8"),
+    ('⅞', "7/8"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource_name(self) -> Name<'static> {
+        match self {
+            Font::Regular => Name(b"F1"),
+            Font::Bold => Name(b"F2"),
+        }
+    }
+
+    /// Width of an encoded character, in thousandths of the font size.
+    fn char_width(self, c: u8) -> u16 {
+        let widths = match self {
+            Font::Regular => &HELVETICA_WIDTHS,
+            Font::Bold => &HELVETICA_BOLD_WIDTHS,
+        };
+
+        match c {
+            b' '..=b'~' => widths[(c - b' ') as usize],
+            0xC0..=0xFF => self.char_width(LATIN1_BASES[(c - 0xC0) as usize]),
+            0x85 | 0x97 => 1000,
+            0xBC..=0xBE => 834,
+            0xB0 => 400,
+            0x95 => 350,
+            0x91 | 0x92 | 0xA0 | 0xB7 => 278,
+            _ => 556,
+        }
+    }
+
+    fn width(self, text: &[u8], size: f32) -> f32 {
+        text.iter().map(|c| self.char_width(*c) as f32).sum::<f32>() * size / 1000.0
+    }
+}
+
+/// Encodes text for the standard fonts, which use WinAnsiEncoding. Unknown
+/// characters become "?".
+fn encode(text: &str) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(text.len());
+
+    for c in text.chars() {
+        let byte = match c {
+            ' '..='~' => c as u8,
+            '\u{A0}'..='\u{FF}' => c as u32 as u8,
+            '\t' => b' ',
+            '€' => 0x80,
+            '‚' => 0x82,
+            '„' => 0x84,
+            '…' => 0x85,
+            'Œ' => 0x8C,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '™' => 0x99,
+            'œ' => 0x9C,
+            c => {
+                if let Some((_, written)) = FRACTIONS.iter().find(|(f, _)| *f == c) {
+                    // "1⅓" would read as "11/3"
+                    if encoded.last().is_some_and(u8::is_ascii_digit) {
+                        encoded.push(b' ');
+                    }
+                    encoded.extend_from_slice(written.as_bytes());
+                    continue;
+                }
+
+                b'?'
+            }
+        };
+
+        encoded.push(byte);
+    }
+
+    encoded
+}
+
+/// Breaks encoded text into lines no wider than `width`. Words longer than a
+/// line are left to stick out.
+fn wrap(text: &[u8], font: Font, size: f32, width: f32) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    let mut line: Vec<u8> = Vec::new();
+
+    for word in text.split(|c| *c == b' ').filter(|word| !word.is_empty()) {
+        if !line.is_empty() {
+            let mut longer = line.clone();
+            longer.push(b' ');
+            longer.extend_from_slice(word);
+
+            if font.width(&longer, size) <= width {
+                line = longer;
+                continue;
+            }
+
+            lines.push(std::mem::take(&mut line));
+        }
+
+        line.extend_from_slice(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+struct TextRun {
+    x: f32,
+    y: f32,
+    font: Font,
+    size: f32,
+    gray: f32,
+    text: Vec<u8>,
+}
+
+/// A clickable area going to another page.
+struct Link {
+    rect: Rect,
+    page: usize,
+}
+
+#[derive(Default)]
+struct Page {
+    runs: Vec<TextRun>,
+    links: Vec<Link>,
+}
+
+/// Lays out text top to bottom, starting new pages as they fill up.
+struct Layout {
+    pages: Vec<Page>,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Layout {
+            pages: Vec::new(),
+            y: 0.0,
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page::default());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Starts a new page if less than `height` is left, so headings don't
+    /// end up alone at the bottom of a page.
+    fn keep(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    /// Moves down a line of `height`.
+    fn advance(&mut self, height: f32) {
+        self.keep(height);
+        self.y -= height;
+    }
+
+    fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn text(&mut self, x: f32, font: Font, size: f32, gray: f32, text: Vec<u8>) {
+        let y = self.y;
+
+        self.pages.last_mut().unwrap().runs.push(TextRun {
+            x,
+            y,
+            font,
+            size,
+            gray,
+            text,
+        });
+    }
+
+    /// Writes a wrapped paragraph, optionally with a marker before its first
+    /// line and the rest indented to line up.
+    fn paragraph(&mut self, text: &str, font: Font, size: f32, gray: f32, bullet: Option<&str>) {
+        let indent = if bullet.is_some() { BULLET_INDENT } else { 0.0 };
+
+        for (i, line) in wrap(&encode(text), font, size, TEXT_WIDTH - indent)
+            .into_iter()
+            .enumerate()
+        {
+            self.advance(size * 1.4);
+
+            if i == 0
+                && let Some(bullet) = bullet
+            {
+                self.text(MARGIN, font, size, gray, encode(bullet));
+            }
+
+            self.text(MARGIN + indent, font, size, gray, line);
+        }
+    }
+
+    fn heading(&mut self, text: &str, size: f32) {
+        self.space(size * 0.6);
+        // The heading and the first two lines below it
+        self.keep(size * 1.4 + 2.0 * 11.0 * 1.4);
+        self.paragraph(text, Font::Bold, size, 0.0, None);
+        self.space(size * 0.2);
+    }
+
+    fn recipe(&mut self, recipe: &Recipe) {
+        self.new_page();
+        self.paragraph(&recipe.title, Font::Bold, 22.0, 0.0, None);
+
+        let details = recipe
+            .servings
+            .map(|servings| format!("Voor {servings} personen"))
+            .into_iter()
+            .chain(recipe.times())
+            .collect::<Vec<_>>();
+
+        if !details.is_empty() {
+            self.paragraph(&details.join(" · "), Font::Regular, 11.0, 0.4, None);
+        }
+
+        if !recipe.tags.is_empty() {
+            self.paragraph(&recipe.tags.join(", "), Font::Regular, 11.0, 0.4, None);
+        }
+
+        if !recipe.ingredients.is_empty() {
+            self.heading("Ingrediënten", 14.0);
+
+            for ingredient in &recipe.ingredients {
+                self.paragraph(&ingredient.to_string(), Font::Regular, 11.0, 0.0, Some("•"));
+            }
+        }
+
+        let instructions = recipe
+            .instructions
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+
+        self.heading("Bereiding", 14.0);
+
+        for line in instructions {
+            self.paragraph(line, Font::Regular, 11.0, 0.0, None);
+            self.space(4.0);
+        }
+    }
+
+    /// The table of contents, with each recipe's page number and a link to
+    /// it. `first_pages` are the pages the recipes start on.
+    fn table_of_contents(&mut self, recipes: &[Recipe], first_pages: &[usize]) {
+        self.new_page();
+        self.paragraph("Kookboek", Font::Bold, 26.0, 0.0, None);
+        self.heading("Inhoud", 14.0);
+
+        let size = 12.0;
+        let number_width = 40.0;
+
+        for (recipe, page) in recipes.iter().zip(first_pages) {
+            self.advance(size * 1.6);
+
+            let number = encode(&(page + 1).to_string());
+            let title = truncate(
+                encode(&recipe.title),
+                Font::Regular,
+                size,
+                TEXT_WIDTH - number_width,
+            );
+
+            let number_x = MARGIN + TEXT_WIDTH - Font::Regular.width(&number, size);
+
+            self.text(MARGIN, Font::Regular, size, 0.0, title);
+            self.text(number_x, Font::Regular, size, 0.0, number);
+
+            let y = self.y;
+            self.pages.last_mut().unwrap().links.push(Link {
+                rect: Rect::new(MARGIN, y - 4.0, MARGIN + TEXT_WIDTH, y + size),
+                page: *page,
+            });
+        }
+    }
+}
+
+/// Shortens encoded text with an ellipsis to fit `width`.
+fn truncate(mut text: Vec<u8>, font: Font, size: f32, width: f32) -> Vec<u8> {
+    if font.width(&text, size) <= width {
+        return text;
+    }
+
+    let ellipsis = font.char_width(0x85) as f32 * size / 1000.0;
+
+    while !text.is_empty() && font.width(&text, size) + ellipsis > width {
+        text.pop();
+    }
+
+    text.push(0x85);
+    text
+}
+
+/// Renders recipes, each starting on a new page. More than one recipe makes
+/// a cookbook, which starts with a table of contents.
+pub fn render(recipes: &[Recipe]) -> Vec<u8> {
+    let mut body = Layout::new();
+    let mut first_pages = Vec::with_capacity(recipes.len());
+
+    for recipe in recipes {
+        first_pages.push(body.pages.len());
+        body.recipe(recipe);
+    }
+
+    let mut pages = if recipes.len() > 1 {
+        // The table of contents takes as many pages whatever the page
+
+        let mut counting = Layout::new();
+        counting.table_of_contents(recipes, &first_pages);
+        let offset = counting.pages.len();
+
+        let first_pages = first_pages
+            .iter()
+            .map(|page| page + offset)
+            .collect::<Vec<_>>();
+
+        let mut contents = Layout::new();
+        contents.table_of_contents(recipes, &first_pages);
+
+        contents.pages
+    } else {
+        Vec::new()
+    };
+
+    pages.extend(body.pages);
+
+    let title = match recipes {
+        [recipe] => recipe.title.as_str(),
+        _ => "Kookboek",
+    };
+
+    write(&pages, title)
+}
+
+fn write(pages: &[Page], title: &str) -> Vec<u8> {
+    let mut next_id = Ref::new(1);
+    let catalog_id = next_id.bump();
+    let tree_id = next_id.bump();
+    let regular_id = next_id.bump();
+    let bold_id = next_id.bump();
+    let info_id = next_id.bump();
+    let page_ids = pages.iter().map(|_| next_id.bump()).collect::<Vec<_>>();
+
+    let mut pdf = Pdf::new();
+
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.pages(tree_id)
+        .kids(page_ids.iter().copied())
+        .count(pages.len() as i32);
+    pdf.document_info(info_id)
+        .title(TextStr(title))
+        .creator(TextStr("nom"));
+
+    pdf.type1_font(regular_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (number, (page, page_id)) in pages.iter().zip(&page_ids).enumerate() {
+        let content_id = next_id.bump();
+
+        let mut content = Content::new();
+
+        for run in &page.runs {
+            content.set_fill_gray(run.gray);
+            content
+                .begin_text()
+                .set_font(run.font.resource_name(), run.size)
+                .next_line(run.x, run.y)
+                .show(Str(&run.text))
+                .end_text();
+        }
+
+        let page_number = encode(&(number + 1).to_string());
+        let page_number_x = (PAGE_WIDTH - Font::Regular.width(&page_number, 9.0)) / 2.0;
+
+        content.set_fill_gray(0.4);
+        content
+            .begin_text()
+            .set_font(Font::Regular.resource_name(), 9.0)
+            .next_line(page_number_x, MARGIN / 2.0)
+            .show(Str(&page_number))
+            .end_text();
+
+        pdf.stream(content_id, &content.finish());
+
+        let mut writer = pdf.page(*page_id);
+        writer
+            .parent(tree_id)
+            .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .contents(content_id);
+        writer
+            .resources()
+            .fonts()
+            .pair(Font::Regular.resource_name(), regular_id)
+            .pair(Font::Bold.resource_name(), bold_id);
+
+        if !page.links.is_empty() {
+            let mut annotations = writer.annotations();
+
+            for link in &page.links {
+                annotations
+                    .push()
+                    .subtype(AnnotationType::Link)
+                    .rect(link.rect)
+                    .border(0.0, 0.0, 0.0, None)
+                    .action()
+                    .action_type(ActionType::GoTo)
+                    .destination()
+                    .page(page_ids[link.page])
+                    .xyz(0.0, PAGE_HEIGHT, None);
+            }
+        }
+    }
+
+    pdf.finish()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PdfQuery {
+    /// Comma separated recipe ids
+    ids: String,
+}
+
+/// Serves `/recipes.pdf?ids=3,1,2`, the recipes in that order.
+pub async fn serve_pdf(State(db): State<Pool>, Query(query): Query<PdfQuery>) -> Response {
+    let Ok(ids) = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::parse::<i64>)
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    if ids.is_empty() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let recipes = {
+        let ids = ids.clone();
+
+        db.read(move |db| {
+            ids.iter()
+                .map(|id| load_recipe(db, *id))
+                .collect::<rusqlite::Result<Option<Vec<_>>>>()
+        })
+        .await
+    };
+
+    let recipes = match recipes {
+        Ok(Some(recipes)) => recipes,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            leptos::logging::error!("Could not load recipes {ids:?}: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let file_name = match recipes.as_slice() {
+        [recipe] => recipe.title.clone(),
+        _ => "Kookboek".to_string(),
+    };
+
+    let pdf = match tokio::task::spawn_blocking(move || render(&recipes)).await {
+        Ok(pdf) => pdf,
+        Err(err) => {
+            leptos::logging::error!("Could not render PDF: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    (
+        [
+            (CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                crate::attachment_disposition(&file_name, "pdf"),
+            ),
+        ],
+        pdf,
+    )
+        .into_response()
+}
@@ -0,0 +1,329 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::NomError;
+
+#[cfg(feature = "ssr")]
+use crate::tags::{has_all_tags_sql, tags_json};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MealSlot {
+    Ontbijt,
+    Lunch,
+    Diner,
+}
+
+impl MealSlot {
+    /// All slots, in the order they are eaten.
+    pub const ALL: [MealSlot; 3] = [MealSlot::Ontbijt, MealSlot::Lunch, MealSlot::Diner];
+
+    /// The name as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MealSlot::Ontbijt => "ontbijt",
+            MealSlot::Lunch => "lunch",
+            MealSlot::Diner => "diner",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MealSlot::Ontbijt => "Ontbijt",
+            MealSlot::Lunch => "Lunch",
+            MealSlot::Diner => "Diner",
+        }
+    }
+}
+
+impl fmt::Display for MealSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MealSlot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MealSlot::ALL
+            .into_iter()
+            .find(|slot| slot.as_str() == s)
+            .ok_or_else(|| format!("Unknown meal slot {s}"))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl rusqlite::types::ToSql for MealSlot {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl rusqlite::types::FromSql for MealSlot {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|_| rusqlite::types::FromSqlError::InvalidType)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedMeal {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub slot: MealSlot,
+    pub recipe_id: i64,
+    pub title: String,
+    pub servings: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeekPlan {
+    /// The monday of the week
+    pub start: NaiveDate,
+    pub meals: Vec<PlannedMeal>,
+}
+
+impl WeekPlan {
+    pub fn meals_at(&self, date: NaiveDate, slot: MealSlot) -> Vec<PlannedMeal> {
+        self.meals
+            .iter()
+            .filter(|meal| meal.date == date && meal.slot == slot)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The monday of the week `date` is in.
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date.week(Weekday::Mon).first_day()
+}
+
+/// The seven days of the week starting at `start`.
+pub fn week_days(start: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    start.iter_days().take(7)
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "maandag",
+    "dinsdag",
+    "woensdag",
+    "donderdag",
+    "vrijdag",
+    "zaterdag",
+    "zondag",
+];
+
+const MONTHS: [&str; 12] = [
+    "januari",
+    "februari",
+    "maart",
+    "april",
+    "mei",
+    "juni",
+    "juli",
+    "augustus",
+    "september",
+    "oktober",
+    "november",
+    "december",
+];
+
+/// Formats a date like "maandag 12 oktober".
+pub fn format_day(date: NaiveDate) -> String {
+    format!(
+        "{} {} {}",
+        WEEKDAYS[date.weekday().num_days_from_monday() as usize],
+        date.day(),
+        MONTHS[date.month0() as usize]
+    )
+}
+
+/// The meals planned in the week containing `week`, or the current week.
+#[server]
+pub async fn get_week_plan(
+    #[server(default)] week: Option<NaiveDate>,
+) -> Result<WeekPlan, NomError> {
+    use crate::db::use_db;
+
+    let start = week_start(week.unwrap_or_else(|| chrono::Local::now().date_naive()));
+    let end = start + chrono::Days::new(7);
+
+    use_db().read(move |db| {
+        let mut meals_stmt = db.prepare_cached(
+            "
+            SELECT meal_plan.id, meal_plan.date, meal_plan.slot, meal_plan.recipe, recipes.title, meal_plan.servings
+            FROM meal_plan JOIN recipes ON recipes.id = meal_plan.recipe
+            WHERE meal_plan.date >= ?1 AND meal_plan.date < ?2
+            ORDER BY meal_plan.date, meal_plan.id;
+            ",
+        )?;
+
+        let meals = meals_stmt
+            .query_map((start, end), |row| {
+                Ok(PlannedMeal {
+                    id: row.get(0)?,
+                    date: row.get(1)?,
+                    slot: row.get(2)?,
+                    recipe_id: row.get(3)?,
+                    title: row.get(4)?,
+                    servings: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(WeekPlan { start, meals })
+    })
+    .await
+}
+
+/// Plans a recipe. Without `servings` the servings of the recipe are used.
+#[server]
+pub async fn add_meal(
+    date: NaiveDate,
+    slot: MealSlot,
+    recipe_id: i64,
+    #[server(default)] servings: Option<u32>,
+) -> Result<i64, NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        let inserted = db.execute(
+            "
+            INSERT INTO meal_plan (date, slot, recipe, servings)
+            SELECT ?1, ?2, id, coalesce(?4, servings) FROM recipes WHERE id = ?3;
+            ",
+            (date, slot, recipe_id, servings),
+        )?;
+
+        if inserted == 0 {
+            return Err(NomError::unknown_recipe(recipe_id));
+        }
+
+        Ok(db.last_insert_rowid())
+    })
+    .await
+}
+
+#[server]
+pub async fn move_meal(meal_id: i64, date: NaiveDate, slot: MealSlot) -> Result<(), NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        let updated = db.execute(
+            "UPDATE meal_plan SET date = ?1, slot = ?2 WHERE id = ?3;",
+            (date, slot, meal_id),
+        )?;
+
+        if updated == 0 {
+            return Err(NomError::not_found(format!("meal {meal_id}")));
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[server]
+pub async fn remove_meal(meal_id: i64) -> Result<(), NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        db.execute("DELETE FROM meal_plan WHERE id = (?1);", (meal_id,))?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Fills the empty `slot`s of the week containing `week` with random recipes
+/// having every one of `tags`. Returns the number of meals planned.
+#[server]
+pub async fn autofill_week(
+    week: NaiveDate,
+    slot: MealSlot,
+    #[server(default)] tags: Vec<String>,
+) -> Result<usize, NomError> {
+    use std::collections::HashSet;
+
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let start = week_start(week);
+    let end = start + chrono::Days::new(7);
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        let transaction = db.transaction()?;
+
+        let filled_days = transaction
+            .prepare_cached(
+                "SELECT date FROM meal_plan WHERE slot = ?1 AND date >= ?2 AND date < ?3;",
+            )?
+            .query_map((slot, start, end), |row| row.get(0))?
+            .collect::<Result<HashSet<NaiveDate>, _>>()?;
+
+        let planned_recipes = transaction
+            .prepare_cached("SELECT recipe FROM meal_plan WHERE date >= ?1 AND date < ?2;")?
+            .query_map((start, end), |row| row.get(0))?
+            .collect::<Result<HashSet<i64>, _>>()?;
+
+        let candidates = transaction
+            .prepare_cached(&format!(
+                "SELECT id, servings FROM recipes WHERE {} ORDER BY RANDOM();",
+                has_all_tags_sql(1)
+            ))?
+            .query_map((tags_json(&tags),), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<u32>>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Prefer recipes that aren't planned this week yet, only repeating
+        // recipes when there are too few of them
+        let (fresh, repeats): (Vec<_>, Vec<_>) = candidates
+            .into_iter()
+            .partition(|(id, _)| !planned_recipes.contains(id));
+        let picks: Vec<_> = fresh.into_iter().chain(repeats).collect();
+
+        let empty_days = week_days(start).filter(|day| !filled_days.contains(day));
+
+        let mut num_planned = 0;
+
+        {
+            let mut insert_stmt = transaction.prepare_cached(
+                "INSERT INTO meal_plan (date, slot, recipe, servings) VALUES (?1, ?2, ?3, ?4);",
+            )?;
+
+            for (day, (recipe_id, servings)) in empty_days.zip(picks.iter().cycle()) {
+                insert_stmt.execute((day, slot, recipe_id, servings))?;
+                num_planned += 1;
+            }
+        }
+
+        transaction.commit()?;
+
+        Ok(num_planned)
+    })
+    .await
+}
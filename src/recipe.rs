@@ -1,253 +1,1381 @@
+use std::fmt;
+
 use leptos::prelude::*;
 use leptos_router::components::A;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "ssr")]
+use crate::auth::require_role;
+use crate::auth::{RequireRole, Role};
+use crate::error::NomError;
+use crate::images::RecipeImages;
+use crate::share::ShareLinks;
+use crate::shopping::AddToListButton;
+use crate::tags::TagChips;
+#[cfg(feature = "ssr")]
+use crate::tags::{has_all_tags_sql, recipe_tags, set_recipe_tags, tags_json};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RawRecipe {
     pub title: String,
+    pub servings: Option<u32>,
+    /// In minutes
+    pub prep_time: Option<u32>,
+    /// In minutes
+    pub cook_time: Option<u32>,
     pub ingredients: String,
     pub instructions: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl RawRecipe {
+    /// Parses the pasted ingredient lines, skipping empty ones.
+    pub fn parse_ingredients(&self) -> Vec<Ingredient> {
+        self.ingredients
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Ingredient::parse)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct Recipe {
     pub title: String,
-    pub ingredients: Vec<String>,
+    pub servings: Option<u32>,
+    /// In minutes
+    pub prep_time: Option<u32>,
+    /// In minutes
+    pub cook_time: Option<u32>,
+    pub ingredients: Vec<Ingredient>,
     pub instructions: String,
+    pub tags: Vec<String>,
+    /// Ids of the images, the first one being the main image
+    pub images: Vec<i64>,
+    /// Goes up with every change, so edits made at the same time can be
+    /// noticed, see [`update_recipe`]. 0 for recipes that aren't stored.
+    pub version: u32,
 }
 
-#[component]
-pub fn RecipeComponent(id: i64, recipe: Recipe, with_mod: bool) -> impl IntoView {
-    let ingredients = recipe
-        .ingredients
+impl Recipe {
+    /// The known times, as "Voorbereiding: 15 min".
+    pub fn times(&self) -> Vec<String> {
+        [
+            ("Voorbereiding", self.prep_time),
+            ("Bereiding", self.cook_time),
+        ]
+        .into_iter()
+        .filter_map(|(label, time)| time.map(|time| format!("{label}: {}", format_minutes(time))))
+        .collect()
+    }
+}
+
+/// Parses the ingredients of a submitted recipe. The recipe has no images yet.
+impl From<RawRecipe> for Recipe {
+    fn from(raw: RawRecipe) -> Self {
+        Recipe {
+            ingredients: raw.parse_ingredients(),
+            title: raw.title,
+            servings: raw.servings,
+            prep_time: raw.prep_time,
+            cook_time: raw.cook_time,
+            instructions: raw.instructions,
+            tags: raw.tags,
+            images: Vec::new(),
+            version: 0,
+        }
+    }
+}
+
+/// Writes the ingredients back as lines, the way they are edited, see
+/// [`Ingredient::to_line`]. Converting back gives the same recipe, unless an
+/// ingredient has a comma in its name: the lines can't hold those, so what
+/// comes after the comma becomes the note.
+impl From<Recipe> for RawRecipe {
+    fn from(recipe: Recipe) -> Self {
+        RawRecipe {
+            ingredients: recipe
+                .ingredients
+                .iter()
+                .map(Ingredient::to_line)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            title: recipe.title,
+            servings: recipe.servings,
+            prep_time: recipe.prep_time,
+            cook_time: recipe.cook_time,
+            instructions: recipe.instructions,
+            tags: recipe.tags,
+        }
+    }
+}
+
+/// A single ingredient, e.g. "200 g bloem, gezeefd".
+///
+/// Lines that can't be parsed end up verbatim in `name`, without a quantity,
+/// unit or note.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct Ingredient {
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub name: String,
+    pub note: Option<String>,
+}
+
+/// Units recognised directly after a quantity. Matched case-insensitively and
+/// without a trailing period, so "El." is a unit as well.
+const UNITS: &[&str] = &[
+    "g",
+    "gr",
+    "gram",
+    "kg",
+    "kilo",
+    "kilogram",
+    "mg",
+    "ml",
+    "cl",
+    "dl",
+    "l",
+    "liter",
+    "el",
+    "eetlepel",
+    "eetlepels",
+    "tl",
+    "theelepel",
+    "theelepels",
+    "kl",
+    "koffielepel",
+    "koffielepels",
+    "snuf",
+    "snufje",
+    "snufjes",
+    "mespunt",
+    "mespuntje",
+    "mespuntjes",
+    "teen",
+    "teentje",
+    "teentjes",
+    "tenen",
+    "blik",
+    "blikje",
+    "blikjes",
+    "blikken",
+    "pak",
+    "pakje",
+    "pakjes",
+    "pakken",
+    "zak",
+    "zakje",
+    "zakjes",
+    "bos",
+    "bosje",
+    "bosjes",
+    "takje",
+    "takjes",
+    "plak",
+    "plakje",
+    "plakjes",
+    "plakken",
+    "kop",
+    "kopje",
+    "kopjes",
+    "cup",
+    "cups",
+    "stuk",
+    "stuks",
+    "stukje",
+    "stukjes",
+    "scheut",
+    "scheutje",
+    "handje",
+    "handvol",
+    "pot",
+    "potje",
+    "fles",
+    "tbsp",
+    "tsp",
+    "oz",
+    "lb",
+];
+
+/// Unicode vulgar fractions and their values.
+const FRACTIONS: &[(char, f64)] = &[
+    ('½', 1.0 / 2.0),
+    ('⅓', 1.0 / 3.0),
+    ('⅔', 2.0 / 3.0),
+    ('¼', 1.0 / 4.0),
+    ('¾', 3.0 / 4.0),
+    ('⅕', 1.0 / 5.0),
+    ('⅖', 2.0 / 5.0),
+    ('⅗', 3.0 / 5.0),
+    ('⅘', 4.0 / 5.0),
+    ('⅙', 1.0 / 6.0),
+    ('⅚', 5.0 / 6.0),
+    ('⅛', 1.0 / 8.0),
+    ('⅜', 3.0 / 8.0),
+    ('⅝', 5.0 / 8.0),
+    ('⅞', 7.0 / 8.0),
+];
+
+impl Ingredient {
+    /// Parses a single ingredient line. Never fails: anything that doesn't
+    /// look like "[quantity] [unit] name[, note]" is kept as the name.
+    pub fn parse(line: &str) -> Self {
+        let line = line.trim();
+
+        let fallback = || Ingredient {
+            quantity: None,
+            unit: None,
+            name: line.to_string(),
+            note: None,
+        };
+
+        let (quantity, rest) = match parse_quantity(line) {
+            Ok(Some((quantity, rest))) => (Some(quantity), rest),
+            Ok(None) => (None, line),
+            Err(()) => return fallback(),
+        };
+
+        let (unit, rest) = match quantity {
+            Some(_) => parse_unit(rest),
+            None => (None, rest),
+        };
+
+        let (name, note) = match rest.split_once(',') {
+            Some((name, note)) => (name.trim(), Some(note.trim())),
+            None => (rest.trim(), None),
+        };
+
+        if name.is_empty() {
+            return fallback();
+        }
+
+        Ingredient {
+            quantity,
+            unit: unit.map(str::to_string),
+            name: name.to_string(),
+            note: note.filter(|n| !n.is_empty()).map(str::to_string),
+        }
+    }
+
+    /// The ingredient as a line to edit. Unlike [`fmt::Display`] it writes
+    /// the quantity exactly, so the line parses back to the same quantity.
+    pub fn to_line(&self) -> String {
+        let mut line = String::new();
+        self.write(&mut line, format_exact_quantity)
+            .expect("Writing to a string can't fail");
+
+        line
+    }
+
+    fn write(&self, f: &mut impl fmt::Write, format_quantity: fn(f64) -> String) -> fmt::Result {
+        if let Some(quantity) = self.quantity {
+            write!(f, "{} ", format_quantity(quantity))?;
+        }
+
+        if let Some(unit) = &self.unit {
+            write!(f, "{unit} ")?;
+        }
+
+        write!(f, "{}", self.name)?;
+
+        if let Some(note) = &self.note {
+            write!(f, ", {note}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns this ingredient with its quantity multiplied by `factor`.
+    /// Ingredients without a quantity ("peper en zout") are left as-is.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Ingredient {
+            quantity: self.quantity.map(|q| round_quantity(q * factor)),
+            ..self.clone()
+        }
+    }
+}
+
+/// Rounds a scaled quantity so it stays readable: "83,33 g" becomes "83 g",
+/// while small amounts keep their fractions.
+fn round_quantity(quantity: f64) -> f64 {
+    if quantity >= 10.0 {
+        quantity.round()
+    } else {
+        quantity
+    }
+}
+
+/// The ingredient as shown in a recipe, with its quantity rounded to be
+/// readable.
+impl fmt::Display for Ingredient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, format_quantity)
+    }
+}
+
+/// Parses a leading quantity such as "200", "0,5", "1/2", "½", "1½" or
+/// "1 1/2", returning it with the remaining text.
+///
+/// Returns `Ok(None)` if the text doesn't start with a quantity, and `Err` if
+/// it does but we can't make sense of it (ranges like "2-3", "1/0").
+pub(crate) fn parse_quantity(text: &str) -> Result<Option<(f64, &str)>, ()> {
+    let (whole, rest) = match parse_number(text)? {
+        Some(parsed) => parsed,
+        None => return parse_fraction(text),
+    };
+
+    if rest.starts_with(['-', '–']) {
+        return Err(());
+    }
+
+    // A fraction directly after the number ("1½") or separated by a space
+    // ("1 1/2") makes it a mixed number
+    if let Some((frac, rest)) = parse_fraction(rest)? {
+        return Ok(Some((whole + frac, rest)));
+    }
+
+    if let Some((frac, rest)) = parse_fraction(rest.trim_start())? {
+        return Ok(Some((whole + frac, rest)));
+    }
+
+    Ok(Some((whole, rest)))
+}
+
+/// Parses a leading decimal number, accepting both "." and "," as separator.
+/// A number directly followed by "/" is left to [`parse_fraction`].
+fn parse_number(text: &str) -> Result<Option<(f64, &str)>, ()> {
+    let digits_end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+
+    if digits_end == 0 || text[digits_end..].starts_with(['/', '⁄']) {
+        return Ok(None);
+    }
+
+    let mut end = digits_end;
+
+    if let Some(after_sep) = text[digits_end..].strip_prefix(['.', ',']) {
+        let decimals = after_sep
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after_sep.len());
+
+        if decimals > 0 {
+            end = digits_end + 1 + decimals;
+        }
+    }
+
+    let number = text[..end].replace(',', ".").parse().map_err(|_| ())?;
+
+    Ok(Some((number, &text[end..])))
+}
+
+/// Parses a leading fraction, either a vulgar fraction ("½") or a
+/// numerator/denominator pair ("1/2").
+fn parse_fraction(text: &str) -> Result<Option<(f64, &str)>, ()> {
+    if let Some(first) = text.chars().next()
+        && let Some((_, value)) = FRACTIONS.iter().find(|(c, _)| *c == first)
+    {
+        return Ok(Some((*value, &text[first.len_utf8()..])));
+    }
+
+    let numerator_end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+
+    let Some(after_slash) = text[numerator_end..].strip_prefix(['/', '⁄']) else {
+        return Ok(None);
+    };
+
+    if numerator_end == 0 {
+        return Ok(None);
+    }
+
+    let denominator_end = after_slash
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(after_slash.len());
+
+    let numerator: f64 = text[..numerator_end].parse().map_err(|_| ())?;
+    let denominator: f64 = after_slash[..denominator_end].parse().map_err(|_| ())?;
+
+    if denominator == 0.0 {
+        return Err(());
+    }
+
+    Ok(Some((
+        numerator / denominator,
+        &after_slash[denominator_end..],
+    )))
+}
+
+/// Splits a known unit off the start of `text`. The unit may directly follow
+/// the quantity, as in "200g".
+fn parse_unit(text: &str) -> (Option<&str>, &str) {
+    let text = text.trim_start();
+    let word_end = text.find(char::is_whitespace).unwrap_or(text.len());
+    let word = text[..word_end].trim_end_matches(',');
+
+    let is_unit = |word: &str| {
+        let word = word.strip_suffix('.').unwrap_or(word).to_lowercase();
+        UNITS.contains(&word.as_str())
+    };
+
+    if is_unit(word) {
+        (Some(word), &text[word.len()..])
+    } else {
+        (None, text)
+    }
+}
+
+/// Formats a quantity the way a recipe would write it: whole numbers as-is,
+/// common fractions as vulgar fractions and anything else with a decimal comma.
+pub fn format_quantity(quantity: f64) -> String {
+    let whole = quantity.trunc();
+    let frac = quantity - whole;
+
+    if frac.abs() < 0.01 {
+        return format!("{whole}");
+    }
+
+    if let Some((c, _)) = FRACTIONS
         .iter()
-        .map(|ingr| {
-            view! { <li>{ingr.clone()}</li>}
+        .find(|(_, value)| (frac - value).abs() < 0.01)
+    {
+        return if whole == 0.0 {
+            c.to_string()
+        } else {
+            format!("{whole}{c}")
+        };
+    }
+
+    format_decimal(quantity)
+}
+
+/// Formats a quantity without rounding: as vulgar fraction if it's one, up to
+/// floating point errors, and with as many decimals as needed otherwise.
+fn format_exact_quantity(quantity: f64) -> String {
+    let whole = quantity.trunc();
+    let frac = quantity - whole;
+
+    if frac != 0.0
+        && let Some((c, _)) = FRACTIONS
+            .iter()
+            .find(|(_, value)| (frac - value).abs() < 1e-9)
+    {
+        return if whole == 0.0 {
+            c.to_string()
+        } else {
+            format!("{whole}{c}")
+        };
+    }
+
+    quantity.to_string().replace('.', ",")
+}
+
+/// Formats a duration in minutes, e.g. "1 uur 30 min".
+pub fn format_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes} min"),
+        (hours, 0) => format!("{hours} uur"),
+        (hours, minutes) => format!("{hours} uur {minutes} min"),
+    }
+}
+
+/// Formats a quantity with at most two decimals and a decimal comma, for
+/// metric amounts like "1,25 kg" that read oddly as fractions.
+pub fn format_decimal(quantity: f64) -> String {
+    let formatted = format!("{quantity:.2}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .replace('.', ",")
+}
+
+/// Normalises an ingredient name for matching, so that "Tomaten", "tomaat"
+/// and "tomaatjes" all end up as the same words. Handles case, accents,
+/// Dutch plurals and diminutives and a few common spelling variants.
+pub fn normalize_ingredient(name: &str) -> Vec<String> {
+    let lowered: String = name
+        .to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '’'))
+        .map(strip_accent)
+        .collect();
+
+    lowered
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(normalize_word)
+        .collect()
+}
+
+fn strip_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ä' | 'ã' => 'a',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ò' | 'ó' | 'ô' | 'ö' | 'õ' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ç' => 'c',
+        'ñ' => 'n',
+        c => c,
+    }
+}
+
+fn normalize_word(word: &str) -> String {
+    // Plurals that don't follow any of the rules below
+    let word = match word {
+        "eieren" | "eitje" | "eitjes" => "ei",
+        word => word,
+    };
+
+    let mut word = spelling_variants(word);
+
+    for suffix in ["tjes", "tje", "jes", "je"] {
+        if let Some(stem) = word.strip_suffix(suffix)
+            && stem.len() >= 2
+        {
+            // "tomaatjes" is "tomaat" with "jes", not "toma" with "tjes"
+            word = if suffix.starts_with('t')
+                && ["aa", "ee", "oo", "uu"]
+                    .iter()
+                    .any(|end| stem.ends_with(end))
+            {
+                format!("{stem}t")
+            } else {
+                stem.to_string()
+            };
+            break;
+        }
+    }
+
+    // Singulars like "citroen" and "teen" only look like a plural
+    if let Some(stem) = word.strip_suffix("en")
+        && stem.len() >= 2
+        && !stem.ends_with(['o', 'e'])
+    {
+        // kazen -> kaas, druiven -> druif
+        word = match stem.strip_suffix('z') {
+            Some(rest) => format!("{rest}s"),
+            None => match stem.strip_suffix('v') {
+                Some(rest) => format!("{rest}f"),
+                None => stem.to_string(),
+            },
+        };
+    } else if let Some(stem) = word.strip_suffix('s')
+        && stem.len() >= 3
+        && !["aa", "ee", "oo", "uu", "s"]
+            .iter()
+            .any(|end| stem.ends_with(end))
+    {
+        word = stem.to_string();
+    }
+
+    collapse_doubles(&word)
+}
+
+/// Maps spelling variants onto one form: "ij"/"y", "c"/"k", "ph"/"f" and the
+/// like. Only used for comparing, so the result doesn't need to be pretty.
+fn spelling_variants(word: &str) -> String {
+    let word = word
+        .replace("ij", "ei")
+        .replace("ph", "f")
+        .replace("gh", "g")
+        .replace("qu", "kw")
+        .replace("th", "t")
+        .replace('x', "ks");
+
+    let chars: Vec<char> = word.chars().collect();
+    let mut out = String::with_capacity(word.len());
+
+    for (i, c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1).copied();
+
+        match c {
+            'y' if i == 0 => out.push('j'),
+            'y' => out.push('i'),
+            'c' if next == Some('h') => out.push('c'),
+            'c' if matches!(next, Some('e' | 'i')) => out.push('s'),
+            'c' => out.push('k'),
+            c => out.push(*c),
+        }
+    }
+
+    out
+}
+
+/// Collapses doubled letters, so "tomaat" and "tomat(en)" or "kipp(en)" and
+/// "kip" compare equal.
+fn collapse_doubles(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+
+    for c in word.chars() {
+        if !out.ends_with(c) {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+/// Compounds of words shorter than [`MIN_COMPOUND_PART`], which are found in
+/// too many unrelated words to match them anywhere: "ei" is in "prei" and,
+/// spelled as "ei", in "selderij".
+const SHORT_WORD_COMPOUNDS: &[&str] = &[
+    "bosui",
+    "lenteui",
+    "stengelui",
+    "zilverui",
+    "eendenei",
+    "kippenei",
+    "kwartelei",
+    "eidooier",
+    "eiwit",
+];
+
+/// How long both parts of a compound need to be at least, like "slag" and
+/// "room" in "slagroom".
+const MIN_COMPOUND_PART: usize = 3;
+
+/// Whether the normalised words of a recipe ingredient contain a normalised
+/// search term. Words may be part of a compound, so "kip" is found in
+/// "kipfilet" and "ui" in "bosui".
+pub fn ingredient_matches(ingredient: &[String], term: &[String]) -> bool {
+    !term.is_empty()
+        && term.iter().all(|term_word| {
+            ingredient
+                .iter()
+                .any(|word| word == term_word || is_compound_of(word, term_word))
         })
-        .collect_view();
+}
+
+/// Whether `word` is a compound starting or ending with `part`, both
+/// normalised.
+fn is_compound_of(word: &str, part: &str) -> bool {
+    if part.len() < MIN_COMPOUND_PART {
+        return SHORT_WORD_COMPOUNDS
+            .iter()
+            .any(|compound| normalize_word(compound) == word)
+            && (word.starts_with(part) || word.ends_with(part));
+    }
+
+    word.len() >= part.len() + MIN_COMPOUND_PART && (word.starts_with(part) || word.ends_with(part))
+}
+
+/// Renders a recipe. With `scalable`, recipes that know their number of
+/// servings get a control to rescale the ingredient quantities.
+#[component]
+pub fn RecipeComponent(
+    id: i64,
+    recipe: Recipe,
+    with_mod: bool,
+    #[prop(optional)] scalable: bool,
+    /// When shown through a share link
+    #[prop(optional)]
+    share_token: Option<String>,
+) -> impl IntoView {
+    let (servings, set_servings) = signal(recipe.servings);
+
+    let ingredients = {
+        let ingredients = recipe.ingredients.clone();
+        let original = recipe.servings;
+
+        move || {
+            let factor = original
+                .zip(servings.get())
+                .map_or(1.0, |(original, wanted)| wanted as f64 / original as f64);
+
+            ingredients
+                .iter()
+                .map(|ingr| {
+                    view! { <li>{ingr.scaled(factor).to_string()}</li>}
+                })
+                .collect_view()
+        }
+    };
+
+    let servings_view = match recipe.servings {
+        Some(_) if scalable => {
+            let change = move |delta: i64| {
+                set_servings.update(|s| {
+                    *s = s.map(|s| (s as i64 + delta).max(1) as u32);
+                })
+            };
+
+            view! {
+                <p class="recipe-servings">
+                    "Voor "
+                    <button class="servings-button" on:click=move |_| change(-1)>"-"</button>
+                    <input
+                        type="number"
+                        min="1"
+                        class="servings-input"
+                        prop:value=move || servings.get().unwrap_or(1)
+                        on:change=move |ev| {
+                            if let Ok(wanted) = event_target_value(&ev).parse::<u32>() {
+                                set_servings.set(Some(wanted.max(1)));
+                            }
+                        }
+                    />
+                    <button class="servings-button" on:click=move |_| change(1)>"+"</button>
+                    " personen"
+                </p>
+            }
+            .into_any()
+        }
+        Some(servings) => {
+            view! { <p class="recipe-servings">{format!("Voor {servings} personen")}</p> }
+                .into_any()
+        }
+        None => ().into_any(),
+    };
+
+    let times = recipe.times();
+
+    let times_view =
+        (!times.is_empty()).then(|| view! { <p class="recipe-times">{times.join(" · ")}</p> });
 
     view! {
         <div class="recipe">
+            <RecipeImages images=recipe.images share_token/>
             <h1>{recipe.title}</h1>
+            <TagChips tags=recipe.tags/>
+            {servings_view}
+            {times_view}
             <ul>{ingredients}</ul>
             <p>{recipe.instructions}</p>
             <br/>
-            {with_mod.then(|| view!{ <A class:link-button href={format!("/edit/{id}")}>"Aanpassen"</A>})}
+            {with_mod.then(|| view!{
+                <RequireRole role=Role::Editor>
+                    <A class:link-button href={format!("/edit/{id}")}>"Aanpassen"</A>
+                </RequireRole>
+                <A class:link-button href=move || print_href(id, servings.get())>"Afdrukken"</A>
+                <a class="link-button" href=format!("/recipe/{id}/cook") download rel="external">"Cooklang"</a>
+                <RequireRole role=Role::Editor>
+                    <AddToListButton recipe_id=id servings=servings.into()/>
+                    <ShareLinks recipe_id=id/>
+                </RequireRole>
+            })}
         </div>
     }
 }
 
+/// The print view of a recipe, scaled to `servings` if given.
+fn print_href(id: i64, servings: Option<u32>) -> String {
+    match servings {
+        Some(servings) => format!("/recipe/{id}/print?servings={servings}"),
+        None => format!("/recipe/{id}/print"),
+    }
+}
+
+/// Refuses recipes that can't be shown, i.e. without a title.
+#[cfg(feature = "ssr")]
+fn validate_recipe(raw_recipe: &RawRecipe) -> Result<(), NomError> {
+    if raw_recipe.title.trim().is_empty() {
+        return Err(NomError::Validation("Recipes need a title".to_string()));
+    }
+
+    Ok(())
+}
+
 #[server]
-pub async fn new_recipe(raw_recipe: RawRecipe) -> Result<i64, ServerFnError> {
-    use crate::DB;
+pub async fn new_recipe(raw_recipe: RawRecipe) -> Result<i64, NomError> {
+    use crate::db::use_db;
+    use crate::store::use_store;
 
-    let ingredients_split: Vec<&str> = raw_recipe.ingredients.lines().collect();
+    let store = use_store();
+    let user = require_role(&use_db(), Role::Editor).await?;
+    validate_recipe(&raw_recipe)?;
 
-    let mut db = DB.lock().await;
+    let user_id = user.map(|user| user.id);
 
-    let transaction = db.transaction()?;
+    Ok(store.create(raw_recipe.into(), user_id).await?)
+}
 
-    {
-        let mut new_recipe_stmt = transaction
-            .prepare_cached("INSERT INTO recipes (title, instructions) VALUES (?1, ?2);")?;
+/// Stores a new recipe written by `user_id`.
+#[cfg(feature = "ssr")]
+pub(crate) fn create_recipe(
+    transaction: &rusqlite::Transaction,
+    recipe: &Recipe,
+    user_id: Option<i64>,
+) -> rusqlite::Result<i64> {
+    let new_recipe_id = insert_recipe(transaction, recipe)?;
+
+    transaction.execute(
+        "UPDATE recipes SET created_by = ?1, updated_by = ?1 WHERE id = ?2;",
+        (user_id, new_recipe_id),
+    )?;
+
+    Ok(new_recipe_id)
+}
 
-        let inserted = new_recipe_stmt.execute((raw_recipe.title, raw_recipe.instructions))?;
+/// Stores a new recipe with its ingredients and tags. The images are not
+/// stored, those are uploaded separately.
+#[cfg(feature = "ssr")]
+pub(crate) fn insert_recipe(
+    transaction: &rusqlite::Transaction,
+    recipe: &Recipe,
+) -> rusqlite::Result<i64> {
+    insert_recipe_with_id(transaction, None, recipe)
+}
+
+/// [`insert_recipe`], with the id the recipe should get. Without one it gets
+/// the next free id.
+#[cfg(feature = "ssr")]
+pub(crate) fn insert_recipe_with_id(
+    transaction: &rusqlite::Transaction,
+    id: Option<i64>,
+    recipe: &Recipe,
+) -> rusqlite::Result<i64> {
+    {
+        let mut new_recipe_stmt = transaction.prepare_cached(
+            "
+            INSERT INTO recipes (id, title, servings, prep_time, cook_time, instructions)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6);
+            ",
+        )?;
 
-        assert_eq!(1, inserted);
+        new_recipe_stmt.execute((
+            id,
+            &recipe.title,
+            recipe.servings,
+            recipe.prep_time,
+            recipe.cook_time,
+            &recipe.instructions,
+        ))?;
     }
 
     let new_recipe_id = transaction.last_insert_rowid();
 
     {
-        let mut new_ingredient_stmt = transaction
-            .prepare_cached("INSERT INTO ingredients (recipe, ingredient) VALUES (?1, ?2);")?;
+        let mut new_ingredient_stmt = transaction.prepare_cached(
+            "INSERT INTO ingredients (recipe, quantity, unit, name, note) VALUES (?1, ?2, ?3, ?4, ?5);",
+        )?;
 
-        for ingredient in ingredients_split {
-            let inserted = new_ingredient_stmt.execute((new_recipe_id, ingredient))?;
-            assert_eq!(1, inserted);
+        for ingredient in &recipe.ingredients {
+            new_ingredient_stmt.execute((
+                new_recipe_id,
+                ingredient.quantity,
+                &ingredient.unit,
+                &ingredient.name,
+                &ingredient.note,
+            ))?;
         }
     }
 
-    transaction.commit()?;
+    set_recipe_tags(transaction, new_recipe_id, &recipe.tags)?;
+    index_recipe(transaction, new_recipe_id)?;
 
     Ok(new_recipe_id)
 }
 
+/// Replaces a recipe, edited from `version`. Fails with
+/// [`NomError::Stale`] if someone else changed it since, so the edits can
+/// be merged with the current recipe first.
 #[server]
-pub async fn update_recipe(recipe_id: i64, raw_recipe: RawRecipe) -> Result<Recipe, ServerFnError> {
-    use crate::DB;
+pub async fn update_recipe(
+    recipe_id: i64,
+    raw_recipe: RawRecipe,
+    version: u32,
+) -> Result<Recipe, NomError> {
+    use crate::db::use_db;
+    use crate::store::{Update, use_store};
+
+    let store = use_store();
+    let user = require_role(&use_db(), Role::Editor).await?;
+    validate_recipe(&raw_recipe)?;
 
-    // Smaller scope because the future is !Send otherwise
+    let user_id = user.map(|user| user.id);
+
+    match store
+        .update(recipe_id, raw_recipe.into(), Some(version), user_id)
+        .await?
     {
-        let mut db = DB.lock().await;
+        Update::Updated(recipe) => Ok(recipe),
+        Update::Conflict(_) => Err(NomError::Stale(format!(
+            "Recipe {recipe_id} was changed by someone else"
+        ))),
+        Update::UnknownRecipe => Err(NomError::unknown_recipe(recipe_id)),
+    }
+}
 
-        let transaction = db.transaction()?;
+/// Replaces a recipe, its ingredients and tags, as edited by `user_id`, if
+/// it's still at `version`. Without a version it's replaced regardless. The
+/// images stay. Returns whether the recipe was replaced.
+#[cfg(feature = "ssr")]
+pub(crate) fn update_recipe_rows(
+    transaction: &rusqlite::Transaction,
+    recipe_id: i64,
+    recipe: &Recipe,
+    version: Option<u32>,
+    user_id: Option<i64>,
+) -> rusqlite::Result<bool> {
+    // Update the recipe itself
+    {
+        let mut update_recipe_stmt = transaction.prepare_cached(
+            "
+            UPDATE recipes SET title = ?1, servings = ?2, prep_time = ?3, cook_time = ?4, instructions = ?5,
+                updated_by = ?7, version = version + 1
+            WHERE id = ?6 AND (?8 IS NULL OR version = ?8);
+            ",
+        )?;
 
-        // First delete the old recipes
-        {
-            let mut delete_ingredients_stmt = transaction
-                .prepare_cached("DELETE FROM ingredients WHERE recipe = (?1);")
-                .expect("Malformed query");
+        let updated = update_recipe_stmt.execute((
+            &recipe.title,
+            recipe.servings,
+            recipe.prep_time,
+            recipe.cook_time,
+            &recipe.instructions,
+            recipe_id,
+            user_id,
+            version,
+        ))?;
 
-            _ = delete_ingredients_stmt
-                .execute((recipe_id,))
-                .expect("Failed to delete previous ingredients");
+        if updated == 0 {
+            return Ok(false);
         }
+    }
 
-        // Insert the new ones
-        let ingredients_split: Vec<&str> = raw_recipe
-            .ingredients
-            .lines()
-            .map(str::trim)
-            .filter(|s| !s.is_empty())
-            .collect();
+    // Then delete the old ingredients
+    transaction.execute("DELETE FROM ingredients WHERE recipe = (?1);", (recipe_id,))?;
 
-        {
-            let mut new_ingredient_stmt = transaction
-                .prepare_cached("INSERT INTO ingredients (recipe, ingredient) VALUES (?1, ?2);")?;
+    // Insert the new ones
+    {
+        let mut new_ingredient_stmt = transaction.prepare_cached(
+            "INSERT INTO ingredients (recipe, quantity, unit, name, note) VALUES (?1, ?2, ?3, ?4, ?5);",
+        )?;
 
-            for ingredient in ingredients_split {
-                let inserted = new_ingredient_stmt.execute((recipe_id, ingredient))?;
-                assert_eq!(1, inserted);
-            }
+        for ingredient in &recipe.ingredients {
+            new_ingredient_stmt.execute((
+                recipe_id,
+                ingredient.quantity,
+                &ingredient.unit,
+                &ingredient.name,
+                &ingredient.note,
+            ))?;
         }
+    }
 
-        // Update the recipe itself
-        {
-            let mut update_recipe_stmt = transaction.prepare_cached(
-                "UPDATE recipes SET title = ?1, instructions = ?2 WHERE id = ?3;",
-            )?;
+    set_recipe_tags(transaction, recipe_id, &recipe.tags)?;
+    index_recipe(transaction, recipe_id)?;
 
-            let updated = update_recipe_stmt.execute((
-                raw_recipe.title,
-                raw_recipe.instructions,
-                recipe_id,
-            ))?;
+    Ok(true)
+}
 
-            assert_eq!(1, updated);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct ListedRecipe {
+    pub id: i64,
+    pub title: String,
+    pub thumbnail: Option<i64>,
+}
+
+/// SQL expression for the id of the main image of `recipes.id`, if any.
+#[cfg(feature = "ssr")]
+const THUMBNAIL_SQL: &str =
+    "(SELECT id FROM images WHERE images.recipe = recipes.id ORDER BY position LIMIT 1)";
+
+/// Lists all recipes having every one of `tags`.
+#[server]
+pub async fn list_recipes(
+    #[server(default)] tags: Vec<String>,
+) -> Result<Vec<ListedRecipe>, NomError> {
+    use crate::store::use_store;
+
+    Ok(use_store().list(tags, None, 0).await?)
+}
+
+/// Lists the recipes having every one of `tags`, in the order they were
+/// added, skipping `offset` and returning at most `limit` of them.
+#[cfg(feature = "ssr")]
+pub(crate) fn query_recipes(
+    conn: &rusqlite::Connection,
+    tags: &[String],
+    limit: Option<u32>,
+    offset: u32,
+) -> rusqlite::Result<Vec<ListedRecipe>> {
+    let mut get_recipes_stmt = conn.prepare_cached(&format!(
+        "SELECT id, title, {THUMBNAIL_SQL} FROM recipes WHERE {} ORDER BY id LIMIT ?2 OFFSET ?3;",
+        has_all_tags_sql(1)
+    ))?;
+
+    // A negative limit is no limit to SQLite
+    let limit = limit.map_or(-1, i64::from);
+
+    get_recipes_stmt
+        .query_map((tags_json(tags), limit, offset), |recipe| {
+            Ok(ListedRecipe {
+                id: recipe.get(0)?,
+                title: recipe.get(1)?,
+                thumbnail: recipe.get(2)?,
+            })
+        })?
+        .collect()
+}
+
+/// How many recipes have every one of `tags`.
+#[cfg(feature = "ssr")]
+pub(crate) fn count_recipes(conn: &rusqlite::Connection, tags: &[String]) -> rusqlite::Result<u32> {
+    conn.prepare_cached(&format!(
+        "SELECT count(*) FROM recipes WHERE {};",
+        has_all_tags_sql(1)
+    ))?
+    .query_row((tags_json(tags),), |row| row.get(0))
+}
+
+/// A recipe that can be made with some of the ingredients at hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngredientMatch {
+    pub id: i64,
+    pub title: String,
+    pub have: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+/// Finds recipes using any of the `available` ingredients, ranked by how few
+/// ingredients are still missing.
+#[server]
+pub async fn find_recipes_by_ingredients(
+    #[server(default)] available: Vec<String>,
+) -> Result<Vec<IngredientMatch>, NomError> {
+    use crate::db::use_db;
+
+    let available: Vec<Vec<String>> = available
+        .iter()
+        .map(|name| normalize_ingredient(name))
+        .filter(|words| !words.is_empty())
+        .collect();
+
+    if available.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rows = use_db()
+        .read(|db| {
+            db.prepare_cached(
+                "
+                SELECT recipes.id, recipes.title, ingredients.name
+                FROM recipes JOIN ingredients ON ingredients.recipe = recipes.id
+                ORDER BY recipes.id, ingredients.id;
+                ",
+            )?
+            .query_map((), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+        })
+        .await?;
+
+    let mut matches: Vec<IngredientMatch> = Vec::new();
+
+    for (id, title, ingredient) in rows {
+        if matches.last().is_none_or(|m| m.id != id) {
+            matches.push(IngredientMatch {
+                id,
+                title,
+                have: Vec::new(),
+                missing: Vec::new(),
+            });
         }
 
-        transaction.commit()?;
+        let current = matches.last_mut().unwrap();
+        let words = normalize_ingredient(&ingredient);
 
-        std::mem::drop(db);
+        if available
+            .iter()
+            .any(|term| ingredient_matches(&words, term))
+        {
+            current.have.push(ingredient);
+        } else {
+            current.missing.push(ingredient);
+        }
     }
 
-    let updated_recipe = get_recipe(recipe_id).await.unwrap();
-    Ok(updated_recipe.expect("Could not find updated recipe"))
+    matches.retain(|m| !m.have.is_empty());
+    matches.sort_by(|a, b| {
+        a.missing
+            .len()
+            .cmp(&b.missing.len())
+            .then(b.have.len().cmp(&a.have.len()))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+
+    Ok(matches)
+}
+
+/// (Re)builds the search index entry of a recipe from its stored rows.
+#[cfg(feature = "ssr")]
+fn index_recipe(transaction: &rusqlite::Transaction, recipe_id: i64) -> rusqlite::Result<()> {
+    transaction.execute("DELETE FROM recipes_fts WHERE rowid = (?1);", (recipe_id,))?;
+    transaction.execute(
+        "
+        INSERT INTO recipes_fts (rowid, title, ingredients, instructions)
+            SELECT
+                id,
+                title,
+                (SELECT group_concat(name, char(10)) FROM ingredients WHERE recipe = recipes.id),
+                instructions
+            FROM recipes WHERE id = (?1);
+        ",
+        (recipe_id,),
+    )?;
+
+    Ok(())
 }
 
+/// A piece of a search snippet, highlighted if it matched the query.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ListedRecipe {
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
     pub id: i64,
     pub title: String,
+    pub thumbnail: Option<i64>,
+    pub snippet: Vec<SnippetPart>,
 }
 
+/// Marks the start and end of a match in snippets returned by SQLite
+#[cfg(feature = "ssr")]
+const HIGHLIGHT_START: char = '\u{2}';
+#[cfg(feature = "ssr")]
+const HIGHLIGHT_END: char = '\u{3}';
+
+/// Turns free text into an FTS5 query where every word must match as a
+/// prefix. Quoting each word keeps FTS5 syntax in the input from doing
+/// anything unexpected.
+#[cfg(feature = "ssr")]
+fn fts_query(query: &str) -> String {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(feature = "ssr")]
+fn split_snippet(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut highlight = false;
+
+    for text in snippet.split([HIGHLIGHT_START, HIGHLIGHT_END]) {
+        if !text.is_empty() {
+            parts.push(SnippetPart {
+                text: text.to_string(),
+                highlight,
+            });
+        }
+
+        highlight = !highlight;
+    }
+
+    parts
+}
+
+/// Searches titles, ingredients and instructions of recipes having every one
+/// of `tags`, best matches first.
 #[server]
-pub async fn list_recipes() -> Result<Vec<ListedRecipe>, ServerFnError> {
-    use crate::DB;
+pub async fn search_recipes(
+    query: String,
+    #[server(default)] tags: Vec<String>,
+) -> Result<Vec<SearchResult>, NomError> {
+    use crate::store::use_store;
 
-    let db = DB.lock().await;
+    Ok(use_store().search(query, tags).await?)
+}
 
-    let mut get_recipes_stmt = db.prepare_cached("SELECT id, title FROM recipes;").unwrap();
+/// The best matches for `query` among the recipes having every one of `tags`.
+#[cfg(feature = "ssr")]
+pub(crate) fn search(
+    conn: &rusqlite::Connection,
+    query: &str,
+    tags: &[String],
+) -> rusqlite::Result<Vec<SearchResult>> {
+    let fts_query = fts_query(query);
 
-    let recipes = get_recipes_stmt.query(()).unwrap();
+    if fts_query.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    Ok(recipes
-        .mapped(|recipe| {
-            Ok(ListedRecipe {
-                id: recipe.get(0).unwrap(),
-                title: recipe.get(1).unwrap(),
+    // Titles weigh heaviest, then ingredients, then the instructions
+    let mut search_stmt = conn.prepare_cached(&format!(
+        "
+        SELECT
+            recipes_fts.rowid,
+            recipes_fts.title,
+            {THUMBNAIL_SQL},
+            snippet(recipes_fts, -1, char(2), char(3), '…', 12)
+        FROM recipes_fts JOIN recipes ON recipes.id = recipes_fts.rowid
+        WHERE recipes_fts MATCH (?1) AND {}
+        ORDER BY bm25(recipes_fts, 10.0, 5.0, 1.0)
+        LIMIT 50;
+        ",
+        has_all_tags_sql(2),
+    ))?;
+
+    search_stmt
+        .query_map((fts_query, tags_json(tags)), |row| {
+            Ok(SearchResult {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                thumbnail: row.get(2)?,
+                snippet: split_snippet(&row.get::<_, String>(3)?),
             })
-        })
-        .collect::<Result<Vec<_>, _>>()?)
+        })?
+        .collect()
 }
 
 #[server]
-pub async fn get_recipe(id: i64) -> Result<Option<Recipe>, ServerFnError> {
-    use crate::DB;
-    use rusqlite::OptionalExtension;
+pub async fn get_recipe(id: i64) -> Result<Recipe, NomError> {
+    use crate::store::use_store;
 
-    let db = DB.lock().await;
+    use_store()
+        .get(id)
+        .await?
+        .ok_or(NomError::unknown_recipe(id))
+}
 
-    let mut get_recipe_stmt = db
-        .prepare_cached("SELECT title, instructions FROM recipes WHERE id = (?1);")
-        .expect("Invalid statement");
+/// A recipe with its ingredients, tags and images, if it exists.
+#[cfg(feature = "ssr")]
+pub fn load_recipe(conn: &rusqlite::Connection, id: i64) -> rusqlite::Result<Option<Recipe>> {
+    use rusqlite::OptionalExtension;
 
-    let (title, instructions) = if let Some((title, instructions)) = get_recipe_stmt
-        .query_one((id,), |row| Ok((row.get(0).unwrap(), row.get(1).unwrap())))
-        .optional()
-        .unwrap()
-    {
-        (title, instructions)
-    } else {
+    let mut get_recipe_stmt = conn.prepare_cached(
+        "SELECT title, servings, prep_time, cook_time, instructions, version FROM recipes WHERE id = (?1);",
+    )?;
+
+    let Some((title, servings, prep_time, cook_time, instructions, version)) = get_recipe_stmt
+        .query_one((id,), |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })
+        .optional()?
+    else {
         return Ok(None);
     };
 
-    let mut get_ingredients_stmt = db
-        .prepare_cached("SELECT ingredient FROM ingredients WHERE recipe = (?1);")
-        .expect("Invalid statement");
+    let mut get_ingredients_stmt = conn.prepare_cached(
+        "SELECT quantity, unit, name, note FROM ingredients WHERE recipe = (?1) ORDER BY id;",
+    )?;
 
     let ingredients = get_ingredients_stmt
-        .query_map((id,), |row| Ok(row.get(0).unwrap()))
-        .unwrap()
-        .map(Result::unwrap)
-        .collect();
+        .query_map((id,), |row| {
+            Ok(Ingredient {
+                quantity: row.get(0)?,
+                unit: row.get(1)?,
+                name: row.get(2)?,
+                note: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let tags = recipe_tags(conn, id)?;
+    let images = crate::images::storage::recipe_images(conn, id)?;
 
     Ok(Some(Recipe {
         title,
+        servings,
+        prep_time,
+        cook_time,
         ingredients,
         instructions,
+        tags,
+        images,
+        version,
     }))
 }
 
 #[server]
-pub async fn delete_recipe(recipe_id: i64) -> Result<(), ServerFnError> {
-    use crate::DB;
+pub async fn delete_recipe(recipe_id: i64) -> Result<(), NomError> {
+    use crate::db::use_db;
+    use crate::store::use_store;
 
-    let mut db = DB.lock().await;
+    let store = use_store();
+    require_role(&use_db(), Role::Admin).await?;
 
-    let transaction = db.transaction()?;
+    if !store.delete(recipe_id).await? {
+        return Err(NomError::unknown_recipe(recipe_id));
+    }
 
-    {
-        let mut delete_ingredients_stmt = transaction
-            .prepare_cached("DELETE FROM ingredients WHERE recipe = (?1);")
-            .expect("Malformed query");
+    Ok(())
+}
 
-        _ = delete_ingredients_stmt
-            .execute((recipe_id,))
-            .expect("Failed to  ingredients");
-    }
+#[cfg(feature = "ssr")]
+pub(crate) fn recipe_exists(conn: &rusqlite::Connection, id: i64) -> rusqlite::Result<bool> {
+    conn.prepare_cached("SELECT 1 FROM recipes WHERE id = ?1;")?
+        .exists((id,))
+}
 
-    {
-        let mut delete_recipe_stmt = transaction
-            .prepare_cached("DELETE FROM recipes WHERE id = (?1);")
-            .expect("Malformed query");
+/// Deletes a recipe and everything referring to it. Returns the ids of its
+/// images, so their files can be removed once the transaction is committed.
+/// Fails with [`rusqlite::Error::QueryReturnedNoRows`] for unknown recipes.
+#[cfg(feature = "ssr")]
+pub(crate) fn delete_recipe_rows(
+    transaction: &rusqlite::Transaction,
+    recipe_id: i64,
+) -> rusqlite::Result<Vec<i64>> {
+    transaction.execute("DELETE FROM ingredients WHERE recipe = (?1);", (recipe_id,))?;
+    set_recipe_tags(transaction, recipe_id, &[])?;
+    let image_ids = crate::images::storage::delete_recipe_images(transaction, recipe_id)?;
+    transaction.execute("DELETE FROM meal_plan WHERE recipe = (?1);", (recipe_id,))?;
+    transaction.execute(
+        "DELETE FROM shopping_recipes WHERE recipe = (?1);",
+        (recipe_id,),
+    )?;
+    transaction.execute("DELETE FROM share_links WHERE recipe = (?1);", (recipe_id,))?;
 
-        let num_deleted = delete_recipe_stmt
-            .execute((recipe_id,))
-            .expect("Failed to recipe");
+    let num_deleted = transaction.execute("DELETE FROM recipes WHERE id = (?1);", (recipe_id,))?;
 
-        assert_eq!(1, num_deleted, "Deleted an unexpected number of recipes");
+    if num_deleted == 0 {
+        return Err(rusqlite::Error::QueryReturnedNoRows);
     }
 
-    transaction.commit()?;
+    transaction.execute("DELETE FROM recipes_fts WHERE rowid = (?1);", (recipe_id,))?;
 
-    Ok(())
+    Ok(image_ids)
 }
 
+/// Picks a random recipe having every one of `tags`, if there are any.
 #[server]
-pub async fn random_recipe() -> Result<Option<i64>, ServerFnError> {
-    use crate::DB;
-    use rusqlite::OptionalExtension;
+pub async fn random_recipe(#[server(default)] tags: Vec<String>) -> Result<Option<i64>, NomError> {
+    use crate::store::use_store;
+
+    Ok(use_store().random(tags).await?)
+}
 
-    let db = DB.lock().await;
+/// The id of a random recipe having every one of `tags`.
+#[cfg(feature = "ssr")]
+pub(crate) fn pick_random(
+    conn: &rusqlite::Connection,
+    tags: &[String],
+) -> rusqlite::Result<Option<i64>> {
+    use rusqlite::OptionalExtension;
 
-    Ok(db
-        .query_one(
-            "SELECT id FROM recipes ORDER BY RANDOM() LIMIT 1;",
-            (),
-            |row| Ok(row.get::<_, i64>(0).unwrap()),
-        )
-        .optional()
-        .expect("Failed to read random ID"))
+    conn.query_one(
+        &format!(
+            "SELECT id FROM recipes WHERE {} ORDER BY RANDOM() LIMIT 1;",
+            has_all_tags_sql(1)
+        ),
+        (tags_json(tags),),
+        |row| row.get::<_, i64>(0),
+    )
+    .optional()
 }
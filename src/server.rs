@@ -0,0 +1,103 @@
+//! The routes of the app and the state they share, so `main` and the tests
+//! serve the same thing.
+
+use axum::Router;
+use axum::extract::FromRef;
+use axum::routing::get;
+use leptos::prelude::*;
+use leptos_axum::{LeptosRoutes, generate_route_list};
+use tower_http::compression::CompressionLayer;
+use tower_http::limit::RequestBodyLimitLayer;
+
+use crate::app::{App, shell};
+use crate::auth::throttle::LoginThrottle;
+use crate::db::Pool;
+use crate::store::Store;
+
+/// Largest request body, enough for a few photos at once. Uploads check the
+/// size of every file as well.
+const MAX_REQUEST_BODY: usize = 64 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub leptos_options: LeptosOptions,
+    pub db: Pool,
+    pub store: Store,
+    /// Failed logins, per app so each has its own lockouts
+    pub throttle: LoginThrottle,
+}
+
+impl FromRef<AppState> for LeptosOptions {
+    fn from_ref(state: &AppState) -> Self {
+        state.leptos_options.clone()
+    }
+}
+
+impl FromRef<AppState> for Pool {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for Store {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for LoginThrottle {
+    fn from_ref(state: &AppState) -> Self {
+        state.throttle.clone()
+    }
+}
+
+/// Everything nom serves, with the database, recipe store and login throttle
+/// of `state` for the server functions and handlers.
+pub fn router(state: AppState) -> Router {
+    use crate::auth::middleware::auth_middleware;
+    use crate::cooklang::format::serve_recipe;
+    use crate::export::collection::serve_export;
+    use crate::images::storage::serve_image;
+    use crate::log::middleware::log_middleware;
+    use crate::pdf::serve_pdf;
+    use crate::share::links::serve_shared_image;
+
+    // Generate the list of routes in your Leptos App
+    let routes = generate_route_list(App);
+
+    Router::new()
+        .route("/images/{id}/{variant}", get(serve_image))
+        .route("/export.json", get(serve_export))
+        .route("/recipe/{id}/cook", get(serve_recipe))
+        .route("/recipes.pdf", get(serve_pdf))
+        .route("/s/{token}/images/{id}/{variant}", get(serve_shared_image))
+        .merge(crate::api::router())
+        .leptos_routes_with_context(
+            &state,
+            routes,
+            {
+                let db = state.db.clone();
+                let store = state.store.clone();
+                let throttle = state.throttle.clone();
+
+                move || {
+                    provide_context(db.clone());
+                    provide_context(store.clone());
+                    provide_context(throttle.clone());
+                }
+            },
+            {
+                let leptos_options = state.leptos_options.clone();
+                move || shell(leptos_options.clone())
+            },
+        )
+        .fallback(leptos_axum::file_and_error_handler::<AppState, _>(shell))
+        .layer(CompressionLayer::new())
+        .layer(RequestBodyLimitLayer::new(MAX_REQUEST_BODY))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ))
+        .layer(axum::middleware::from_fn(log_middleware))
+        .with_state(state)
+}
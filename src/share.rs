@@ -0,0 +1,382 @@
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorMessage, NomError};
+use crate::recipe::Recipe;
+
+/// A link that shows a single recipe to anyone who has it, without logging
+/// in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: i64,
+    pub token: String,
+    pub created: DateTime<Utc>,
+    pub expires: Option<DateTime<Utc>>,
+    /// The username of who made it
+    pub created_by: Option<String>,
+}
+
+impl ShareLink {
+    pub fn path(&self) -> String {
+        format!("/s/{}", self.token)
+    }
+}
+
+pub fn shared_image_url(token: &str, image_id: i64, variant: &str) -> String {
+    format!("/s/{token}/images/{image_id}/{variant}")
+}
+
+/// The links of a recipe that still work.
+#[server]
+pub async fn list_share_links(recipe_id: i64) -> Result<Vec<ShareLink>, ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    // Creates the secret the links are signed with, the first time
+    Ok(db.write(move |db| links::list(db, recipe_id)).await?)
+}
+
+/// Makes a link to a recipe, that stops working after `days` if given.
+#[server]
+pub async fn create_share_link(
+    recipe_id: i64,
+    #[server(default)] days: Option<u32>,
+) -> Result<ShareLink, ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    let user = require_role(&db, Role::Editor).await?;
+
+    let expires = days.map(|days| Utc::now() + chrono::Duration::days(days.into()));
+    let user_id = user.map(|user| user.id);
+
+    db.write(move |db| {
+        if !crate::recipe::recipe_exists(db, recipe_id)? {
+            return Err(ServerFnError::new(format!("Unknown recipe {recipe_id}")));
+        }
+
+        Ok(links::create(db, recipe_id, user_id, expires)?)
+    })
+    .await
+}
+
+#[server]
+pub async fn revoke_share_link(link_id: i64) -> Result<(), ServerFnError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    let deleted = db.write(move |db| links::delete(db, link_id)).await?;
+
+    if !deleted {
+        return Err(ServerFnError::new(format!("Unknown share link {link_id}")));
+    }
+
+    Ok(())
+}
+
+/// The recipe a share link is for, if the link still works. Open to anyone,
+/// the link is what gives access.
+#[server(endpoint = "shared_recipe")]
+pub async fn get_shared_recipe(token: String) -> Result<Option<(i64, Recipe)>, ServerFnError> {
+    use crate::db::use_db;
+    use crate::recipe::load_recipe;
+
+    let shared = use_db()
+        .read(move |db| -> rusqlite::Result<_> {
+            let Some(recipe_id) = links::shared_recipe_id(db, &token)? else {
+                return Ok(None);
+            };
+
+            Ok(load_recipe(db, recipe_id)?.map(|recipe| (recipe_id, recipe)))
+        })
+        .await;
+
+    Ok(shared?)
+}
+
+/// The share links of a recipe, to make new ones and revoke them.
+#[component]
+pub fn ShareLinks(recipe_id: i64) -> impl IntoView {
+    let links = Resource::new(
+        || (),
+        move |_| async move { list_share_links(recipe_id).await },
+    );
+    let (days, set_days) = signal(None::<u32>);
+    let error = RwSignal::new(None::<NomError>);
+
+    let create = move |_| {
+        spawn_local(async move {
+            match create_share_link(recipe_id, days.get_untracked()).await {
+                Ok(_) => links.refetch(),
+                Err(err) => error.set(Some(err.into())),
+            }
+        });
+    };
+
+    view! {
+        <details class="share-links">
+            <summary>"Delen"</summary>
+            <p>"Met een deellink kan iedereen dit recept bekijken, zonder in te loggen."</p>
+            {move || error.get().map(|error| view! { <ErrorMessage error/> })}
+            <Transition>
+                {move || links.get().map(|list| {
+                    let list = match list {
+                        Ok(list) => list,
+                        Err(err) => return view! { <ErrorMessage error=err.into()/> }.into_any(),
+                    };
+
+                    view! {
+                        <ul>
+                            {list.into_iter().map(|link| {
+                                let link_id = link.id;
+
+                                let revoke = move |_| {
+                                    spawn_local(async move {
+                                        match revoke_share_link(link_id).await {
+                                            Ok(()) => links.refetch(),
+                                            Err(err) => error.set(Some(err.into())),
+                                        }
+                                    });
+                                };
+
+                                let expires = link.expires.map_or("verloopt niet".to_string(), |expires| {
+                                    format!("verloopt {}", expires.format("%d-%m-%Y"))
+                                });
+                                let created_by = link.created_by.clone().map(|username| format!(" · door {username}"));
+
+                                view! {
+                                    <li>
+                                        <a href=link.path() rel="external">{link.path()}</a>
+                                        {format!(" · {expires}")}
+                                        {created_by}
+                                        <button class="plan-remove" on:click=revoke>"×"</button>
+                                    </li>
+                                }
+                            }).collect_view()}
+                        </ul>
+                    }
+                    .into_any()
+                })}
+            </Transition>
+            <select on:change=move |ev| set_days.set(event_target_value(&ev).parse().ok())>
+                <option value="" selected>"Verloopt niet"</option>
+                <option value="1">"1 dag geldig"</option>
+                <option value="7">"1 week geldig"</option>
+                <option value="30">"1 maand geldig"</option>
+            </select>
+            <button class:link-button on:click=create>"Maak deellink"</button>
+        </details>
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod links {
+    //! Share links are signed, so they can't be guessed from their id, and
+    //! stored, so they can be listed and revoked.
+
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use chrono::{DateTime, Utc};
+    use hmac::{Hmac, Mac};
+    use rusqlite::{Connection, OptionalExtension};
+    use sha2::Sha256;
+
+    use super::ShareLink;
+    use crate::db::Pool;
+
+    /// Bytes of the signature in a token, enough to never be guessed.
+    const SIGNATURE_LEN: usize = 16;
+
+    /// The key share links are signed with, made on first use.
+    fn secret(conn: &Connection) -> rusqlite::Result<Vec<u8>> {
+        let existing = conn
+            .prepare_cached("SELECT value FROM secrets WHERE name = 'share_links';")?
+            .query_row((), |row| row.get(0))
+            .optional()?;
+
+        if let Some(secret) = existing {
+            return Ok(secret);
+        }
+
+        let mut secret = vec![0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+
+        conn.prepare_cached("INSERT INTO secrets (name, value) VALUES ('share_links', ?1);")?
+            .execute((&secret,))?;
+
+        Ok(secret)
+    }
+
+    fn mac(secret: &[u8], id: i64, recipe_id: i64, expires: Option<i64>) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes any key length");
+
+        let expires = expires.map_or("-".to_string(), |expires| expires.to_string());
+        mac.update(format!("{id}:{recipe_id}:{expires}").as_bytes());
+
+        mac
+    }
+
+    fn token(secret: &[u8], id: i64, recipe_id: i64, expires: Option<i64>) -> String {
+        let signature = mac(secret, id, recipe_id, expires).finalize().into_bytes();
+        let signature: String = signature[..SIGNATURE_LEN]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        format!("{id}.{signature}")
+    }
+
+    fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+        if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return None;
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect()
+    }
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap_or_default()
+    }
+
+    pub fn create(
+        conn: &Connection,
+        recipe_id: i64,
+        user_id: Option<i64>,
+        expires: Option<DateTime<Utc>>,
+    ) -> rusqlite::Result<ShareLink> {
+        let now = Utc::now().timestamp();
+        let expires = expires.map(|expires| expires.timestamp());
+
+        conn.prepare_cached("DELETE FROM share_links WHERE expires <= ?1;")?
+            .execute((now,))?;
+        conn.prepare_cached(
+            "INSERT INTO share_links (recipe, created_by, created, expires) VALUES (?1, ?2, ?3, ?4);",
+        )?
+        .execute((recipe_id, user_id, now, expires))?;
+
+        let id = conn.last_insert_rowid();
+
+        let created_by = user_id
+            .map(|user_id| {
+                conn.prepare_cached("SELECT username FROM users WHERE id = ?1;")?
+                    .query_row((user_id,), |row| row.get(0))
+            })
+            .transpose()?;
+
+        Ok(ShareLink {
+            id,
+            token: token(&secret(conn)?, id, recipe_id, expires),
+            created: timestamp(now),
+            expires: expires.map(timestamp),
+            created_by,
+        })
+    }
+
+    pub fn list(conn: &Connection, recipe_id: i64) -> rusqlite::Result<Vec<ShareLink>> {
+        let secret = secret(conn)?;
+
+        conn.prepare_cached(
+            "
+            SELECT share_links.id, share_links.created, share_links.expires, users.username
+            FROM share_links LEFT JOIN users ON users.id = share_links.created_by
+            WHERE share_links.recipe = ?1 AND (share_links.expires IS NULL OR share_links.expires > ?2)
+            ORDER BY share_links.created, share_links.id;
+            ",
+        )?
+        .query_map((recipe_id, Utc::now().timestamp()), |row| {
+            let id = row.get(0)?;
+            let expires: Option<i64> = row.get(2)?;
+
+            Ok(ShareLink {
+                id,
+                token: token(&secret, id, recipe_id, expires),
+                created: timestamp(row.get(1)?),
+                expires: expires.map(timestamp),
+                created_by: row.get(3)?,
+            })
+        })?
+        .collect()
+    }
+
+    pub fn delete(conn: &Connection, link_id: i64) -> rusqlite::Result<bool> {
+        let deleted = conn
+            .prepare_cached("DELETE FROM share_links WHERE id = ?1;")?
+            .execute((link_id,))?;
+
+        Ok(deleted == 1)
+    }
+
+    /// The recipe a token gives access to, if it's signed by us and hasn't
+    /// expired or been revoked.
+    pub fn shared_recipe_id(conn: &Connection, token: &str) -> rusqlite::Result<Option<i64>> {
+        let Some((id, signature)) = token
+            .split_once('.')
+            .and_then(|(id, signature)| Some((id.parse::<i64>().ok()?, decode_hex(signature)?)))
+            .filter(|(_, signature)| signature.len() == SIGNATURE_LEN)
+        else {
+            return Ok(None);
+        };
+
+        let link = conn
+            .prepare_cached("SELECT recipe, expires FROM share_links WHERE id = ?1;")?
+            .query_row((id,), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?))
+            })
+            .optional()?;
+
+        let Some((recipe_id, expires)) = link else {
+            return Ok(None);
+        };
+
+        if expires.is_some_and(|expires| expires <= Utc::now().timestamp()) {
+            return Ok(None);
+        }
+
+        let valid = mac(&secret(conn)?, id, recipe_id, expires)
+            .verify_truncated_left(&signature)
+            .is_ok();
+
+        Ok(valid.then_some(recipe_id))
+    }
+
+    /// Serves `/s/{token}/images/{id}/{variant}`, the images of a shared
+    /// recipe.
+    pub async fn serve_shared_image(
+        State(db): State<Pool>,
+        Path((token, image_id, variant)): Path<(String, i64, String)>,
+    ) -> Response {
+        let shared = db
+            .read(move |db| {
+                let Some(recipe_id) = shared_recipe_id(db, &token)? else {
+                    return Ok(false);
+                };
+
+                db.prepare_cached("SELECT 1 FROM images WHERE id = ?1 AND recipe = ?2;")?
+                    .exists((image_id, recipe_id))
+            })
+            .await;
+
+        match shared {
+            Ok(true) => crate::images::storage::serve_image(Path((image_id, variant))).await,
+            Ok(false) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => {
+                leptos::logging::error!("Could not check share link: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,619 @@
+use chrono::NaiveDate;
+use leptos::prelude::*;
+use leptos::reactive::spawn_local;
+use leptos_router::components::A;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorMessage, NomError};
+
+/// A recipe on the shopping list, for the given number of servings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoppingRecipe {
+    pub id: i64,
+    pub recipe_id: i64,
+    pub title: String,
+    pub servings: Option<u32>,
+}
+
+/// The merged ingredients of all recipes on the list that share a name and a
+/// compatible unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoppingItem {
+    /// Identifies the item across changes to the list, so it stays ticked off
+    pub key: String,
+    pub text: String,
+    /// Titles of the recipes needing this item
+    pub recipes: Vec<String>,
+    pub checked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoppingSection {
+    pub name: String,
+    pub items: Vec<ShoppingItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoppingList {
+    pub recipes: Vec<ShoppingRecipe>,
+    /// In the order you walk through a store
+    pub sections: Vec<ShoppingSection>,
+}
+
+#[server]
+pub async fn get_shopping_list() -> Result<ShoppingList, NomError> {
+    use crate::db::use_db;
+
+    use_db().read(move |db| {
+        let mut recipes_stmt = db.prepare_cached(
+            "
+            SELECT shopping_recipes.id, recipes.id, recipes.title, coalesce(shopping_recipes.servings, recipes.servings)
+            FROM shopping_recipes JOIN recipes ON recipes.id = shopping_recipes.recipe
+            ORDER BY shopping_recipes.id;
+            ",
+        )?;
+
+        let recipes = recipes_stmt
+            .query_map((), |row| {
+                Ok(ShoppingRecipe {
+                    id: row.get(0)?,
+                    recipe_id: row.get(1)?,
+                    title: row.get(2)?,
+                    servings: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ShoppingList {
+            recipes,
+            sections: aggregate::load_sections(db)?,
+        })
+    })
+    .await
+}
+
+/// Puts a recipe on the list. Without `servings` the servings of the recipe
+/// are used.
+#[server]
+pub async fn add_to_shopping_list(
+    recipe_id: i64,
+    #[server(default)] servings: Option<u32>,
+) -> Result<(), NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        let inserted = db.execute(
+            "INSERT INTO shopping_recipes (recipe, servings) SELECT id, ?2 FROM recipes WHERE id = ?1;",
+            (recipe_id, servings),
+        )?;
+
+        if inserted == 0 {
+            return Err(NomError::unknown_recipe(recipe_id));
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Puts every meal planned in the week containing `week` on the list.
+/// Returns the number of recipes added.
+#[server]
+pub async fn add_plan_to_shopping_list(week: NaiveDate) -> Result<usize, NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+    use crate::plan::week_start;
+
+    let start = week_start(week);
+    let end = start + chrono::Days::new(7);
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        let inserted = db.execute(
+            "
+            INSERT INTO shopping_recipes (recipe, servings)
+            SELECT recipe, servings FROM meal_plan WHERE date >= ?1 AND date < ?2 ORDER BY date, id;
+            ",
+            (start, end),
+        )?;
+
+        Ok(inserted)
+    })
+    .await
+}
+
+#[server]
+pub async fn remove_from_shopping_list(entry_id: i64) -> Result<(), NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        let transaction = db.transaction()?;
+
+        transaction.execute("DELETE FROM shopping_recipes WHERE id = (?1);", (entry_id,))?;
+        aggregate::prune_checked(&transaction)?;
+
+        transaction.commit()?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[server]
+pub async fn clear_shopping_list() -> Result<(), NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Editor).await?;
+
+    db.write(move |db| {
+        db.execute_batch("DELETE FROM shopping_recipes; DELETE FROM shopping_checked;")?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Ticks an item off the list, or puts it back. Viewers may, it doesn't
+/// change what is on the list.
+#[server]
+pub async fn set_item_checked(key: String, checked: bool) -> Result<(), NomError> {
+    use crate::auth::{Role, require_role};
+    use crate::db::use_db;
+
+    let db = use_db();
+    require_role(&db, Role::Viewer).await?;
+
+    db.write(move |db| {
+        if checked {
+            db.execute(
+                "INSERT OR IGNORE INTO shopping_checked (item) VALUES (?1);",
+                (key,),
+            )?;
+        } else {
+            db.execute("DELETE FROM shopping_checked WHERE item = (?1);", (key,))?;
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Puts a recipe on the shopping list for the given number of servings.
+#[component]
+pub fn AddToListButton(recipe_id: i64, servings: Signal<Option<u32>>) -> impl IntoView {
+    let (added, set_added) = signal(false);
+    let error = RwSignal::new(None::<NomError>);
+
+    let add = move |_| {
+        let servings = servings.get_untracked();
+
+        spawn_local(async move {
+            match add_to_shopping_list(recipe_id, servings).await {
+                Ok(()) => set_added.set(true),
+                Err(err) => error.set(Some(err)),
+            }
+        });
+    };
+
+    move || {
+        if let Some(error) = error.get() {
+            view! { <ErrorMessage error/> }.into_any()
+        } else if added.get() {
+            view! { <A class:link-button href="/shopping">"Bekijk boodschappenlijst"</A> }
+                .into_any()
+        } else {
+            view! {
+                <button type="button" class:link-button on:click=add>"Op boodschappenlijst"</button>
+            }
+            .into_any()
+        }
+    }
+}
+
+/// Merging the ingredients of the recipes on the list into shopping items.
+#[cfg(feature = "ssr")]
+mod aggregate {
+    use std::collections::{HashMap, HashSet};
+
+    use super::{ShoppingItem, ShoppingSection};
+    use crate::recipe::{Ingredient, format_decimal, ingredient_matches, normalize_ingredient};
+
+    /// Store sections in walking order, with words for the ingredients found
+    /// there. Ingredients matching none of them end up in "Overig".
+    const SECTIONS: &[(&str, &[&str])] = &[
+        (
+            "Groente en fruit",
+            &[
+                "aardappel",
+                "aardbei",
+                "andijvie",
+                "appel",
+                "asperge",
+                "aubergine",
+                "avocado",
+                "banaan",
+                "basilicum",
+                "bes",
+                "biet",
+                "bieslook",
+                "bloemkool",
+                "boon",
+                "broccoli",
+                "champignon",
+                "chilipeper",
+                "citroen",
+                "courgette",
+                "dille",
+                "druif",
+                "erwt",
+                "framboos",
+                "gember",
+                "knoflook",
+                "komkommer",
+                "kool",
+                "koriander",
+                "limoen",
+                "mais",
+                "munt",
+                "paprika",
+                "peer",
+                "peterselie",
+                "pompoen",
+                "prei",
+                "radijs",
+                "rucola",
+                "selderij",
+                "sinaasappel",
+                "sla",
+                "spinazie",
+                "spruit",
+                "tomaat",
+                "ui",
+                "venkel",
+                "wortel",
+            ],
+        ),
+        (
+            "Vlees en vis",
+            &[
+                "bacon",
+                "biefstuk",
+                "chorizo",
+                "garnaal",
+                "gehakt",
+                "ham",
+                "kabeljauw",
+                "kalkoen",
+                "kip",
+                "lam",
+                "mossel",
+                "rund",
+                "spek",
+                "tonijn",
+                "varken",
+                "vis",
+                "worst",
+                "zalm",
+            ],
+        ),
+        (
+            "Zuivel en eieren",
+            &[
+                "boter",
+                "creme fraiche",
+                "ei",
+                "feta",
+                "kaas",
+                "karnemelk",
+                "kwark",
+                "mascarpone",
+                "melk",
+                "mozzarella",
+                "parmezaan",
+                "ricotta",
+                "room",
+                "yoghurt",
+            ],
+        ),
+        (
+            "Brood en bakkerij",
+            &[
+                "beschuit",
+                "brood",
+                "croissant",
+                "pita",
+                "stokbrood",
+                "tortilla",
+                "wrap",
+            ],
+        ),
+        (
+            "Kruiden en specerijen",
+            &[
+                "bouillon",
+                "chilipoeder",
+                "kaneel",
+                "kerrie",
+                "komijn",
+                "kurkuma",
+                "laurier",
+                "nootmuskaat",
+                "oregano",
+                "paprikapoeder",
+                "peper",
+                "rozemarijn",
+                "tijm",
+                "zout",
+            ],
+        ),
+        (
+            "Voorraadkast",
+            &[
+                "amandel",
+                "azijn",
+                "bakpoeder",
+                "bloem",
+                "bulgur",
+                "cacao",
+                "chocolade",
+                "couscous",
+                "gist",
+                "havermout",
+                "honing",
+                "ketchup",
+                "kikkererwt",
+                "linzen",
+                "macaroni",
+                "mayonaise",
+                "meel",
+                "mosterd",
+                "noedel",
+                "noot",
+                "olie",
+                "passata",
+                "pasta",
+                "pindakaas",
+                "quinoa",
+                "rijst",
+                "saus",
+                "sojasaus",
+                "spaghetti",
+                "suiker",
+                "tomatenpuree",
+                "vanille",
+                "wijn",
+            ],
+        ),
+    ];
+
+    const OTHER_SECTION: &str = "Overig";
+
+    /// The store section of an ingredient. The longest matching word wins, so
+    /// "paprikapoeder" is a spice rather than a vegetable.
+    fn section(words: &[String]) -> &'static str {
+        SECTIONS
+            .iter()
+            .flat_map(|(section, keywords)| keywords.iter().map(move |kw| (*section, *kw)))
+            .filter(|(_, keyword)| ingredient_matches(words, &normalize_ingredient(keyword)))
+            .max_by_key(|(_, keyword)| keyword.len())
+            .map_or(OTHER_SECTION, |(section, _)| section)
+    }
+
+    /// Units that can be summed with other units of the same kind, as their
+    /// base unit and how many of those they are.
+    fn base_unit(unit: &str) -> Option<(&'static str, f64)> {
+        match unit.to_lowercase().trim_end_matches('.') {
+            "mg" => Some(("g", 0.001)),
+            "g" | "gr" | "gram" => Some(("g", 1.0)),
+            "kg" | "kilo" | "kilogram" => Some(("g", 1000.0)),
+            "ml" => Some(("ml", 1.0)),
+            "cl" => Some(("ml", 10.0)),
+            "dl" => Some(("ml", 100.0)),
+            "l" | "liter" => Some(("ml", 1000.0)),
+            "el" | "eetlepel" | "eetlepels" | "tbsp" => Some(("el", 1.0)),
+            "tl" | "theelepel" | "theelepels" | "tsp" => Some(("tl", 1.0)),
+            _ => None,
+        }
+    }
+
+    /// Switches large metric amounts to the bigger unit: 1500 g is shown as
+    /// 1,5 kg.
+    fn display_unit(quantity: f64, unit: &str) -> (f64, &str) {
+        match unit {
+            "g" if quantity >= 1000.0 => (quantity / 1000.0, "kg"),
+            "ml" if quantity >= 1000.0 => (quantity / 1000.0, "l"),
+            _ => (quantity, unit),
+        }
+    }
+
+    struct Merged {
+        key: String,
+        words: Vec<String>,
+        name: String,
+        unit: Option<String>,
+        quantity: Option<f64>,
+        recipes: Vec<String>,
+    }
+
+    /// Merges ingredients with the same normalised name and a compatible
+    /// unit, summing their quantities. Keeps the order of first appearance.
+    fn merge(ingredients: Vec<(Ingredient, String)>) -> Vec<Merged> {
+        let mut merged: Vec<Merged> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+
+        for (ingredient, recipe) in ingredients {
+            let words = normalize_ingredient(&ingredient.name);
+
+            let (unit, factor, unit_key) = match ingredient.unit.as_deref() {
+                Some(unit) => match base_unit(unit) {
+                    Some((base, factor)) => (Some(base.to_string()), factor, base.to_string()),
+                    None => (
+                        Some(unit.to_string()),
+                        1.0,
+                        normalize_ingredient(unit).join(" "),
+                    ),
+                },
+                None => (None, 1.0, String::new()),
+            };
+
+            let key = format!("{}|{unit_key}", words.join(" "));
+            let quantity = ingredient.quantity.map(|q| q * factor);
+
+            match index.get(&key) {
+                Some(&i) => {
+                    let item = &mut merged[i];
+
+                    item.quantity = match (item.quantity, quantity) {
+                        (Some(a), Some(b)) => Some(a + b),
+                        (a, b) => a.or(b),
+                    };
+
+                    if !item.recipes.contains(&recipe) {
+                        item.recipes.push(recipe);
+                    }
+                }
+                None => {
+                    index.insert(key.clone(), merged.len());
+                    merged.push(Merged {
+                        key,
+                        words,
+                        name: ingredient.name,
+                        unit,
+                        quantity,
+                        recipes: vec![recipe],
+                    });
+                }
+            }
+        }
+
+        merged
+    }
+
+    /// The scaled ingredients of every recipe on the list, with the title of
+    /// their recipe.
+    fn load_ingredients(
+        conn: &rusqlite::Connection,
+    ) -> rusqlite::Result<Vec<(Ingredient, String)>> {
+        let mut ingredients_stmt = conn.prepare_cached(
+            "
+            SELECT ingredients.quantity, ingredients.unit, ingredients.name, recipes.title,
+                recipes.servings, shopping_recipes.servings
+            FROM shopping_recipes
+            JOIN recipes ON recipes.id = shopping_recipes.recipe
+            JOIN ingredients ON ingredients.recipe = recipes.id
+            ORDER BY shopping_recipes.id, ingredients.id;
+            ",
+        )?;
+
+        ingredients_stmt
+            .query_map((), |row| {
+                let ingredient = Ingredient {
+                    quantity: row.get(0)?,
+                    unit: row.get(1)?,
+                    name: row.get(2)?,
+                    note: None,
+                };
+
+                let original: Option<u32> = row.get(4)?;
+                let wanted: Option<u32> = row.get(5)?;
+                let factor = original
+                    .zip(wanted)
+                    .map_or(1.0, |(original, wanted)| wanted as f64 / original as f64);
+
+                Ok((ingredient.scaled(factor), row.get(3)?))
+            })?
+            .collect()
+    }
+
+    fn load_checked(conn: &rusqlite::Connection) -> rusqlite::Result<HashSet<String>> {
+        conn.prepare_cached("SELECT item FROM shopping_checked;")?
+            .query_map((), |row| row.get(0))?
+            .collect()
+    }
+
+    /// The items on the list, grouped by store section.
+    pub(super) fn load_sections(
+        conn: &rusqlite::Connection,
+    ) -> rusqlite::Result<Vec<ShoppingSection>> {
+        let checked = load_checked(conn)?;
+
+        let mut sections: Vec<ShoppingSection> = SECTIONS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain([OTHER_SECTION])
+            .map(|name| ShoppingSection {
+                name: name.to_string(),
+                items: Vec::new(),
+            })
+            .collect();
+
+        let mut items = merge(load_ingredients(conn)?);
+        items.sort_by_cached_key(|item| item.name.to_lowercase());
+
+        for item in items {
+            let section = section(&item.words);
+
+            let text = match (item.quantity, item.unit.as_deref()) {
+                (Some(quantity), Some(unit @ ("g" | "ml"))) => {
+                    let (quantity, unit) = display_unit(quantity, unit);
+                    format!("{} {unit} {}", format_decimal(quantity), item.name)
+                }
+                (quantity, unit) => Ingredient {
+                    quantity,
+                    unit: unit.map(str::to_string),
+                    name: item.name,
+                    note: None,
+                }
+                .to_string(),
+            };
+
+            sections
+                .iter_mut()
+                .find(|s| s.name == section)
+                .expect("Every section is listed")
+                .items
+                .push(ShoppingItem {
+                    checked: checked.contains(&item.key),
+                    key: item.key,
+                    text,
+                    recipes: item.recipes,
+                });
+        }
+
+        sections.retain(|section| !section.items.is_empty());
+
+        Ok(sections)
+    }
+
+    /// Forgets ticked off items that are no longer on the list, so they
+    /// aren't ticked off already when they come back later.
+    pub(super) fn prune_checked(transaction: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        let keys: HashSet<String> = merge(load_ingredients(transaction)?)
+            .into_iter()
+            .map(|item| item.key)
+            .collect();
+
+        let mut delete_stmt =
+            transaction.prepare_cached("DELETE FROM shopping_checked WHERE item = (?1);")?;
+
+        for checked in load_checked(transaction)? {
+            if !keys.contains(&checked) {
+                delete_stmt.execute((checked,))?;
+            }
+        }
+
+        Ok(())
+    }
+}
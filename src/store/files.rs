@@ -0,0 +1,455 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OptionalExtension, Transaction};
+
+use super::sqlite::not_updated;
+use super::{RecipeStore, SqliteStore, StoreError, StoreResult, Update};
+use crate::cooklang::format::{parse, write};
+use crate::db::Pool;
+use crate::export::collection::import_rows;
+use crate::export::{ExportDocument, ImportMode, ImportSummary};
+use crate::recipe::{
+    ListedRecipe, Recipe, SearchResult, create_recipe, delete_recipe_rows, insert_recipe,
+    insert_recipe_with_id, load_recipe, update_recipe_rows,
+};
+
+/// Recipes as Cooklang files in a directory, named after their id and title,
+/// e.g. `12-pannenkoeken.cook`.
+///
+/// The files are the recipes. The database keeps an index of them, for
+/// searching and for everything referring to recipes, like the meal plan.
+/// The index is brought in line with the files when the store is opened, so
+/// files changed outside of nom, e.g. by a `git pull`, show up after a
+/// restart:
+///
+/// - Changed files update their recipe, and are renamed if the title changed.
+/// - Files not named after a recipe, like a new `soep.cook` or
+///   `3-gangen.cook`, become new recipes and are renamed after their id.
+/// - Recipes without a file are written back, to be deleted in nom if that
+///   was meant. Deleting them here would take them out of meal plans too,
+///   e.g. when a file went missing in a `git checkout`.
+pub struct FileStore {
+    dir: PathBuf,
+    db: Pool,
+    index: SqliteStore,
+}
+
+impl FileStore {
+    pub async fn open(dir: impl Into<PathBuf>, db: Pool) -> Result<Self, StoreError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let synced = {
+            let dir = dir.clone();
+            db.write(move |db| sync(db, &dir)).await?
+        };
+
+        leptos::logging::log!("Keeping recipes in {}, found {synced}", dir.display());
+
+        Ok(FileStore {
+            dir,
+            index: SqliteStore::new(db.clone()),
+            db,
+        })
+    }
+}
+
+/// The recipe as it will be read back from its file, with the text of the
+/// file. Cooklang can't hold everything exactly as it was entered, so this
+/// is what gets stored, and the index matches the file.
+fn canonical(recipe: &Recipe) -> (String, Recipe) {
+    let text = write(recipe);
+    let recipe = parse(&text, "");
+
+    (text, recipe)
+}
+
+/// Whether the recipes differ in anything kept in their file.
+fn differs(stored: &Recipe, recipe: &Recipe) -> bool {
+    stored
+        != &Recipe {
+            images: stored.images.clone(),
+            version: stored.version,
+            ..recipe.clone()
+        }
+}
+
+/// The title in lower case with dashes between the words, to name files
+/// after.
+fn slug(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(8)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn file_name(id: i64, title: &str) -> String {
+    match slug(title).as_str() {
+        "" => format!("{id}.cook"),
+        slug => format!("{id}-{slug}.cook"),
+    }
+}
+
+/// The id a file is named after, as in `12-pannenkoeken.cook` or `12.cook`.
+/// Only if [`is_file_of`] that recipe, as titles can start with a number.
+fn file_id(name: &str) -> Option<i64> {
+    let (stem, extension) = name.rsplit_once('.')?;
+
+    if !extension.eq_ignore_ascii_case("cook") {
+        return None;
+    }
+
+    stem.split_once('-')
+        .map_or(stem, |(id, _)| id)
+        .parse()
+        .ok()
+        .filter(|&id| id > 0)
+}
+
+/// Whether `name` is the file of recipe `id`, being named after its title in
+/// the `indexed` titles. Recipes that aren't indexed yet, e.g. pulled in with
+/// git, are named after the `title` in their file. Other files starting with
+/// a number, like `3-gangen.cook`, are new recipes.
+fn is_file_of(name: &str, id: i64, indexed: &HashMap<i64, String>, title: &str) -> bool {
+    name == file_name(id, indexed.get(&id).map_or(title, String::as_str))
+}
+
+/// The titles of the recipes in the index, by id.
+fn indexed_titles(conn: &Connection) -> rusqlite::Result<HashMap<i64, String>> {
+    conn.prepare_cached("SELECT id, title FROM recipes;")?
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+fn indexed_title(conn: &Connection, id: i64) -> rusqlite::Result<Option<String>> {
+    conn.prepare_cached("SELECT title FROM recipes WHERE id = ?1;")?
+        .query_row((id,), |row| row.get(0))
+        .optional()
+}
+
+/// Names of the `.cook` files in `dir`, sorted.
+fn recipe_files(dir: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+
+        let is_cook_file = !name.starts_with('.')
+            && name
+                .rsplit_once('.')
+                .is_some_and(|(_, extension)| extension.eq_ignore_ascii_case("cook"));
+
+        if is_cook_file && entry.file_type()?.is_file() {
+            names.push(name);
+        }
+    }
+
+    names.sort();
+
+    Ok(names)
+}
+
+/// Removes the file of recipe `id`, named after its `title`. A file that is
+/// gone already is fine, a directory that is gone isn't.
+fn remove_file(dir: &Path, id: i64, title: &str) -> io::Result<()> {
+    match std::fs::remove_file(dir.join(file_name(id, title))) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound && dir.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+/// Writes the file of recipe `id`, replacing the one named after its
+/// `old_title`. It is only put in place once `commit` succeeds, so a failed
+/// commit leaves the file as it was. Should putting it in place fail after
+/// all, the next sync brings the index back in line with the file.
+fn commit_file(
+    dir: &Path,
+    id: i64,
+    old_title: Option<&str>,
+    title: &str,
+    text: &str,
+    commit: impl FnOnce() -> rusqlite::Result<()>,
+) -> Result<(), StoreError> {
+    let name = file_name(id, title);
+
+    // Written next to it first, so the file is never half written
+    let mut temporary = OsString::from(".");
+    temporary.push(&name);
+    temporary.push(".tmp");
+    let temporary = dir.join(temporary);
+
+    std::fs::write(&temporary, text)?;
+
+    if let Err(err) = commit() {
+        _ = std::fs::remove_file(&temporary);
+        return Err(err.into());
+    }
+
+    std::fs::rename(&temporary, dir.join(&name))?;
+
+    match old_title {
+        Some(old_title) if file_name(id, old_title) != name => Ok(remove_file(dir, id, old_title)?),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the file of recipe `id` right away, see [`commit_file`].
+fn write_file(
+    dir: &Path,
+    id: i64,
+    old_title: Option<&str>,
+    title: &str,
+    text: &str,
+) -> Result<(), StoreError> {
+    commit_file(dir, id, old_title, title, text, || Ok(()))
+}
+
+/// Writes the file of recipe `id` from the index, which has no file.
+fn write_back(transaction: &Transaction, dir: &Path, id: i64) -> Result<(), StoreError> {
+    let Some(stored) = load_recipe(transaction, id)? else {
+        return Ok(());
+    };
+
+    let (text, recipe) = canonical(&stored);
+
+    if differs(&stored, &recipe) {
+        update_recipe_rows(transaction, id, &recipe, None, None)?;
+    }
+
+    write_file(dir, id, None, &recipe.title, &text)
+}
+
+/// Writes the recipes of the index to files, for a new directory. Returns
+/// how many there are.
+fn write_all(transaction: &Transaction, dir: &Path) -> Result<usize, StoreError> {
+    let ids = transaction
+        .prepare_cached("SELECT id FROM recipes ORDER BY id;")?
+        .query_map((), |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for &id in &ids {
+        write_back(transaction, dir, id)?;
+    }
+
+    Ok(ids.len())
+}
+
+/// Brings the index in line with the files in `dir`, see [`FileStore`].
+/// Returns how many recipes there are.
+fn sync(db: &mut Connection, dir: &Path) -> Result<usize, StoreError> {
+    let names = recipe_files(dir)?;
+    let transaction = db.transaction()?;
+
+    if names.is_empty() {
+        let written = write_all(&transaction, dir)?;
+        transaction.commit()?;
+
+        return Ok(written);
+    }
+
+    let indexed = indexed_titles(&transaction)?;
+
+    let mut seen = HashSet::new();
+    let mut new = Vec::new();
+    let mut retitled = Vec::new();
+
+    for name in names {
+        let recipe = parse(&std::fs::read_to_string(dir.join(&name))?, &name);
+
+        // A second file with the same id, e.g. after merging in git, is a
+        // recipe of its own
+        let Some(id) = file_id(&name)
+            .filter(|&id| is_file_of(&name, id, &indexed, &recipe.title))
+            .filter(|&id| seen.insert(id))
+        else {
+            new.push((name, recipe));
+            continue;
+        };
+
+        if !indexed.contains_key(&id) {
+            insert_recipe_with_id(&transaction, Some(id), &recipe)?;
+        } else if load_recipe(&transaction, id)?.is_none_or(|stored| differs(&stored, &recipe)) {
+            update_recipe_rows(&transaction, id, &recipe, None, None)?;
+        }
+
+        // The title was changed in the file, it's found by the new one next
+        // time
+        let current = file_name(id, &recipe.title);
+        if name != current {
+            retitled.push((name, current));
+        }
+    }
+
+    // After all files with an id, so their ids aren't given away
+    for (name, recipe) in new {
+        let id = insert_recipe(&transaction, &recipe)?;
+        seen.insert(id);
+
+        std::fs::rename(dir.join(name), dir.join(file_name(id, &recipe.title)))?;
+    }
+
+    // After the new ones, which may have had the name
+    for (name, current) in retitled {
+        std::fs::rename(dir.join(name), dir.join(current))?;
+    }
+
+    let mut missing: Vec<_> = indexed
+        .keys()
+        .copied()
+        .filter(|id| !seen.contains(id))
+        .collect();
+    missing.sort();
+
+    for &id in &missing {
+        leptos::logging::warn!("Recipe {id} has no file, writing it back");
+        write_back(&transaction, dir, id)?;
+    }
+
+    transaction.commit()?;
+
+    Ok(seen.len() + missing.len())
+}
+
+impl RecipeStore for FileStore {
+    fn create(&self, recipe: Recipe, user_id: Option<i64>) -> StoreResult<'_, i64> {
+        let dir = self.dir.clone();
+
+        Box::pin(self.db.write(move |db| {
+            let (text, recipe) = canonical(&recipe);
+
+            let transaction = db.transaction()?;
+            let id = create_recipe(&transaction, &recipe, user_id)?;
+            commit_file(&dir, id, None, &recipe.title, &text, || {
+                transaction.commit()
+            })?;
+
+            Ok(id)
+        }))
+    }
+
+    fn get(&self, id: i64) -> StoreResult<'_, Option<Recipe>> {
+        self.index.get(id)
+    }
+
+    fn update(
+        &self,
+        id: i64,
+        recipe: Recipe,
+        version: Option<u32>,
+        user_id: Option<i64>,
+    ) -> StoreResult<'_, Update> {
+        let dir = self.dir.clone();
+
+        Box::pin(self.db.write(move |db| {
+            let (text, recipe) = canonical(&recipe);
+
+            let transaction = db.transaction()?;
+            let old_title = indexed_title(&transaction, id)?;
+
+            if !update_recipe_rows(&transaction, id, &recipe, version, user_id)? {
+                return Ok(not_updated(&transaction, id)?);
+            }
+
+            commit_file(&dir, id, old_title.as_deref(), &recipe.title, &text, || {
+                transaction.commit()
+            })?;
+
+            Ok(load_recipe(db, id)?.map_or(Update::UnknownRecipe, Update::Updated))
+        }))
+    }
+
+    fn delete(&self, id: i64) -> StoreResult<'_, bool> {
+        let dir = self.dir.clone();
+
+        Box::pin(self.db.write(move |db| {
+            let Some(title) = indexed_title(db, id)? else {
+                return Ok(false);
+            };
+
+            let transaction = db.transaction()?;
+            let image_ids = delete_recipe_rows(&transaction, id)?;
+
+            // Before committing, so the recipe stays if the file does. Should
+            // committing fail, the next sync writes the file back.
+            remove_file(&dir, id, &title)?;
+            transaction.commit()?;
+
+            crate::images::storage::remove(&image_ids);
+
+            Ok(true)
+        }))
+    }
+
+    fn list(
+        &self,
+        tags: Vec<String>,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> StoreResult<'_, Vec<ListedRecipe>> {
+        self.index.list(tags, limit, offset)
+    }
+
+    fn count(&self, tags: Vec<String>) -> StoreResult<'_, u32> {
+        self.index.count(tags)
+    }
+
+    fn random(&self, tags: Vec<String>) -> StoreResult<'_, Option<i64>> {
+        self.index.random(tags)
+    }
+
+    fn search(&self, query: String, tags: Vec<String>) -> StoreResult<'_, Vec<SearchResult>> {
+        self.index.search(query, tags)
+    }
+
+    fn import(
+        &self,
+        mut document: ExportDocument,
+        mode: ImportMode,
+    ) -> StoreResult<'_, ImportSummary> {
+        let dir = self.dir.clone();
+
+        for recipe in &mut document.recipes {
+            let (_, canonical) = canonical(&recipe.clone().into());
+            *recipe = canonical.into();
+        }
+
+        Box::pin(self.db.write(move |db| {
+            let transaction = db.transaction()?;
+            let titles = indexed_titles(&transaction)?;
+            let imported = import_rows(&transaction, document, mode)?;
+
+            for &id in &imported.created {
+                if let Some(recipe) = load_recipe(&transaction, id)? {
+                    let old_title = titles.get(&id).map(String::as_str);
+                    write_file(&dir, id, old_title, &recipe.title, &write(&recipe))?;
+                }
+            }
+
+            transaction.commit()?;
+
+            crate::images::storage::remove(&imported.removed_images);
+
+            // A replacing import can give the new recipes the ids of the
+            // deleted ones, their files are replaced already
+            for id in imported.deleted {
+                if let Some(title) = titles.get(&id)
+                    && !imported.created.contains(&id)
+                {
+                    remove_file(&dir, id, title)?;
+                }
+            }
+
+            Ok(imported.summary)
+        }))
+    }
+}
@@ -0,0 +1,126 @@
+//! Where the recipes are kept.
+//!
+//! By default that's the database, see [`SqliteStore`]. With `NOM_RECIPES`
+//! set, recipes are kept as Cooklang files in that directory instead, one
+//! file per recipe, so they can be versioned with git. See [`FileStore`].
+//!
+//! Everything else, like the meal plan and the images, is always in the
+//! database.
+
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::db::Pool;
+use crate::export::{ExportDocument, ImportMode, ImportSummary};
+use crate::recipe::{ListedRecipe, Recipe, SearchResult};
+
+mod files;
+mod sqlite;
+
+pub use files::FileStore;
+pub use sqlite::SqliteStore;
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(err) => write!(f, "database error: {err}"),
+            StoreError::Io(err) => write!(f, "could not access recipe files: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Sqlite(err)
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(err: std::io::Error) -> Self {
+        StoreError::Io(err)
+    }
+}
+
+pub type StoreResult<'a, T> = Pin<Box<dyn Future<Output = Result<T, StoreError>> + Send + 'a>>;
+
+/// What came of [`RecipeStore::update`].
+#[derive(Debug)]
+pub enum Update {
+    /// With the recipe as stored
+    Updated(Recipe),
+    /// The recipe changed since the version the update started from. With
+    /// the recipe as it is now.
+    Conflict(Recipe),
+    UnknownRecipe,
+}
+
+/// Storing and finding recipes. Recipes are validated before they get here.
+pub trait RecipeStore: Send + Sync {
+    /// Stores a new recipe written by `user_id`, returning its id.
+    fn create(&self, recipe: Recipe, user_id: Option<i64>) -> StoreResult<'_, i64>;
+
+    fn get(&self, id: i64) -> StoreResult<'_, Option<Recipe>>;
+
+    /// Replaces everything of a recipe but its images, as edited by
+    /// `user_id`, if it's still at `version`. Without a version it's
+    /// replaced regardless.
+    fn update(
+        &self,
+        id: i64,
+        recipe: Recipe,
+        version: Option<u32>,
+        user_id: Option<i64>,
+    ) -> StoreResult<'_, Update>;
+
+    /// Deletes a recipe and everything referring to it. Returns whether it
+    /// existed.
+    fn delete(&self, id: i64) -> StoreResult<'_, bool>;
+
+    /// The recipes having every one of `tags`, in the order they were added,
+    /// skipping `offset` and returning at most `limit` of them.
+    fn list(
+        &self,
+        tags: Vec<String>,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> StoreResult<'_, Vec<ListedRecipe>>;
+
+    /// How many recipes have every one of `tags`.
+    fn count(&self, tags: Vec<String>) -> StoreResult<'_, u32>;
+
+    /// The id of a random recipe having every one of `tags`.
+    fn random(&self, tags: Vec<String>) -> StoreResult<'_, Option<i64>>;
+
+    /// The best matches for `query` among the recipes having every one of
+    /// `tags`.
+    fn search(&self, query: String, tags: Vec<String>) -> StoreResult<'_, Vec<SearchResult>>;
+
+    /// Adds the recipes of an export, see [`ImportMode`].
+    fn import(&self, document: ExportDocument, mode: ImportMode) -> StoreResult<'_, ImportSummary>;
+}
+
+/// Cheap to clone, all clones share the same store.
+pub type Store = Arc<dyn RecipeStore>;
+
+/// The recipe store of the current request, for server functions.
+pub fn use_store() -> Store {
+    leptos::prelude::expect_context()
+}
+
+/// The store `NOM_RECIPES` asks for, keeping its index in `db` if it's
+/// a directory.
+pub async fn from_env(db: Pool) -> Result<Store, StoreError> {
+    Ok(match std::env::var_os("NOM_RECIPES") {
+        Some(dir) => Arc::new(FileStore::open(dir, db).await?),
+        None => Arc::new(SqliteStore::new(db)),
+    })
+}
@@ -0,0 +1,138 @@
+use rusqlite::Connection;
+
+use super::{RecipeStore, StoreResult, Update};
+use crate::db::Pool;
+use crate::export::collection::import_collection;
+use crate::export::{ExportDocument, ImportMode, ImportSummary};
+use crate::recipe::{
+    ListedRecipe, Recipe, SearchResult, count_recipes, create_recipe, delete_recipe_rows,
+    load_recipe, pick_random, query_recipes, recipe_exists, search, update_recipe_rows,
+};
+
+/// Recipes in the database, next to everything else.
+pub struct SqliteStore {
+    db: Pool,
+}
+
+impl SqliteStore {
+    pub fn new(db: Pool) -> Self {
+        SqliteStore { db }
+    }
+}
+
+/// Why [`update_recipe_rows`] didn't update recipe `id`.
+pub(super) fn not_updated(conn: &Connection, id: i64) -> rusqlite::Result<Update> {
+    Ok(load_recipe(conn, id)?.map_or(Update::UnknownRecipe, Update::Conflict))
+}
+
+impl RecipeStore for SqliteStore {
+    fn create(&self, recipe: Recipe, user_id: Option<i64>) -> StoreResult<'_, i64> {
+        Box::pin(async move {
+            let id = self
+                .db
+                .write(move |db| -> rusqlite::Result<_> {
+                    let transaction = db.transaction()?;
+                    let id = create_recipe(&transaction, &recipe, user_id)?;
+                    transaction.commit()?;
+
+                    Ok(id)
+                })
+                .await?;
+
+            Ok(id)
+        })
+    }
+
+    fn get(&self, id: i64) -> StoreResult<'_, Option<Recipe>> {
+        Box::pin(async move { Ok(self.db.read(move |db| load_recipe(db, id)).await?) })
+    }
+
+    fn update(
+        &self,
+        id: i64,
+        recipe: Recipe,
+        version: Option<u32>,
+        user_id: Option<i64>,
+    ) -> StoreResult<'_, Update> {
+        Box::pin(async move {
+            let update = self
+                .db
+                .write(move |db| -> rusqlite::Result<_> {
+                    let transaction = db.transaction()?;
+
+                    if !update_recipe_rows(&transaction, id, &recipe, version, user_id)? {
+                        return not_updated(&transaction, id);
+                    }
+
+                    transaction.commit()?;
+
+                    Ok(load_recipe(db, id)?.map_or(Update::UnknownRecipe, Update::Updated))
+                })
+                .await?;
+
+            Ok(update)
+        })
+    }
+
+    fn delete(&self, id: i64) -> StoreResult<'_, bool> {
+        Box::pin(async move {
+            let image_ids = self
+                .db
+                .write(move |db| -> rusqlite::Result<_> {
+                    if !recipe_exists(db, id)? {
+                        return Ok(None);
+                    }
+
+                    let transaction = db.transaction()?;
+                    let image_ids = delete_recipe_rows(&transaction, id)?;
+                    transaction.commit()?;
+
+                    Ok(Some(image_ids))
+                })
+                .await?;
+
+            let Some(image_ids) = image_ids else {
+                return Ok(false);
+            };
+
+            crate::images::storage::remove(&image_ids);
+
+            Ok(true)
+        })
+    }
+
+    fn list(
+        &self,
+        tags: Vec<String>,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> StoreResult<'_, Vec<ListedRecipe>> {
+        Box::pin(async move {
+            Ok(self
+                .db
+                .read(move |db| query_recipes(db, &tags, limit, offset))
+                .await?)
+        })
+    }
+
+    fn count(&self, tags: Vec<String>) -> StoreResult<'_, u32> {
+        Box::pin(async move { Ok(self.db.read(move |db| count_recipes(db, &tags)).await?) })
+    }
+
+    fn random(&self, tags: Vec<String>) -> StoreResult<'_, Option<i64>> {
+        Box::pin(async move { Ok(self.db.read(move |db| pick_random(db, &tags)).await?) })
+    }
+
+    fn search(&self, query: String, tags: Vec<String>) -> StoreResult<'_, Vec<SearchResult>> {
+        Box::pin(async move { Ok(self.db.read(move |db| search(db, &query, &tags)).await?) })
+    }
+
+    fn import(&self, document: ExportDocument, mode: ImportMode) -> StoreResult<'_, ImportSummary> {
+        Box::pin(async move {
+            Ok(self
+                .db
+                .write(move |db| import_collection(db, document, mode))
+                .await?)
+        })
+    }
+}
@@ -0,0 +1,258 @@
+use leptos::prelude::*;
+use leptos_router::components::A;
+
+/// Tags are compared case-insensitively and without surrounding whitespace,
+/// so "Soep " and "soep" are the same tag.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Normalises and deduplicates a list of tags, dropping empty ones.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|tag| normalize_tag(tag))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+/// Href of the home page filtered on `tags`.
+pub fn tags_href(tags: &[String]) -> String {
+    if tags.is_empty() {
+        "/".to_string()
+    } else {
+        let param = tags
+            .iter()
+            .map(|tag| percent_encode(&escape_tag(tag)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!("/?tags={param}")
+    }
+}
+
+/// Parses the comma-separated `tags` query parameter, as made by
+/// [`tags_href`].
+pub fn parse_tags_param(param: Option<String>) -> Vec<String> {
+    param
+        .map(|param| normalize_tags(&param.split(',').map(unescape_tag).collect::<Vec<String>>()))
+        .unwrap_or_default()
+}
+
+/// Escapes the commas in a tag, which separate the tags in the query
+/// parameter, and the `%` they are escaped with. Imported tags can contain
+/// commas.
+fn escape_tag(tag: &str) -> String {
+    tag.replace('%', "%25").replace(',', "%2C")
+}
+
+fn unescape_tag(escaped: &str) -> String {
+    let mut tag = String::with_capacity(escaped.len());
+    let mut rest = escaped;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("%2C").or(rest.strip_prefix("%2c")) {
+            tag.push(',');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("%25") {
+            tag.push('%');
+            rest = after;
+        } else {
+            tag.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    tag
+}
+
+/// Percent-encodes everything but the unreserved characters of a URL, so
+/// tags with `&`, `#` or spaces stay in the query parameter.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// SQL condition that holds for recipes having all tags in the JSON array
+/// bound to `?{param}`. An empty array matches every recipe.
+#[cfg(feature = "ssr")]
+pub(crate) fn has_all_tags_sql(param: usize) -> String {
+    format!(
+        "(SELECT count(*) FROM recipe_tags JOIN tags ON tags.id = recipe_tags.tag
+          WHERE recipe_tags.recipe = recipes.id
+          AND tags.name IN (SELECT value FROM json_each(?{param}))) = json_array_length(?{param})"
+    )
+}
+
+/// Encodes tags as a JSON array, for use with [`has_all_tags_sql`].
+#[cfg(feature = "ssr")]
+pub(crate) fn tags_json(tags: &[String]) -> String {
+    serde_json::to_string(&normalize_tags(tags)).expect("Strings always serialize")
+}
+
+/// Replaces the tags of a recipe, creating new tags and removing unused ones.
+#[cfg(feature = "ssr")]
+pub(crate) fn set_recipe_tags(
+    transaction: &rusqlite::Transaction,
+    recipe_id: i64,
+    tags: &[String],
+) -> rusqlite::Result<()> {
+    transaction.execute("DELETE FROM recipe_tags WHERE recipe = (?1);", (recipe_id,))?;
+
+    {
+        let mut new_tag_stmt =
+            transaction.prepare_cached("INSERT OR IGNORE INTO tags (name) VALUES (?1);")?;
+        let mut link_tag_stmt = transaction.prepare_cached(
+            "INSERT INTO recipe_tags (recipe, tag) SELECT ?1, id FROM tags WHERE name = ?2;",
+        )?;
+
+        for tag in normalize_tags(tags) {
+            new_tag_stmt.execute((&tag,))?;
+            link_tag_stmt.execute((recipe_id, &tag))?;
+        }
+    }
+
+    transaction.execute(
+        "DELETE FROM tags WHERE id NOT IN (SELECT tag FROM recipe_tags);",
+        (),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) fn recipe_tags(
+    conn: &rusqlite::Connection,
+    recipe_id: i64,
+) -> rusqlite::Result<Vec<String>> {
+    let mut tags_stmt = conn.prepare_cached(
+        "
+        SELECT tags.name FROM recipe_tags JOIN tags ON tags.id = recipe_tags.tag
+        WHERE recipe_tags.recipe = (?1) ORDER BY tags.name;
+        ",
+    )?;
+
+    tags_stmt
+        .query_map((recipe_id,), |row| row.get(0))?
+        .collect()
+}
+
+/// All tags in use, alphabetically.
+#[server]
+pub async fn list_tags() -> Result<Vec<String>, ServerFnError> {
+    use crate::db::use_db;
+
+    let tags = use_db()
+        .read(|db| {
+            db.prepare_cached("SELECT name FROM tags ORDER BY name;")?
+                .query_map((), |row| row.get(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await?;
+
+    Ok(tags)
+}
+
+/// Tags of a recipe, each linking to the home page filtered on it.
+#[component]
+pub fn TagChips(tags: Vec<String>) -> impl IntoView {
+    (!tags.is_empty()).then(|| {
+        view! {
+            <ul class="tag-list">
+                {tags.into_iter().map(|tag| {
+                    let href = tags_href(std::slice::from_ref(&tag));
+
+                    view! { <li><A class:tag-chip href=href>{tag}</A></li> }
+                }).collect_view()}
+            </ul>
+        }
+    })
+}
+
+/// Edits a list of tags, suggesting the tags that already exist.
+#[component]
+pub fn TagInput(tags: RwSignal<Vec<String>>) -> impl IntoView {
+    let existing = Resource::new(|| (), async |_| list_tags().await.unwrap_or_default());
+    let (pending, set_pending) = signal(String::new());
+
+    let add_pending = move || {
+        let tag = normalize_tag(&pending.get_untracked());
+
+        if !tag.is_empty() {
+            tags.update(|tags| {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            });
+        }
+
+        set_pending.set(String::new());
+    };
+
+    let remove = move |tag: String| tags.update(|tags| tags.retain(|t| *t != tag));
+
+    view! {
+        <div class="tag-input">
+            <ul class="tag-list">
+                {move || tags.get().into_iter().map(|tag| {
+                    let to_remove = tag.clone();
+
+                    view! {
+                        <li class="tag-chip">
+                            {tag}
+                            <button
+                                type="button"
+                                class="tag-remove"
+                                on:click=move |_| remove(to_remove.clone())
+                            >"×"</button>
+                        </li>
+                    }
+                }).collect_view()}
+            </ul>
+            <input
+                type="text"
+                placeholder="Voeg een tag toe"
+                list="tag-suggestions"
+                prop:value=pending
+                on:input=move |ev| {
+                    let value = event_target_value(&ev);
+
+                    // A comma finishes the tag, like enter does
+                    if let Some(tag) = value.strip_suffix(',') {
+                        set_pending.set(tag.to_string());
+                        add_pending();
+                    } else {
+                        set_pending.set(value);
+                    }
+                }
+                on:keydown=move |ev| {
+                    if ev.key() == "Enter" {
+                        ev.prevent_default();
+                        add_pending();
+                    }
+                }
+                on:change=move |_| add_pending()
+            />
+            <datalist id="tag-suggestions">
+                <Suspense>
+                    {move || existing.get().map(|existing| {
+                        existing.into_iter().map(|tag| view! { <option value=tag/> }).collect_view()
+                    })}
+                </Suspense>
+            </datalist>
+        </div>
+    }
+}
@@ -0,0 +1,259 @@
+#![cfg(feature = "ssr")]
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::Body;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{Method, Request, StatusCode};
+use leptos::config::LeptosOptions;
+use leptos::server_fn::ServerFn;
+use nom::auth::throttle::{LoginThrottle, ThrottleConfig};
+use nom::db::Pool;
+use nom::error::NomError;
+use nom::images::UploadImages;
+use nom::recipe::{
+    DeleteRecipe, GetRecipe, ListRecipes, ListedRecipe, NewRecipe, Recipe, SearchRecipes,
+    SearchResult, UpdateRecipe,
+};
+use nom::server::{AppState, router};
+use nom::store::SqliteStore;
+use serde::de::DeserializeOwned;
+use tower::ServiceExt;
+
+/// The whole app, with an empty database of its own.
+fn app() -> Router {
+    let db = Pool::open(":memory:", 1).unwrap();
+
+    router(AppState {
+        leptos_options: LeptosOptions::builder().output_name("nom_front").build(),
+        store: Arc::new(SqliteStore::new(db.clone())),
+        db,
+        throttle: LoginThrottle::new(ThrottleConfig::from_env()),
+    })
+}
+
+async fn send(app: &Router, method: Method, uri: &str, form: &str) -> (StatusCode, String) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form.to_string()))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Calls the server function `F` with its arguments URL encoded in `form`.
+async fn call<F: ServerFn, T: DeserializeOwned>(app: &Router, form: &str) -> Result<T, NomError> {
+    let (status, body) = send(app, Method::POST, F::PATH, form).await;
+
+    if status.is_success() {
+        Ok(serde_json::from_str(&body).unwrap())
+    } else {
+        Err(serde_json::from_str(&body).unwrap())
+    }
+}
+
+const PANCAKES: &str = "raw_recipe[title]=Pannenkoeken&raw_recipe[servings]=4\
+    &raw_recipe[ingredients]=250+g+bloem%0A0.5+l+melk&raw_recipe[instructions]=Bakken";
+
+#[tokio::test]
+async fn recipe_lifecycle() {
+    let app = app();
+
+    let id: i64 = call::<NewRecipe, _>(&app, PANCAKES).await.unwrap();
+
+    let recipe: Recipe = call::<GetRecipe, _>(&app, &format!("id={id}"))
+        .await
+        .unwrap();
+    assert_eq!("Pannenkoeken", recipe.title);
+    assert_eq!(Some(4), recipe.servings);
+    assert_eq!(2, recipe.ingredients.len());
+    assert_eq!("melk", recipe.ingredients[1].name);
+
+    let listed: Vec<ListedRecipe> = call::<ListRecipes, _>(&app, "").await.unwrap();
+    assert_eq!(vec![(id, "Pannenkoeken")], titles(&listed));
+
+    let updated: Recipe = call::<UpdateRecipe, _>(
+        &app,
+        &format!(
+            "recipe_id={id}&version=1&raw_recipe[title]=Flensjes\
+             &raw_recipe[ingredients]=250+g+bloem&raw_recipe[instructions]=Dun+bakken"
+        ),
+    )
+    .await
+    .unwrap();
+    assert_eq!("Flensjes", updated.title);
+    assert_eq!(None, updated.servings);
+    assert_eq!(2, updated.version);
+
+    let found: Vec<SearchResult> = call::<SearchRecipes, _>(&app, "query=dun").await.unwrap();
+    assert_eq!(
+        vec![id],
+        found.iter().map(|found| found.id).collect::<Vec<_>>()
+    );
+
+    // The page is rendered from the same database
+    let (status, page) = send(&app, Method::GET, &format!("/recipe/{id}"), "").await;
+    assert_eq!(StatusCode::OK, status);
+    assert!(page.contains("Flensjes"));
+
+    call::<DeleteRecipe, ()>(&app, &format!("recipe_id={id}"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        Err(NomError::NotFound(format!("recipe {id}"))),
+        call::<GetRecipe, Recipe>(&app, &format!("id={id}")).await
+    );
+    assert_eq!(
+        Err(NomError::NotFound(format!("recipe {id}"))),
+        call::<DeleteRecipe, ()>(&app, &format!("recipe_id={id}")).await
+    );
+}
+
+#[tokio::test]
+async fn invalid_recipes_are_refused() {
+    let app = app();
+
+    assert_eq!(
+        Err(NomError::Validation("Recipes need a title".to_string())),
+        call::<NewRecipe, i64>(
+            &app,
+            "raw_recipe[title]=+&raw_recipe[ingredients]=&raw_recipe[instructions]="
+        )
+        .await
+    );
+}
+
+#[tokio::test]
+async fn stale_edits_are_refused() {
+    let app = app();
+
+    let id: i64 = call::<NewRecipe, _>(&app, PANCAKES).await.unwrap();
+    let edit = |title: &str| {
+        format!(
+            "recipe_id={id}&version=1&raw_recipe[title]={title}\
+             &raw_recipe[ingredients]=250+g+bloem&raw_recipe[instructions]=Bakken"
+        )
+    };
+
+    call::<UpdateRecipe, Recipe>(&app, &edit("Flensjes"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        Err(NomError::Stale(format!(
+            "Recipe {id} was changed by someone else"
+        ))),
+        call::<UpdateRecipe, Recipe>(&app, &edit("Poffertjes")).await
+    );
+
+    let recipe: Recipe = call::<GetRecipe, _>(&app, &format!("id={id}"))
+        .await
+        .unwrap();
+    assert_eq!("Flensjes", recipe.title);
+    assert_eq!(2, recipe.version);
+}
+
+#[tokio::test]
+async fn api_replacements_need_the_current_version() {
+    let app = app();
+
+    let id: i64 = call::<NewRecipe, _>(&app, PANCAKES).await.unwrap();
+    let replace = |body: &'static str| {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri(format!("/api/v1/recipes/{id}"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        app.clone().oneshot(request)
+    };
+
+    let response = replace(r#"{"title": "Poffertjes"}"#).await.unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = replace(r#"{"title": "Flensjes", "version": 1}"#)
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = replace(r#"{"title": "Poffertjes", "version": 1}"#)
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CONFLICT, response.status());
+
+    let recipe: Recipe = call::<GetRecipe, _>(&app, &format!("id={id}"))
+        .await
+        .unwrap();
+    assert_eq!("Flensjes", recipe.title);
+    assert_eq!(2, recipe.version);
+}
+
+#[tokio::test]
+async fn large_uploads_are_refused() {
+    let app = app();
+
+    let id: i64 = call::<NewRecipe, _>(&app, PANCAKES).await.unwrap();
+
+    let upload = |image_size: usize| {
+        let mut body = format!(
+            "--grens\r\nContent-Disposition: form-data; name=\"recipe\"\r\n\r\n{id}\r\n\
+             --grens\r\nContent-Disposition: form-data; name=\"image\"; filename=\"foto.jpg\"\r\n\
+             Content-Type: image/jpeg\r\n\r\n"
+        )
+        .into_bytes();
+        body.resize(body.len() + image_size, 0);
+        body.extend(b"\r\n--grens--\r\n");
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(UploadImages::PATH)
+            .header(CONTENT_TYPE, "multipart/form-data; boundary=grens")
+            .header(CONTENT_LENGTH, body.len())
+            .body(Body::from(body))
+            .unwrap();
+
+        app.clone().oneshot(request)
+    };
+
+    let response = upload(21 * 1024 * 1024).await.unwrap();
+    assert!(!response.status().is_success());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("at most 20 MB"));
+
+    let response = upload(65 * 1024 * 1024).await.unwrap();
+    assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, response.status());
+}
+
+#[tokio::test]
+async fn instances_have_their_own_database() {
+    let first = app();
+    let second = app();
+
+    call::<NewRecipe, i64>(&first, PANCAKES).await.unwrap();
+
+    let listed: Vec<ListedRecipe> = call::<ListRecipes, _>(&second, "").await.unwrap();
+    assert!(listed.is_empty());
+
+    let listed: Vec<ListedRecipe> = call::<ListRecipes, _>(&first, "").await.unwrap();
+    assert_eq!(1, listed.len());
+}
+
+fn titles(listed: &[ListedRecipe]) -> Vec<(i64, &str)> {
+    listed
+        .iter()
+        .map(|recipe| (recipe.id, recipe.title.as_str()))
+        .collect()
+}
@@ -0,0 +1,697 @@
+#![cfg(feature = "ssr")]
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::Body;
+use axum::http::header::{ACCEPT, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE, WWW_AUTHENTICATE};
+use axum::http::{Method, Request, StatusCode};
+use axum::response::Response;
+use axum_extra::headers::{Authorization, HeaderMapExt};
+use leptos::config::LeptosOptions;
+use leptos::server_fn::ServerFn;
+use nom::auth::store::{
+    create_api_token, create_first_admin, create_session, create_user, delete_api_token,
+    hash_password, list_api_tokens, list_users, set_password_hash,
+};
+use nom::auth::throttle::{LoginThrottle, ThrottleConfig};
+use nom::auth::tokens::{CreateApiToken, ListApiTokens, RevokeApiToken, TokenAuth, TokenScope};
+use nom::auth::{Login, Role, SESSION_COOKIE, SetPassword};
+use nom::db::Pool;
+use nom::plan::{AddMeal, AutofillWeek, MoveMeal, RemoveMeal};
+use nom::recipe::NewRecipe;
+use nom::server::{AppState, router};
+use nom::share::links;
+use nom::share::{CreateShareLink, GetSharedRecipe, RevokeShareLink, ShareLink};
+use nom::shopping::{
+    AddPlanToShoppingList, AddToShoppingList, ClearShoppingList, RemoveFromShoppingList,
+    SetItemChecked,
+};
+use nom::store::SqliteStore;
+use tower::ServiceExt;
+
+const PASSWORD: &str = "correct horse";
+
+/// Locks out after a single failure, so tests don't spend their time hashing.
+fn strict() -> ThrottleConfig {
+    ThrottleConfig {
+        max_failures: 1,
+        lockout_secs: 60,
+        persist: false,
+    }
+}
+
+/// The whole app, with a database of its own and an admin `anna`.
+async fn app(config: ThrottleConfig) -> (Router, Pool) {
+    let db = Pool::open(":memory:", 1).unwrap();
+
+    db.write(|db| create_user(db, "anna", &hash_password(PASSWORD), Role::Admin))
+        .await
+        .unwrap();
+
+    let app = router(AppState {
+        leptos_options: LeptosOptions::builder().output_name("nom_front").build(),
+        store: Arc::new(SqliteStore::new(db.clone())),
+        db: db.clone(),
+        throttle: LoginThrottle::new(config),
+    });
+
+    (app, db)
+}
+
+/// Gets `uri` with Basic auth.
+async fn get_as(app: &Router, uri: &str, username: &str, password: &str) -> StatusCode {
+    let mut request = Request::builder()
+        .method(Method::GET)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap();
+    request
+        .headers_mut()
+        .typed_insert(Authorization::basic(username, password));
+
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+/// Adds a user that is logged in, returning the cookie of their session.
+async fn session(db: &Pool, username: &str, role: Role) -> String {
+    let username = username.to_string();
+
+    let token = db
+        .write(move |db| {
+            // Never checked, the session is what logs them in
+            let user_id = create_user(db, &username, "no hash", role)?;
+            create_session(db, user_id)
+        })
+        .await
+        .unwrap();
+
+    format!("{SESSION_COOKIE}={token}")
+}
+
+/// Gets `uri` without any credentials but the session `cookie`, if given.
+async fn get(app: &Router, uri: &str, cookie: Option<&str>, accept: &str) -> Response {
+    let mut request = Request::builder()
+        .method(Method::GET)
+        .uri(uri)
+        .header(ACCEPT, accept);
+
+    if let Some(cookie) = cookie {
+        request = request.header(COOKIE, cookie);
+    }
+
+    app.clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+/// Logs in with the form, returning the session cookie if that worked.
+async fn login(app: &Router, username: &str, password: &str) -> Result<String, String> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(Login::PATH)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(format!(
+            "username={username}&password={}",
+            password.replace(' ', "+")
+        )))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    if !response.status().is_success() {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        return Err(String::from_utf8(body.to_vec()).unwrap());
+    }
+
+    let cookie = response.headers()[SET_COOKIE].to_str().unwrap();
+    Ok(cookie.split(';').next().unwrap().to_string())
+}
+
+/// Adds a user with an API token, returning the user's id and the token.
+async fn api_token(db: &Pool, username: &str, role: Role, scopes: &[TokenScope]) -> (i64, String) {
+    let username = username.to_string();
+    let scopes = scopes.to_vec();
+
+    db.write(move |db| {
+        let user_id = create_user(db, &username, "no hash", role)?;
+        let token = create_api_token(db, user_id, "test", &scopes)?;
+
+        Ok::<_, rusqlite::Error>((user_id, token))
+    })
+    .await
+    .unwrap()
+}
+
+/// Posts `form` to `uri` with the session `cookie`.
+async fn post(app: &Router, uri: &str, cookie: &str, form: &str) -> (StatusCode, String) {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(COOKIE, cookie)
+        .body(Body::from(form.to_string()))
+        .unwrap();
+
+    send(app, request).await
+}
+
+/// Sends `body` to `uri` with the API `token`, as `content_type`.
+async fn send_with_token(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    token: &str,
+    content_type: &str,
+    body: &str,
+) -> (StatusCode, String) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .headers_mut()
+        .typed_insert(Authorization::bearer(token).unwrap());
+
+    send(app, request).await
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, String) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// The title of the recipe shared with `token`, as anyone would see it.
+async fn shared_title(app: &Router, token: &str) -> Option<String> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(GetSharedRecipe::PATH)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(format!("token={token}")))
+        .unwrap();
+
+    let (status, body) = send(app, request).await;
+    assert_eq!(StatusCode::OK, status, "{body}");
+
+    let shared: serde_json::Value = serde_json::from_str(&body).unwrap();
+    shared[1]["title"].as_str().map(str::to_string)
+}
+
+/// Adds a recipe and shares it, returning its id and the link.
+async fn share_recipe(app: &Router, cookie: &str) -> (i64, ShareLink) {
+    let (_, body) = post(
+        app,
+        NewRecipe::PATH,
+        cookie,
+        "raw_recipe[title]=Pannenkoeken&raw_recipe[ingredients]=250+g+bloem\
+         &raw_recipe[instructions]=Bakken",
+    )
+    .await;
+    let recipe_id: i64 = body.parse().unwrap();
+
+    let (_, body) = post(
+        app,
+        CreateShareLink::PATH,
+        cookie,
+        &format!("recipe_id={recipe_id}"),
+    )
+    .await;
+
+    (recipe_id, serde_json::from_str(&body).unwrap())
+}
+
+#[tokio::test]
+async fn apps_have_their_own_lockouts() {
+    let (locked, _) = app(strict()).await;
+    let (other, _) = app(strict()).await;
+
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        get_as(&locked, "/api/v1/recipes", "anna", "wrong").await
+    );
+    assert_eq!(
+        StatusCode::TOO_MANY_REQUESTS,
+        get_as(&locked, "/api/v1/recipes", "anna", PASSWORD).await
+    );
+
+    assert_eq!(
+        StatusCode::OK,
+        get_as(&other, "/api/v1/recipes", "anna", PASSWORD).await
+    );
+}
+
+#[tokio::test]
+async fn basic_auth_is_only_remembered_for_the_same_password() {
+    let (app, db) = app(strict()).await;
+
+    // Every request sends the password, they're not each a login
+    for _ in 0..3 {
+        assert_eq!(
+            StatusCode::OK,
+            get_as(&app, "/api/v1/recipes", "anna", PASSWORD).await
+        );
+    }
+
+    db.write(|db| {
+        let anna = list_users(db)?.remove(0);
+        set_password_hash(db, anna.id, &hash_password("battery staple"), None)
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        get_as(&app, "/api/v1/recipes", "anna", PASSWORD).await
+    );
+}
+
+#[tokio::test]
+async fn viewers_cant_change_plans_or_shopping_lists() {
+    let (app, db) = app(strict()).await;
+    let viewer = session(&db, "vera", Role::Viewer).await;
+    let editor = session(&db, "eddie", Role::Editor).await;
+
+    for (uri, form) in [
+        (AddMeal::PATH, "date=2026-10-19&slot=diner&recipe_id=1"),
+        (MoveMeal::PATH, "meal_id=1&date=2026-10-20&slot=lunch"),
+        (RemoveMeal::PATH, "meal_id=1"),
+        (AutofillWeek::PATH, "week=2026-10-19&slot=diner"),
+        (AddToShoppingList::PATH, "recipe_id=1"),
+        (AddPlanToShoppingList::PATH, "week=2026-10-19"),
+        (RemoveFromShoppingList::PATH, "entry_id=1"),
+        (ClearShoppingList::PATH, ""),
+    ] {
+        let (status, body) = post(&app, uri, &viewer, form).await;
+        assert_eq!(StatusCode::FORBIDDEN, status, "{uri}");
+        assert!(body.contains("This needs the editor role"), "{uri}: {body}");
+
+        let (_, body) = post(&app, uri, &editor, form).await;
+        assert!(
+            !body.contains("This needs the editor role"),
+            "{uri}: {body}"
+        );
+    }
+
+    // Ticking items off leaves the list as it is, viewers may do that
+    let (status, body) = post(
+        &app,
+        SetItemChecked::PATH,
+        &viewer,
+        "key=bloem&checked=true",
+    )
+    .await;
+    assert!(status.is_success(), "{body}");
+}
+
+#[tokio::test]
+async fn logging_in_starts_a_session() {
+    let (app, _) = app(strict()).await;
+
+    let cookie = login(&app, "anna", PASSWORD).await.unwrap();
+    assert!(cookie.starts_with(&format!("{SESSION_COOKIE}=")));
+
+    let response = get(&app, "/api/v1/recipes", Some(&cookie), "application/json").await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    for (username, password) in [("anna", "wrong horse"), ("bert", PASSWORD)] {
+        let err = login(&app, username, password).await.unwrap_err();
+        assert!(err.contains("Incorrect username or password"), "{err}");
+    }
+
+    // A made up session logs no one in
+    let response = get(
+        &app,
+        "/api/v1/recipes",
+        Some(&format!("{SESSION_COOKIE}=made-up")),
+        "application/json",
+    )
+    .await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+}
+
+#[tokio::test]
+async fn expired_sessions_are_refused() {
+    let (app, db) = app(strict()).await;
+    let cookie = session(&db, "vera", Role::Viewer).await;
+
+    let response = get(&app, "/api/v1/recipes", Some(&cookie), "application/json").await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    db.write(|db| {
+        db.execute(
+            "UPDATE sessions SET expires = ?1;",
+            (chrono::Utc::now().timestamp() - 1,),
+        )
+    })
+    .await
+    .unwrap();
+
+    let response = get(&app, "/api/v1/recipes", Some(&cookie), "application/json").await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let response = get(&app, "/", Some(&cookie), "text/html").await;
+    assert_eq!(StatusCode::SEE_OTHER, response.status());
+}
+
+#[tokio::test]
+async fn only_the_first_account_is_bootstrapped() {
+    let db = Pool::open(":memory:", 1).unwrap();
+
+    assert!(
+        db.write(|db| create_first_admin(db, "anna", PASSWORD))
+            .await
+            .unwrap()
+    );
+    assert!(
+        !db.write(|db| create_first_admin(db, "bert", PASSWORD))
+            .await
+            .unwrap()
+    );
+
+    let users = db.read(list_users).await.unwrap();
+    assert_eq!(1, users.len());
+    assert_eq!("anna", users[0].username);
+    assert_eq!(Role::Admin, users[0].role);
+}
+
+#[tokio::test]
+async fn requests_without_a_session_are_sent_to_log_in() {
+    let (app, _) = app(strict()).await;
+
+    // Browsers go to the login page, and come back afterwards
+    let response = get(&app, "/plan?week=2026-10-19", None, "text/html").await;
+    assert_eq!(StatusCode::SEE_OTHER, response.status());
+    assert_eq!(
+        "/login?next=/plan%3Fweek%3D2026-10-19",
+        response.headers()[LOCATION]
+    );
+
+    // The API and server functions just fail
+    let response = get(&app, "/api/v1/recipes", None, "application/json").await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(!response.headers().contains_key(WWW_AUTHENTICATE));
+
+    // Anything else asks for Basic auth
+    let response = get(&app, "/export.json", None, "*/*").await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+
+    let response = get(&app, "/login", None, "text/html").await;
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn api_tokens_are_stored_hashed() {
+    let db = Pool::open(":memory:", 1).unwrap();
+    let (user_id, token) = api_token(&db, "anna", Role::Admin, &[]).await;
+
+    let stored: String = db
+        .read(|db| db.query_row("SELECT token_hash FROM api_tokens;", (), |row| row.get(0)))
+        .await
+        .unwrap();
+    assert!(token.starts_with("nom_"));
+    assert_eq!(64, stored.len());
+    assert!(!stored.contains(&token[4..]));
+
+    let tokens = db
+        .read(move |db| list_api_tokens(db, user_id))
+        .await
+        .unwrap();
+    assert_eq!(1, tokens.len());
+    assert_eq!(None, tokens[0].last_used);
+}
+
+#[tokio::test]
+async fn api_tokens_log_in_until_revoked() {
+    let (app, db) = app(strict()).await;
+    let (user_id, token) = api_token(&db, "eddie", Role::Editor, &[]).await;
+    let recipes = |token: String| {
+        let app = app.clone();
+        async move {
+            send_with_token(&app, Method::GET, "/api/v1/recipes", &token, "", "")
+                .await
+                .0
+        }
+    };
+
+    assert_eq!(StatusCode::OK, recipes(token.clone()).await);
+
+    let tokens = db
+        .read(move |db| list_api_tokens(db, user_id))
+        .await
+        .unwrap();
+    let last_used = tokens[0].last_used;
+    assert!(last_used.is_some());
+
+    // Only written down again once it is a while ago
+    assert_eq!(StatusCode::OK, recipes(token.clone()).await);
+    let tokens = db
+        .read(move |db| list_api_tokens(db, user_id))
+        .await
+        .unwrap();
+    assert_eq!(last_used, tokens[0].last_used);
+
+    let token_id = tokens[0].id;
+    db.write(move |db| {
+        db.execute(
+            "UPDATE api_tokens SET last_used = 0 WHERE id = ?1;",
+            (token_id,),
+        )
+    })
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::OK, recipes(token.clone()).await);
+    let tokens = db
+        .read(move |db| list_api_tokens(db, user_id))
+        .await
+        .unwrap();
+    assert!(tokens[0].last_used.unwrap().timestamp() > 0);
+
+    db.write(move |db| delete_api_token(db, user_id, token_id))
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::UNAUTHORIZED, recipes(token).await);
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        recipes("nom_made_up".to_string()).await
+    );
+}
+
+#[tokio::test]
+async fn tokens_with_unknown_scopes_are_refused() {
+    let (app, db) = app(strict()).await;
+    let (_, token) = api_token(&db, "eddie", Role::Editor, &[TokenScope::Read]).await;
+
+    // E.g. made by a newer version. Dropping the scope would leave none,
+    // which is full access.
+    db.write(|db| db.execute("UPDATE api_tokens SET scopes = 'write';", ()))
+        .await
+        .unwrap();
+
+    let (status, _) = send_with_token(&app, Method::GET, "/api/v1/recipes", &token, "", "").await;
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+}
+
+#[test]
+fn trmnl_tokens_only_reach_the_display() {
+    let trmnl = TokenAuth {
+        id: 1,
+        scopes: vec![TokenScope::Trmnl],
+    };
+
+    for path in [
+        "/trmnl",
+        "/images/1/thumbnail",
+        "/pkg/nom.css",
+        "/favicon.ico",
+    ] {
+        assert!(trmnl.allows_path(path), "{path}");
+    }
+
+    for path in [
+        "/",
+        "/recipe/1",
+        "/api/v1/recipes",
+        "/api/add_meal",
+        "/trmnl/../",
+        // The router would resolve these to somewhere else
+        "/pkg/../recipe/1",
+        "/images/%2e%2e/api/v1/recipes",
+    ] {
+        assert!(!trmnl.allows_path(path), "{path}");
+    }
+
+    assert_eq!(Role::Viewer, trmnl.limit_role(Role::Admin));
+
+    let full = TokenAuth {
+        id: 2,
+        scopes: Vec::new(),
+    };
+    assert!(full.allows_path("/api/v1/recipes"));
+    assert_eq!(Role::Admin, full.limit_role(Role::Admin));
+}
+
+#[tokio::test]
+async fn api_tokens_do_what_their_scopes_allow() {
+    const FORM: &str = "application/x-www-form-urlencoded";
+    const JSON: &str = "application/json";
+    const RECIPE: &str = r#"{"title": "Pannenkoeken"}"#;
+
+    let (app, db) = app(strict()).await;
+    let (_, full) = api_token(&db, "eddie", Role::Editor, &[]).await;
+    let (_, read) = api_token(&db, "ruth", Role::Editor, &[TokenScope::Read]).await;
+    let (_, trmnl) = api_token(&db, "tim", Role::Editor, &[TokenScope::Trmnl]).await;
+
+    for (token, method, uri, body, expected) in [
+        (&full, Method::GET, "/api/v1/recipes", "", StatusCode::OK),
+        (
+            &full,
+            Method::POST,
+            "/api/v1/recipes",
+            RECIPE,
+            StatusCode::CREATED,
+        ),
+        (&read, Method::GET, "/api/v1/recipes", "", StatusCode::OK),
+        (
+            &read,
+            Method::POST,
+            "/api/v1/recipes",
+            RECIPE,
+            StatusCode::FORBIDDEN,
+        ),
+        (&trmnl, Method::GET, "/trmnl", "", StatusCode::OK),
+        (
+            &trmnl,
+            Method::GET,
+            "/api/v1/recipes",
+            "",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            &trmnl,
+            Method::GET,
+            "/export.json",
+            "",
+            StatusCode::FORBIDDEN,
+        ),
+    ] {
+        let (status, body) = send_with_token(&app, method, uri, token, JSON, body).await;
+        assert_eq!(expected, status, "{uri}: {body}");
+    }
+
+    // Not even a full token can manage tokens or passwords
+    for (uri, form) in [
+        (ListApiTokens::PATH, ""),
+        (CreateApiToken::PATH, "name=meer"),
+        (RevokeApiToken::PATH, "token_id=1"),
+        (SetPassword::PATH, "user_id=1&password=a+new+password"),
+    ] {
+        let (status, body) = send_with_token(&app, Method::POST, uri, &full, FORM, form).await;
+        assert!(!status.is_success(), "{uri}");
+        assert!(
+            body.contains("Not possible with an API token"),
+            "{uri}: {body}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn share_links_only_work_as_made() {
+    let (app, db) = app(strict()).await;
+    let editor = session(&db, "eddie", Role::Editor).await;
+
+    let (recipe_id, link) = share_recipe(&app, &editor).await;
+    let (_, other) = share_recipe(&app, &editor).await;
+
+    assert_eq!(
+        Some("Pannenkoeken"),
+        shared_title(&app, &link.token).await.as_deref()
+    );
+
+    // Tampered with
+    let (id, signature) = link.token.split_once('.').unwrap();
+    let (_, other_signature) = other.token.split_once('.').unwrap();
+    let flipped = if signature.ends_with('0') { "1" } else { "0" };
+
+    for token in [
+        format!("{id}.{}{flipped}", &signature[..signature.len() - 1]),
+        format!("{}.{signature}", other.id),
+        format!("{id}.{other_signature}"),
+        format!("{id}.{}", &signature[..8]),
+        id.to_string(),
+        "made-up".to_string(),
+    ] {
+        assert_eq!(None, shared_title(&app, &token).await, "{token}");
+    }
+
+    // Expired
+    let expired = db
+        .write(move |db| {
+            links::create(
+                db,
+                recipe_id,
+                None,
+                Some(chrono::Utc::now() - chrono::Duration::minutes(1)),
+            )
+        })
+        .await
+        .unwrap();
+    assert_eq!(None, shared_title(&app, &expired.token).await);
+
+    // Revoked
+    post(
+        &app,
+        RevokeShareLink::PATH,
+        &editor,
+        &format!("link_id={}", link.id),
+    )
+    .await;
+    assert_eq!(None, shared_title(&app, &link.token).await);
+    assert!(shared_title(&app, &other.token).await.is_some());
+}
+
+#[tokio::test]
+async fn share_links_unlock_nothing_else() {
+    let (app, db) = app(strict()).await;
+    let editor = session(&db, "eddie", Role::Editor).await;
+
+    let (recipe_id, link) = share_recipe(&app, &editor).await;
+
+    let response = get(&app, &link.path(), None, "text/html").await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(!response.headers().contains_key(SET_COOKIE));
+
+    let response = get(&app, &format!("/recipe/{recipe_id}"), None, "text/html").await;
+    assert_eq!(StatusCode::SEE_OTHER, response.status());
+
+    // The router would resolve these to the recipe
+    for path in [
+        format!("{}/../../recipe/{recipe_id}", link.path()),
+        format!("/s/%2E%2e/recipe/{recipe_id}"),
+        format!("/pkg/../recipe/{recipe_id}"),
+    ] {
+        let response = get(&app, &path, None, "text/html").await;
+        assert_eq!(StatusCode::SEE_OTHER, response.status(), "{path}");
+    }
+
+    // Only images of the shared recipe
+    let response = get(
+        &app,
+        &format!("{}/images/1/thumbnail", link.path()),
+        None,
+        "image/*",
+    )
+    .await;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "ssr")]
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use nom::db::{Pool, configure_writer};
+use rusqlite::Connection;
+use tokio::sync::oneshot;
+
+/// The directory of a test database, removed with the `-wal` and `-shm`
+/// files next to it when the test is done.
+struct TestDir(PathBuf);
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn pool(name: &str) -> (TestDir, Pool) {
+    let dir = std::env::temp_dir().join(format!("nom-db-{name}-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = TestDir(dir);
+
+    let mut conn = Connection::open(dir.0.join("nom.db")).unwrap();
+    configure_writer(&conn).unwrap();
+    nom::migrations::migrate(&mut conn, false).unwrap();
+
+    (dir, Pool::new(conn, 2).unwrap())
+}
+
+fn count_tags(conn: &Connection) -> i64 {
+    conn.query_row("SELECT count(*) FROM tags;", (), |row| row.get(0))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn readers_see_committed_writes() {
+    let (_dir, pool) = pool("commit");
+
+    pool.write(|db| db.execute("INSERT INTO tags (name) VALUES ('soep');", ()))
+        .await
+        .unwrap();
+
+    assert_eq!(1, pool.read(count_tags).await);
+
+    let journal_mode: String = pool
+        .read(|db| db.pragma_query_value(None, "journal_mode", |row| row.get(0)))
+        .await
+        .unwrap();
+
+    assert_eq!("wal", journal_mode);
+}
+
+#[tokio::test]
+async fn readers_refuse_to_write() {
+    let (_dir, pool) = pool("readonly");
+
+    let inserted = pool
+        .read(|db| db.execute("INSERT INTO tags (name) VALUES ('soep');", ()))
+        .await;
+
+    assert!(inserted.is_err());
+}
+
+#[tokio::test]
+async fn reads_go_on_during_a_write() {
+    let (_dir, pool) = pool("concurrent");
+    let pool = std::sync::Arc::new(pool);
+
+    let (started, writing) = oneshot::channel();
+    let (finish, finishing) = std::sync::mpsc::channel::<()>();
+
+    let writer = tokio::spawn({
+        let pool = pool.clone();
+
+        async move {
+            pool.write(move |db| {
+                let transaction = db.transaction().unwrap();
+                transaction
+                    .execute("INSERT INTO tags (name) VALUES ('soep');", ())
+                    .unwrap();
+                started.send(()).unwrap();
+
+                // Holds on to the write until the read is done
+                finishing.recv().unwrap();
+                transaction.commit().unwrap();
+            })
+            .await
+        }
+    });
+    writing.await.unwrap();
+
+    let count = tokio::time::timeout(Duration::from_secs(5), pool.read(count_tags))
+        .await
+        .expect("Reading waited for the write");
+
+    assert_eq!(0, count);
+
+    finish.send(()).unwrap();
+    writer.await.unwrap();
+
+    assert_eq!(1, pool.read(count_tags).await);
+}
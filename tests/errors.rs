@@ -0,0 +1,55 @@
+#![cfg(feature = "ssr")]
+
+use axum::http::StatusCode;
+use leptos::server_fn::error::{FromServerFnError, ServerFnErrorErr};
+use nom::error::NomError;
+
+#[test]
+fn errors_map_to_statuses() {
+    for (error, status) in [
+        (NomError::unknown_recipe(12), StatusCode::NOT_FOUND),
+        (
+            NomError::Validation("Recipes need a title".to_string()),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            NomError::Conflict("Name taken".to_string()),
+            StatusCode::CONFLICT,
+        ),
+        (
+            NomError::Stale("Recipe 12 was changed by someone else".to_string()),
+            StatusCode::CONFLICT,
+        ),
+        (NomError::NotLoggedIn, StatusCode::UNAUTHORIZED),
+        (
+            NomError::Unauthorized("Editors only".to_string()),
+            StatusCode::FORBIDDEN,
+        ),
+        (NomError::Database, StatusCode::INTERNAL_SERVER_ERROR),
+    ] {
+        assert_eq!(status, error.status(), "{error:?}");
+    }
+}
+
+#[test]
+fn errors_survive_the_trip_to_the_client() {
+    for error in [
+        NomError::unknown_recipe(12),
+        NomError::Validation("Recipes need a title".to_string()),
+        NomError::Database,
+    ] {
+        assert_eq!(error, NomError::de(error.ser()));
+    }
+
+    let failed = NomError::from_server_fn_error(ServerFnErrorErr::Request("offline".into()));
+
+    assert!(matches!(NomError::de(failed.ser()), NomError::ServerFn(_)));
+}
+
+#[test]
+fn database_errors_keep_the_details_to_the_server() {
+    let error = NomError::from(rusqlite::Error::QueryReturnedNoRows);
+
+    assert_eq!(NomError::Database, error);
+    assert_eq!("Database error", error.to_string());
+}
@@ -0,0 +1,195 @@
+#![cfg(feature = "ssr")]
+
+use nom::export::collection::{ImportError, export_collection, import_collection, parse_document};
+use nom::export::{ExportDocument, ExportedRecipe, FORMAT_VERSION, ImportMode};
+use nom::recipe::{Ingredient, RawRecipe, Recipe};
+use rusqlite::Connection;
+
+fn empty_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    nom::migrations::migrate(&mut conn, false).unwrap();
+    conn
+}
+
+fn ingredient(
+    quantity: Option<f64>,
+    unit: Option<&str>,
+    name: &str,
+    note: Option<&str>,
+) -> Ingredient {
+    Ingredient {
+        quantity,
+        unit: unit.map(str::to_string),
+        name: name.to_string(),
+        note: note.map(str::to_string),
+    }
+}
+
+fn recipe(title: &str) -> ExportedRecipe {
+    ExportedRecipe {
+        title: title.to_string(),
+        servings: Some(4),
+        prep_time: Some(10),
+        cook_time: None,
+        ingredients: vec![
+            ingredient(Some(200.0), Some("g"), "bloem", Some("gezeefd")),
+            ingredient(Some(0.5), Some("l"), "melk", None),
+            ingredient(Some(1.0 / 3.0), Some("tl"), "zout", None),
+            ingredient(Some(2.0), None, "eieren", None),
+            ingredient(None, None, "peper naar smaak", None),
+        ],
+        instructions: "Meng alles.\nBak in de pan.".to_string(),
+        tags: vec!["ontbijt".to_string(), "zoet".to_string()],
+    }
+}
+
+fn document(titles: &[&str]) -> ExportDocument {
+    ExportDocument {
+        version: FORMAT_VERSION,
+        recipes: titles.iter().map(|title| recipe(title)).collect(),
+    }
+}
+
+#[test]
+fn export_round_trips_through_json_and_database() {
+    let original = document(&["Pannenkoeken", "Wentelteefjes"]);
+
+    let mut conn = empty_db();
+    import_collection(&mut conn, original.clone(), ImportMode::Merge).unwrap();
+
+    let exported = export_collection(&conn).unwrap();
+    assert_eq!(original, exported);
+
+    let json = serde_json::to_vec_pretty(&exported).unwrap();
+    let parsed = parse_document(&json).unwrap();
+    assert_eq!(exported, parsed);
+
+    let mut other = empty_db();
+    import_collection(&mut other, parsed, ImportMode::Merge).unwrap();
+    assert_eq!(original, export_collection(&other).unwrap());
+}
+
+#[test]
+fn exported_recipes_round_trip_through_recipe_and_raw_recipe() {
+    let exported = recipe("Pannenkoeken");
+
+    let recipe = Recipe::from(exported.clone());
+    assert_eq!(exported, ExportedRecipe::from(recipe.clone()));
+
+    let raw = RawRecipe::from(recipe.clone());
+    assert_eq!(recipe, Recipe::from(raw));
+}
+
+#[test]
+fn raw_recipes_keep_quantities_exactly() {
+    let mut recipe = Recipe::from(recipe("Pannenkoeken"));
+    recipe.ingredients = vec![
+        ingredient(Some(0.333), Some("tl"), "zout", None),
+        ingredient(Some(1.005), Some("kg"), "bloem", None),
+        ingredient(Some(2.0 + 1.0 / 3.0), Some("el"), "suiker", None),
+        ingredient(Some(0.1 + 0.2), Some("l"), "melk", None),
+    ];
+
+    let raw = RawRecipe::from(recipe.clone());
+    assert_eq!(recipe, Recipe::from(raw));
+}
+
+#[test]
+fn commas_in_ingredient_names_become_notes() {
+    let mut recipe = Recipe::from(recipe("Pannenkoeken"));
+    recipe.ingredients = vec![ingredient(
+        Some(100.0),
+        Some("g"),
+        "kaas, belegen",
+        Some("geraspt"),
+    )];
+
+    let raw = RawRecipe::from(recipe);
+    assert_eq!(
+        vec![ingredient(
+            Some(100.0),
+            Some("g"),
+            "kaas",
+            Some("belegen, geraspt")
+        )],
+        Recipe::from(raw).ingredients
+    );
+}
+
+#[test]
+fn merge_skips_duplicate_titles() {
+    let mut conn = empty_db();
+
+    let summary = import_collection(
+        &mut conn,
+        document(&["Soep", "Stamppot"]),
+        ImportMode::Merge,
+    )
+    .unwrap();
+    assert_eq!(2, summary.imported);
+    assert!(summary.skipped.is_empty());
+
+    let summary = import_collection(
+        &mut conn,
+        document(&[" soep ", "Lasagne", "LASAGNE"]),
+        ImportMode::Merge,
+    )
+    .unwrap();
+    assert_eq!(1, summary.imported);
+    assert_eq!(vec![" soep ", "LASAGNE"], summary.skipped);
+
+    let titles: Vec<_> = export_collection(&conn)
+        .unwrap()
+        .recipes
+        .into_iter()
+        .map(|recipe| recipe.title)
+        .collect();
+    assert_eq!(vec!["Soep", "Stamppot", "Lasagne"], titles);
+}
+
+#[test]
+fn replace_removes_existing_recipes() {
+    let mut conn = empty_db();
+
+    import_collection(
+        &mut conn,
+        document(&["Soep", "Stamppot"]),
+        ImportMode::Merge,
+    )
+    .unwrap();
+
+    let summary = import_collection(
+        &mut conn,
+        document(&["Soep", "Lasagne"]),
+        ImportMode::Replace,
+    )
+    .unwrap();
+    assert_eq!(2, summary.imported);
+    assert!(summary.skipped.is_empty());
+
+    assert_eq!(
+        document(&["Soep", "Lasagne"]),
+        export_collection(&conn).unwrap()
+    );
+}
+
+#[test]
+fn newer_format_versions_are_refused() {
+    let mut newer = document(&["Soep"]);
+    newer.version = FORMAT_VERSION + 1;
+
+    let json = serde_json::to_vec(&newer).unwrap();
+
+    assert!(matches!(
+        parse_document(&json),
+        Err(ImportError::UnsupportedVersion { .. })
+    ));
+}
+
+#[test]
+fn invalid_documents_are_refused() {
+    assert!(matches!(
+        parse_document(b"{\"recipes\": []}"),
+        Err(ImportError::Json(_))
+    ));
+}
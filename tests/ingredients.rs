@@ -0,0 +1,205 @@
+use nom::recipe::{Ingredient, format_quantity, ingredient_matches, normalize_ingredient};
+
+fn ingredient(
+    quantity: Option<f64>,
+    unit: Option<&str>,
+    name: &str,
+    note: Option<&str>,
+) -> Ingredient {
+    Ingredient {
+        quantity,
+        unit: unit.map(str::to_string),
+        name: name.to_string(),
+        note: note.map(str::to_string),
+    }
+}
+
+/// Kept as a whole in the name, as it was entered.
+fn verbatim(line: &str) -> Ingredient {
+    ingredient(None, None, line, None)
+}
+
+#[test]
+fn ingredients_are_parsed() {
+    for (line, expected) in [
+        // Whole numbers and decimals, with either separator
+        (
+            "200 g bloem",
+            ingredient(Some(200.0), Some("g"), "bloem", None),
+        ),
+        ("0,5 l melk", ingredient(Some(0.5), Some("l"), "melk", None)),
+        (
+            "1.25 kg aardappelen",
+            ingredient(Some(1.25), Some("kg"), "aardappelen", None),
+        ),
+        // Fractions, vulgar and mixed
+        ("½ l melk", ingredient(Some(0.5), Some("l"), "melk", None)),
+        (
+            "1/3 tl zout",
+            ingredient(Some(1.0 / 3.0), Some("tl"), "zout", None),
+        ),
+        (
+            "1½ kg uien",
+            ingredient(Some(1.5), Some("kg"), "uien", None),
+        ),
+        (
+            "1 1/2 el suiker",
+            ingredient(Some(1.5), Some("el"), "suiker", None),
+        ),
+        // Unit aliases, case and periods, and units directly after the number
+        (
+            "2 El. olie",
+            ingredient(Some(2.0), Some("El."), "olie", None),
+        ),
+        (
+            "3 eetlepels azijn",
+            ingredient(Some(3.0), Some("eetlepels"), "azijn", None),
+        ),
+        (
+            "200g boter",
+            ingredient(Some(200.0), Some("g"), "boter", None),
+        ),
+        (
+            "2 teentjes knoflook",
+            ingredient(Some(2.0), Some("teentjes"), "knoflook", None),
+        ),
+        // Unitless items
+        ("3 eieren", ingredient(Some(3.0), None, "eieren", None)),
+        (
+            "peper en zout",
+            ingredient(None, None, "peper en zout", None),
+        ),
+        ("snufje zout", ingredient(None, None, "snufje zout", None)),
+        // Notes after the first comma
+        (
+            "200 g bloem, gezeefd",
+            ingredient(Some(200.0), Some("g"), "bloem", Some("gezeefd")),
+        ),
+        (
+            "1 ui, gesnipperd, fijn",
+            ingredient(Some(1.0), None, "ui", Some("gesnipperd, fijn")),
+        ),
+        ("2 uien,", ingredient(Some(2.0), None, "uien", None)),
+        (
+            "  1 l bouillon  ",
+            ingredient(Some(1.0), Some("l"), "bouillon", None),
+        ),
+    ] {
+        assert_eq!(expected, Ingredient::parse(line), "{line:?}");
+    }
+}
+
+#[test]
+fn unparseable_lines_are_kept_as_they_are() {
+    for line in [
+        // Ranges
+        "2-3 tenen knoflook",
+        "2–3 tenen knoflook",
+        // Division by zero
+        "1/0 kop melk",
+        // Nothing but a quantity, unit or note
+        "200 g",
+        "3",
+        ", gehakt",
+    ] {
+        assert_eq!(verbatim(line), Ingredient::parse(line), "{line:?}");
+    }
+}
+
+#[test]
+fn quantities_are_written_the_way_recipes_do() {
+    for (quantity, expected) in [
+        (2.0, "2"),
+        (200.0, "200"),
+        (0.5, "½"),
+        (1.5, "1½"),
+        (2.75, "2¾"),
+        (1.0 / 3.0, "⅓"),
+        (0.125, "⅛"),
+        (0.3, "0,3"),
+        (1.05, "1,05"),
+        (0.001, "0"),
+    ] {
+        assert_eq!(expected, format_quantity(quantity), "{quantity}");
+    }
+}
+
+#[test]
+fn written_ingredients_parse_back() {
+    for line in [
+        "200 g bloem, gezeefd",
+        "½ l melk",
+        "1¼ kg aardappelen",
+        "0,3 l room",
+        "3 eieren",
+        "peper en zout",
+    ] {
+        let parsed = Ingredient::parse(line);
+
+        assert_eq!(line, parsed.to_string());
+        assert_eq!(parsed, Ingredient::parse(&parsed.to_string()));
+    }
+}
+
+#[test]
+fn ingredient_names_are_normalised() {
+    for names in [
+        &["tomaat", "Tomaten", "tomaatjes"][..],
+        &["ei", "eieren", "eitjes"],
+        &["kaas", "kazen"],
+        &["druif", "druiven"],
+        &["ui", "uien", "uitjes"],
+        &["crème fraîche", "creme fraiche"],
+        &["paprika's", "paprikas"],
+        &["yoghurt", "joghurt"],
+    ] {
+        for name in names {
+            assert_eq!(
+                normalize_ingredient(names[0]),
+                normalize_ingredient(name),
+                "{name:?}"
+            );
+        }
+    }
+
+    // Singulars that only look like a plural
+    assert_eq!(vec!["sitroen"], normalize_ingredient("citroen"));
+    assert_eq!(vec!["ten"], normalize_ingredient("teen"));
+}
+
+#[test]
+fn ingredients_are_found_in_compounds() {
+    let matches = |ingredient: &str, term: &str| {
+        ingredient_matches(
+            &normalize_ingredient(ingredient),
+            &normalize_ingredient(term),
+        )
+    };
+
+    for (ingredient, term) in [
+        ("kipfilet", "kip"),
+        ("bosui", "ui"),
+        ("zilveruitjes", "ui"),
+        ("rode uien", "ui"),
+        ("kwarteleitjes", "ei"),
+        ("geitenkaas", "kaas"),
+        ("slagroom", "room"),
+        ("gerookte zalm", "zalm"),
+        ("kipfilet", "Kip filet"),
+    ] {
+        assert!(matches(ingredient, term), "{term:?} in {ingredient:?}");
+    }
+
+    for (ingredient, term) in [
+        ("prei", "ei"),
+        ("bladselderij", "ei"),
+        ("ijsbergsla", "ei"),
+        ("fruit", "ui"),
+        ("kruidnagel", "ui"),
+        ("kip", "kipfilet"),
+        ("zalm", "gerookte zalm"),
+        ("kaas", ""),
+    ] {
+        assert!(!matches(ingredient, term), "{term:?} in {ingredient:?}");
+    }
+}
@@ -0,0 +1,123 @@
+#![cfg(feature = "ssr")]
+
+use nom::migrations::{MigrationError, latest_version, migrate, schema_version};
+use nom::recipe::{Ingredient, load_recipe};
+use rusqlite::Connection;
+
+/// A database as it was before migrations existed, with one recipe.
+fn baseline_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS recipes (
+            id INTEGER PRIMARY KEY,
+            title TEXT,
+            instructions TEXT
+        );
+        CREATE TABLE IF NOT EXISTS ingredients (
+            id INTEGER PRIMARY KEY,
+            recipe INTEGER,
+            ingredient TEXT,
+            FOREIGN KEY(recipe) REFERENCES recipes(id)
+        );
+        INSERT INTO recipes (id, title, instructions)
+            VALUES (1, 'Pannenkoeken', 'Beslag maken\nBakken');
+        INSERT INTO ingredients (recipe, ingredient) VALUES
+            (1, '250 g bloem, gezeefd'),
+            (1, '½ l melk'),
+            (1, '2-3 eieren'),
+            (1, 'snufje zout');
+    ",
+    )
+    .unwrap();
+
+    conn
+}
+
+fn ingredient(
+    quantity: Option<f64>,
+    unit: Option<&str>,
+    name: &str,
+    note: Option<&str>,
+) -> Ingredient {
+    Ingredient {
+        quantity,
+        unit: unit.map(str::to_string),
+        name: name.to_string(),
+        note: note.map(str::to_string),
+    }
+}
+
+#[test]
+fn baseline_databases_keep_their_recipes() {
+    let mut conn = baseline_db();
+
+    let applied = migrate(&mut conn, false).unwrap();
+    assert_eq!(latest_version() as usize, applied.len());
+    assert_eq!(latest_version(), schema_version(&conn).unwrap());
+
+    let recipe = load_recipe(&conn, 1).unwrap().unwrap();
+    assert_eq!("Pannenkoeken", recipe.title);
+    assert_eq!("Beslag maken\nBakken", recipe.instructions);
+    assert_eq!(
+        vec![
+            ingredient(Some(250.0), Some("g"), "bloem", Some("gezeefd")),
+            ingredient(Some(0.5), Some("l"), "melk", None),
+            // Ranges can't be parsed and are kept as they were
+            ingredient(None, None, "2-3 eieren", None),
+            ingredient(None, None, "snufje zout", None),
+        ],
+        recipe.ingredients
+    );
+
+    // Up to date now, so nothing is left to apply
+    assert!(migrate(&mut conn, false).unwrap().is_empty());
+}
+
+#[test]
+fn dry_runs_change_nothing() {
+    let mut conn = baseline_db();
+
+    let applied = migrate(&mut conn, true).unwrap();
+    assert_eq!(latest_version() as usize, applied.len());
+    assert_eq!(0, schema_version(&conn).unwrap());
+
+    let raw: String = conn
+        .query_row(
+            "SELECT ingredient FROM ingredients ORDER BY id LIMIT 1;",
+            (),
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!("250 g bloem, gezeefd", raw);
+
+    let tables: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM sqlite_schema WHERE type = 'table';",
+            (),
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(2, tables);
+}
+
+#[test]
+fn newer_databases_are_refused() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate(&mut conn, false).unwrap();
+    conn.pragma_update(None, "user_version", latest_version() + 1)
+        .unwrap();
+
+    let Err(MigrationError::NewerSchema {
+        database,
+        supported,
+    }) = migrate(&mut conn, false)
+    else {
+        panic!("Migrated a database of a newer version");
+    };
+
+    assert_eq!(latest_version() + 1, database);
+    assert_eq!(latest_version(), supported);
+    assert_eq!(latest_version() + 1, schema_version(&conn).unwrap());
+}
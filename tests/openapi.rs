@@ -0,0 +1,134 @@
+#![cfg(feature = "ssr")]
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, Request, StatusCode};
+use nom::api::{ApiErrorBody, OPENAPI_PATH, PREFIX, openapi, router};
+use nom::db::Pool;
+use nom::store::{SqliteStore, Store};
+use tower::ServiceExt;
+use utoipa::openapi::path::{Operation, PathItem};
+
+const METHODS: [Method; 5] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::PATCH,
+];
+
+async fn send(method: Method, uri: &str) -> (StatusCode, Vec<u8>) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+
+    // An empty database of its own for each request
+    let store: Store = Arc::new(SqliteStore::new(Pool::open(":memory:", 1).unwrap()));
+    let response = router().with_state(store).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    (status, body.to_vec())
+}
+
+fn operation<'a>(item: &'a PathItem, method: &Method) -> Option<&'a Operation> {
+    match *method {
+        Method::GET => item.get.as_ref(),
+        Method::POST => item.post.as_ref(),
+        Method::PUT => item.put.as_ref(),
+        Method::DELETE => item.delete.as_ref(),
+        Method::PATCH => item.patch.as_ref(),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn served_document_is_the_generated_one() {
+    let (status, body) = send(Method::GET, OPENAPI_PATH).await;
+
+    assert_eq!(StatusCode::OK, status);
+
+    let served: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(serde_json::to_value(openapi()).unwrap(), served);
+    assert!(served["openapi"].as_str().unwrap().starts_with("3."));
+}
+
+#[tokio::test]
+async fn documented_operations_match_the_routes() {
+    let document = openapi();
+
+    assert!(!document.paths.paths.is_empty());
+
+    for (path, item) in &document.paths.paths {
+        assert!(path.starts_with(PREFIX), "{path} is outside {PREFIX}");
+
+        let uri = path.replace("{id}", "0");
+
+        for method in METHODS {
+            let documented = operation(item, &method).is_some();
+            let (status, body) = send(method.clone(), &uri).await;
+            let error = serde_json::from_slice::<ApiErrorBody>(&body)
+                .ok()
+                .map(|body| body.error);
+
+            if documented {
+                assert_ne!(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    status,
+                    "{method} {path} is documented but not routed"
+                );
+                assert_ne!(
+                    Some("Unknown API endpoint"),
+                    error.as_deref(),
+                    "{method} {path} is documented but not routed"
+                );
+            } else {
+                assert_eq!(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    status,
+                    "{method} {path} is routed but not documented"
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn every_documented_response_names_its_schema() {
+    let document = serde_json::to_value(openapi()).unwrap();
+    let schemas = document["components"]["schemas"].as_object().unwrap();
+
+    for name in [
+        "ApiRecipe",
+        "ApiRecipeInput",
+        "ApiErrorBody",
+        "Recipe",
+        "Ingredient",
+        "ListedRecipe",
+    ] {
+        assert!(
+            schemas.contains_key(name),
+            "{name} is missing from the schemas"
+        );
+    }
+
+    // Every reference has to point at a schema in the document
+    let text = document.to_string();
+
+    for reference in text.split("\"$ref\":\"#/components/schemas/").skip(1) {
+        let name = reference.split('"').next().unwrap();
+
+        assert!(
+            schemas.contains_key(name),
+            "{name} is referenced but missing"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+#![cfg(feature = "ssr")]
+
+use std::path::PathBuf;
+
+use nom::db::Pool;
+use nom::recipe::{RawRecipe, Recipe};
+use nom::store::{FileStore, RecipeStore, SqliteStore, Update};
+
+fn recipe_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nom-store-{name}-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+
+    dir
+}
+
+fn files(dir: &PathBuf) -> Vec<String> {
+    let mut names = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    names
+}
+
+fn recipe(title: &str, ingredients: &str) -> Recipe {
+    RawRecipe {
+        title: title.to_string(),
+        servings: Some(4),
+        prep_time: Some(10),
+        cook_time: None,
+        ingredients: ingredients.to_string(),
+        instructions: "Beslag maken\nBakken".to_string(),
+        tags: vec!["zoet".to_string()],
+    }
+    .into()
+}
+
+#[tokio::test]
+async fn recipes_are_kept_as_files() {
+    let dir = recipe_dir("files");
+    let store = FileStore::open(&dir, Pool::open(":memory:", 1).unwrap())
+        .await
+        .unwrap();
+
+    let id = store
+        .create(recipe("Pannenkoeken", "250 g bloem\n½ l melk"), None)
+        .await
+        .unwrap();
+
+    assert_eq!(vec![format!("{id}-pannenkoeken.cook")], files(&dir));
+
+    let text = std::fs::read_to_string(dir.join(format!("{id}-pannenkoeken.cook"))).unwrap();
+    assert!(text.contains(">> title: Pannenkoeken"));
+    assert!(text.contains("@melk{1/2%l}"));
+
+    let updated = store
+        .update(id, recipe("Flensjes", "250 g bloem"), Some(1), None)
+        .await
+        .unwrap();
+    let Update::Updated(updated) = updated else {
+        panic!("Not updated: {updated:?}");
+    };
+    assert_eq!("Flensjes", updated.title);
+    assert_eq!(Some(updated), store.get(id).await.unwrap());
+    assert_eq!(vec![format!("{id}-flensjes.cook")], files(&dir));
+
+    let found = store.search("flens".to_string(), Vec::new()).await.unwrap();
+    assert_eq!(
+        vec![id],
+        found.iter().map(|found| found.id).collect::<Vec<_>>()
+    );
+
+    assert!(store.delete(id).await.unwrap());
+    assert!(files(&dir).is_empty());
+    assert!(!store.delete(id).await.unwrap());
+}
+
+#[tokio::test]
+async fn recipes_stay_when_their_file_cant_be_removed() {
+    let dir = recipe_dir("delete");
+    let store = FileStore::open(&dir, Pool::open(":memory:", 1).unwrap())
+        .await
+        .unwrap();
+
+    let id = store
+        .create(recipe("Pannenkoeken", "250 g bloem"), None)
+        .await
+        .unwrap();
+
+    let moved = dir.with_extension("moved");
+    _ = std::fs::remove_dir_all(&moved);
+    std::fs::rename(&dir, &moved).unwrap();
+
+    assert!(store.delete(id).await.is_err());
+    assert!(store.get(id).await.unwrap().is_some());
+
+    std::fs::rename(&moved, &dir).unwrap();
+
+    assert!(store.delete(id).await.unwrap());
+    assert_eq!(None, store.get(id).await.unwrap());
+    assert!(files(&dir).is_empty());
+}
+
+#[tokio::test]
+async fn changed_files_are_read_when_opening() {
+    let dir = recipe_dir("sync");
+    let db = Pool::open(":memory:", 1).unwrap();
+
+    let store = FileStore::open(&dir, db.clone()).await.unwrap();
+    let kept = store
+        .create(recipe("Pannenkoeken", "250 g bloem"), None)
+        .await
+        .unwrap();
+    let removed = store
+        .create(recipe("Wafels", "250 g bloem"), None)
+        .await
+        .unwrap();
+    drop(store);
+
+    std::fs::write(
+        dir.join(format!("{kept}-pannenkoeken.cook")),
+        ">> title: Pannenkoeken\n\n@bloem{300%g}\n\nBakken\n",
+    )
+    .unwrap();
+    std::fs::remove_file(dir.join(format!("{removed}-wafels.cook"))).unwrap();
+    std::fs::write(dir.join("soep.cook"), "Snijd de @ui{1} en bak hem.\n").unwrap();
+
+    let store = FileStore::open(&dir, db).await.unwrap();
+
+    let pancakes = store.get(kept).await.unwrap().unwrap();
+    assert_eq!(Some(300.0), pancakes.ingredients[0].quantity);
+    assert_eq!(None, pancakes.servings);
+
+    // Written back rather than deleted, it may have gone missing by accident
+    assert_eq!("Wafels", store.get(removed).await.unwrap().unwrap().title);
+
+    let listed = store.list(Vec::new(), None, 0).await.unwrap();
+    assert_eq!(3, listed.len());
+
+    let soup = &listed[2];
+    assert_eq!("soep", soup.title);
+    assert_eq!(
+        vec![
+            format!("{kept}-pannenkoeken.cook"),
+            format!("{removed}-wafels.cook"),
+            format!("{}-soep.cook", soup.id)
+        ],
+        files(&dir)
+    );
+}
+
+#[tokio::test]
+async fn only_files_named_after_a_recipe_are_that_recipe() {
+    let dir = recipe_dir("names");
+    let db = Pool::open(":memory:", 1).unwrap();
+
+    let store = FileStore::open(&dir, db.clone()).await.unwrap();
+    let id = store
+        .create(recipe("Pannenkoeken", "250 g bloem"), None)
+        .await
+        .unwrap();
+    drop(store);
+
+    // Titles starting with a number, not meant as the id
+    std::fs::write(
+        dir.join(format!("{id}-gangen.cook")),
+        ">> title: Drie gangen\n\n@ui{1}\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("2024-kerstdiner.cook"), "@kalkoen{1}\n").unwrap();
+
+    let store = FileStore::open(&dir, db.clone()).await.unwrap();
+
+    assert_eq!("Pannenkoeken", store.get(id).await.unwrap().unwrap().title);
+    assert_eq!(None, store.get(2024).await.unwrap());
+
+    let titles = store
+        .list(Vec::new(), None, 0)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|listed| listed.title)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec!["Pannenkoeken", "Drie gangen", "2024-kerstdiner"],
+        titles
+    );
+    drop(store);
+
+    // A title changed in the file renames it, so it's found next time
+    std::fs::write(
+        dir.join(format!("{id}-pannenkoeken.cook")),
+        ">> title: Flensjes\n\n@bloem{250%g}\n",
+    )
+    .unwrap();
+
+    let store = FileStore::open(&dir, db.clone()).await.unwrap();
+    assert_eq!("Flensjes", store.get(id).await.unwrap().unwrap().title);
+    assert!(files(&dir).contains(&format!("{id}-flensjes.cook")));
+    drop(store);
+
+    let store = FileStore::open(&dir, db).await.unwrap();
+    assert_eq!(3, store.count(Vec::new()).await.unwrap());
+}
+
+#[tokio::test]
+async fn a_new_directory_gets_the_recipes_of_the_database() {
+    let dir = recipe_dir("new");
+    let db = Pool::open(":memory:", 1).unwrap();
+
+    let id = SqliteStore::new(db.clone())
+        .create(recipe("Pannenkoeken", "250 g bloem"), None)
+        .await
+        .unwrap();
+
+    let store = FileStore::open(&dir, db).await.unwrap();
+
+    assert_eq!(vec![format!("{id}-pannenkoeken.cook")], files(&dir));
+    assert_eq!("Pannenkoeken", store.get(id).await.unwrap().unwrap().title);
+}
@@ -0,0 +1,56 @@
+use nom::tags::{parse_tags_param, tags_href};
+
+/// Decodes a query parameter value, like the router does.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            decoded.push(u8::from_str_radix(&value[i + 1..i + 3], 16).unwrap());
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).unwrap()
+}
+
+#[test]
+fn tags_survive_the_home_page_href() {
+    for tags in [
+        vec!["soep".to_string()],
+        vec!["vis & schaaldieren".to_string(), "snel".to_string()],
+        vec!["#vega".to_string()],
+        vec!["kaas, eieren".to_string(), "50% korting".to_string()],
+        vec!["%2c".to_string(), "crème brûlée".to_string()],
+    ] {
+        let href = tags_href(&tags);
+        let param = href.strip_prefix("/?tags=").unwrap();
+
+        assert!(!param.contains(['&', '#', ' ', '=']), "{href}");
+
+        let mut expected = tags.clone();
+        expected.sort();
+
+        assert_eq!(
+            expected,
+            parse_tags_param(Some(percent_decode(param))),
+            "{href}"
+        );
+    }
+
+    assert_eq!("/", tags_href(&[]));
+}
+
+#[test]
+fn typed_tag_params_are_read() {
+    assert_eq!(
+        vec!["diner", "snel"],
+        parse_tags_param(Some("Snel, diner,,".to_string()))
+    );
+    assert!(parse_tags_param(None).is_empty());
+}
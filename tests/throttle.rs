@@ -0,0 +1,178 @@
+#![cfg(feature = "ssr")]
+
+use nom::auth::throttle::{LoginThrottle, Throttle, ThrottleConfig, keys};
+use nom::db::Pool;
+
+const NOW: i64 = 1_760_000_000;
+
+fn throttle(persist: bool) -> ThrottleConfig {
+    ThrottleConfig {
+        max_failures: 3,
+        lockout_secs: 60,
+        persist,
+    }
+}
+
+fn key(key: &str) -> Vec<String> {
+    vec![key.to_string()]
+}
+
+#[test]
+fn lockouts_start_after_too_many_failures() {
+    let mut throttle = Throttle::new(throttle(false));
+
+    for count in 1..3 {
+        assert_eq!(count, throttle.fail("user:anna", NOW).count);
+        assert_eq!(None, throttle.locked_for(&key("user:anna"), NOW));
+    }
+
+    throttle.fail("user:anna", NOW);
+    assert_eq!(Some(60), throttle.locked_for(&key("user:anna"), NOW));
+    assert_eq!(Some(1), throttle.locked_for(&key("user:anna"), NOW + 59));
+    assert_eq!(None, throttle.locked_for(&key("user:anna"), NOW + 60));
+
+    // Other keys aren't affected
+    assert_eq!(None, throttle.locked_for(&key("user:bert"), NOW));
+
+    // Logging in forgets the failures
+    assert!(throttle.forget("user:anna"));
+    assert_eq!(None, throttle.locked_for(&key("user:anna"), NOW));
+}
+
+#[test]
+fn lockouts_double_with_every_failure() {
+    let mut throttle = Throttle::new(throttle(false));
+
+    for _ in 0..2 {
+        throttle.fail("ip:10.0.0.1", NOW);
+    }
+
+    for lockout in [60, 120, 240, 480] {
+        let failures = throttle.fail("ip:10.0.0.1", NOW);
+        assert_eq!(NOW + lockout, failures.locked_until);
+    }
+
+    // Up to an hour
+    for _ in 0..20 {
+        throttle.fail("ip:10.0.0.1", NOW);
+    }
+    assert_eq!(Some(60 * 60), throttle.locked_for(&key("ip:10.0.0.1"), NOW));
+}
+
+#[test]
+fn failures_are_forgotten_after_a_day() {
+    let mut throttle = Throttle::new(throttle(false));
+
+    for _ in 0..3 {
+        throttle.fail("user:anna", NOW);
+    }
+
+    let failures = throttle.fail("user:anna", NOW + 24 * 60 * 60 + 1);
+    assert_eq!(1, failures.count);
+}
+
+#[test]
+fn attempts_are_counted_before_they_are_checked() {
+    let mut throttle = Throttle::new(throttle(false));
+    let keys = keys(Some([10, 0, 0, 1].into()), Some("anna"));
+
+    // Attempts that haven't finished count already, so a burst of them
+    // can't all get through before the first one fails
+    for _ in 0..3 {
+        assert!(throttle.attempt(&keys, NOW).is_ok());
+    }
+    assert_eq!(Err(60), throttle.attempt(&keys, NOW).map(|_| ()));
+
+    // Refused attempts don't lock out for longer
+    assert_eq!(Err(30), throttle.attempt(&keys, NOW + 30).map(|_| ()));
+}
+
+#[test]
+fn failures_are_counted_per_address_and_username() {
+    assert_eq!(
+        vec!["ip:10.0.0.1", "user:anna"],
+        keys(Some([10, 0, 0, 1].into()), Some("Anna"))
+    );
+    assert_eq!(vec!["ip:10.0.0.1"], keys(Some([10, 0, 0, 1].into()), None));
+    assert_eq!(vec!["user:anna"], keys(None, Some("anna")));
+
+    let mut throttle = Throttle::new(throttle(false));
+
+    for _ in 0..3 {
+        throttle.fail("ip:10.0.0.1", NOW);
+    }
+
+    // Guessing other usernames from the same address is locked out too
+    let other_user = keys(Some([10, 0, 0, 1].into()), Some("bert"));
+    assert!(throttle.locked_for(&other_user, NOW).is_some());
+
+    // As is the same username from other addresses
+    for _ in 0..3 {
+        throttle.fail("user:anna", NOW);
+    }
+    let other_address = keys(Some([10, 0, 0, 2].into()), Some("anna"));
+    assert!(throttle.locked_for(&other_address, NOW).is_some());
+
+    let elsewhere = keys(Some([10, 0, 0, 2].into()), Some("bert"));
+    assert_eq!(None, throttle.locked_for(&elsewhere, NOW));
+}
+
+#[tokio::test]
+async fn logging_in_only_forgets_the_username() {
+    let db = Pool::open(":memory:", 1).unwrap();
+    let throttle = LoginThrottle::new(throttle(false));
+    let ip = Some([10, 0, 0, 1].into());
+
+    // Guessing at bert's password, logging in as anna in between
+    for _ in 0..2 {
+        throttle
+            .record_failure(&db, &keys(ip, Some("bert")))
+            .await
+            .unwrap();
+    }
+    throttle
+        .attempt(&db, &keys(ip, Some("anna")))
+        .await
+        .unwrap();
+    throttle.record_success(&db, ip, "anna").await.unwrap();
+
+    assert_eq!(None, throttle.locked_for(&keys(ip, Some("anna"))));
+
+    // Only the successful attempt was taken back
+    throttle
+        .record_failure(&db, &keys(ip, Some("carla")))
+        .await
+        .unwrap();
+    assert!(throttle.locked_for(&keys(ip, None)).is_some());
+}
+
+#[tokio::test]
+async fn failures_can_be_kept_in_the_database() {
+    let db = Pool::open(":memory:", 1).unwrap();
+    let keys = keys(None, Some("anna"));
+
+    let before = LoginThrottle::new(throttle(true));
+    for _ in 0..3 {
+        before.record_failure(&db, &keys).await.unwrap();
+    }
+    assert!(before.locked_for(&keys).is_some());
+
+    // As after a restart
+    let after = LoginThrottle::new(throttle(true));
+    let loading = after.clone();
+    db.write(move |db| loading.load(db)).await.unwrap();
+    assert!(after.locked_for(&keys).is_some());
+
+    // Not without persisting
+    let forgetful = LoginThrottle::new(throttle(false));
+    let loading = forgetful.clone();
+    db.write(move |db| loading.load(db)).await.unwrap();
+    assert_eq!(None, forgetful.locked_for(&keys));
+
+    // Forgotten after logging in
+    after.record_success(&db, None, "anna").await.unwrap();
+    let restarted = LoginThrottle::new(throttle(true));
+    let loading = restarted.clone();
+    db.write(move |db| loading.load(db)).await.unwrap();
+    assert_eq!(None, restarted.locked_for(&keys));
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "ssr")]
+
+use std::io::{Cursor, Write};
+
+use image::ImageError;
+use nom::cooklang::format::{MAX_IMPORT_SIZE, read_zip};
+use nom::images::storage::resize;
+use zip::write::SimpleFileOptions;
+
+/// A GIF of a few bytes that claims to be `width` by `height` pixels.
+fn gif(width: u16, height: u16) -> Vec<u8> {
+    let [w0, w1] = width.to_le_bytes();
+    let [h0, h1] = height.to_le_bytes();
+
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend([w0, w1, h0, h1, 0x80, 0, 0]);
+    // Black and white colour table
+    gif.extend([0, 0, 0, 0xFF, 0xFF, 0xFF]);
+    gif.extend([0x2C, 0, 0, 0, 0, w0, w1, h0, h1, 0]);
+    gif.extend([0x02, 0x02, 0x44, 0x01, 0x00, 0x3B]);
+
+    gif
+}
+
+#[test]
+fn huge_images_are_refused_before_decoding() {
+    assert!(resize(&gif(1, 1)).is_ok());
+
+    assert!(matches!(
+        resize(&gif(u16::MAX, u16::MAX)),
+        Err(ImageError::Limits(_))
+    ));
+}
+
+fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+    for (name, contents) in files {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn zips_are_unpacked_up_to_a_limit() {
+    let soup = zip(&[("soep.cook", b"Snijd de @ui{1}.\n")]);
+    assert_eq!(
+        vec![("soep.cook".to_string(), "Snijd de @ui{1}.\n".to_string())],
+        read_zip(&soup).unwrap()
+    );
+
+    // Compresses to next to nothing
+    let half = vec![b' '; MAX_IMPORT_SIZE / 2 + 1];
+    let bomb = zip(&[("a.cook", &half), ("b.cook", &half)]);
+    assert!(bomb.len() < MAX_IMPORT_SIZE / 100);
+
+    assert!(read_zip(&bomb).is_err());
+}